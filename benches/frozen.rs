@@ -0,0 +1,58 @@
+use mtable::table::Table;
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+const ITERATIONS: usize = 200;
+
+fn build() -> Table<u32> {
+    let mut t = Table::new("abcdefghijklmnopqrstuvwxyz0123456789+-*/() ".to_string());
+    t.add("[abcdefghijklmnopqrstuvwxyz]+", 1).unwrap();
+    t.add("[0123456789]+", 2).unwrap();
+    t.add("+", 3).unwrap();
+    t.add("-", 4).unwrap();
+    t.add("*", 5).unwrap();
+    t.add("/", 6).unwrap();
+    t.add("(", 7).unwrap();
+    t.add(")", 8).unwrap();
+    t.add(" +", 9).unwrap();
+    t
+}
+
+fn measure<F: FnMut()>(name: &str, mut f: F) -> Duration {
+    f();
+    let start = Instant::now();
+    for _ in 0..ITERATIONS {
+        f();
+    }
+    let elapsed = start.elapsed();
+    println!("{name:<24} {:>12?} / iter", elapsed / ITERATIONS as u32);
+    elapsed
+}
+
+fn main() {
+    let table = build();
+    let frozen = table.freeze();
+
+    let input = "(alpha + 42) * beta - 7 / (gamma + 1234) ".repeat(1000);
+    let words: Vec<String> = (0..10_000).map(|i| format!("word{i}")).collect();
+
+    measure("table::lexer", || {
+        let count = table.lexer(black_box(&input)).unwrap().count();
+        black_box(count);
+    });
+    measure("frozen::lexer", || {
+        let count = frozen.lexer(black_box(&input)).unwrap().count();
+        black_box(count);
+    });
+
+    measure("table::get", || {
+        for w in &words {
+            black_box(table.get(black_box(w)).ok());
+        }
+    });
+    measure("frozen::get", || {
+        for w in &words {
+            black_box(frozen.get(black_box(w)).ok());
+        }
+    });
+}
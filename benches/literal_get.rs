@@ -0,0 +1,45 @@
+use mtable::table::Table;
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+const ITERATIONS: usize = 200;
+
+fn build(words: &[String], with_class: bool) -> Table<usize> {
+    let mut t = Table::new("abcdefghijklmnopqrstuvwxyz0123456789_".to_string());
+    for (i, w) in words.iter().enumerate() {
+        t.add(w, i).unwrap();
+    }
+    if with_class {
+        // same keys, but the table is no longer literal-only
+        t.add("_[0123456789]", usize::MAX).unwrap();
+    }
+    t
+}
+
+fn measure<F: FnMut()>(name: &str, mut f: F) -> Duration {
+    f();
+    let start = Instant::now();
+    for _ in 0..ITERATIONS {
+        f();
+    }
+    let elapsed = start.elapsed();
+    println!("{name:<24} {:>12?} / iter", elapsed / ITERATIONS as u32);
+    elapsed
+}
+
+fn main() {
+    let words: Vec<String> = (0..10_000).map(|i| format!("keyword{i}")).collect();
+    let literal = build(&words, false);
+    let mixed = build(&words, true);
+
+    measure("get literal-only", || {
+        for w in &words {
+            black_box(literal.get(black_box(w)).ok());
+        }
+    });
+    measure("get with classes", || {
+        for w in &words {
+            black_box(mixed.get(black_box(w)).ok());
+        }
+    });
+}
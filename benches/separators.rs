@@ -0,0 +1,45 @@
+use mtable::table::Table;
+use std::collections::HashSet;
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+const ITERATIONS: usize = 200;
+
+fn measure<F: FnMut()>(name: &str, mut f: F) -> Duration {
+    f();
+    let start = Instant::now();
+    for _ in 0..ITERATIONS {
+        f();
+    }
+    let elapsed = start.elapsed();
+    println!("{name:<24} {:>12?} / iter", elapsed / ITERATIONS as u32);
+    elapsed
+}
+
+fn main() {
+    let mut table = Table::new("abcdefghijklmnopqrstuvwxyz ".to_string());
+    table.add("[abcdefghijklmnopqrstuvwxyz]+", 1).unwrap();
+    table.add(" ", 2).unwrap();
+    // a long pattern crossing separators, never matched by the input
+    table
+        .add(
+            "[abcdefghijklmnopqrstuvwxyz]+ [abcdefghijklmnopqrstuvwxyz]+ x x",
+            3,
+        )
+        .unwrap();
+    let seps = HashSet::from([' ']);
+
+    let input = "lorem ipsum dolor sit amet ".repeat(2000);
+
+    measure("lexer", || {
+        let count = table.lexer(black_box(&input)).unwrap().count();
+        black_box(count);
+    });
+    measure("lexer_with_separators", || {
+        let count = table
+            .lexer_with_separators(black_box(&input), &seps)
+            .unwrap()
+            .count();
+        black_box(count);
+    });
+}
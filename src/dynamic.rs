@@ -0,0 +1,152 @@
+use crate::error::TableError;
+use crate::pattern::{Pattern, alternatives};
+use crate::table::Table;
+use std::{collections::BTreeSet, fmt::Debug};
+
+#[derive(Debug)]
+pub struct DynamicTable<T> {
+    patterns: Vec<(String, T)>,
+}
+
+impl<T> Default for DynamicTable<T> {
+    fn default() -> Self {
+        Self { patterns: vec![] }
+    }
+}
+
+impl<T: Debug + Clone> DynamicTable<T> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn add(&mut self, s: &str, value: T) -> &mut Self {
+        self.patterns.push((s.to_string(), value));
+        self
+    }
+
+    pub fn alphabet(&self) -> String {
+        let mut chars: BTreeSet<char> = BTreeSet::new();
+        for (s, _) in &self.patterns {
+            for pattern in alternatives(s, &[]) {
+                // an invalid pattern is reported by `build`
+                if let Ok(parsed) = Pattern::parse::<T>(pattern, &[]) {
+                    for atom in parsed.atoms() {
+                        chars.extend(&atom.chars);
+                        chars.extend(&atom.posix);
+                        for (start, end) in &atom.ranges {
+                            chars.extend(*start..=*end);
+                        }
+                    }
+                }
+            }
+        }
+        chars.into_iter().collect()
+    }
+
+    pub fn build(self) -> Result<Table<T>, TableError<T>> {
+        let mut table = Table::new(self.alphabet());
+        for (pattern, value) in self.patterns {
+            table.add(&pattern, value)?;
+        }
+        Ok(table)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn dynamic_infers_alphabet() {
+        let mut d = DynamicTable::new();
+        d.add("get", "GET")
+            .add("put", "PUT")
+            .add("[0123456789]+", "NUM");
+
+        assert_eq!(d.alphabet(), "0123456789egptu");
+    }
+
+    #[test]
+    fn dynamic_build_and_lex() {
+        let mut d = DynamicTable::new();
+        d.add("get", "GET")
+            .add("put", "PUT")
+            .add("[0123456789]+", "NUM");
+        let t = d.build().unwrap();
+
+        assert_eq!(t.get("get").unwrap(), Some(&"GET"));
+        assert_eq!(t.get("42").unwrap(), Some(&"NUM"));
+
+        let tokens: Vec<_> = t
+            .lexer("get42put")
+            .unwrap()
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert_eq!(
+            tokens,
+            vec![(&"GET", "get"), (&"NUM", "42"), (&"PUT", "put")]
+        );
+    }
+
+    #[test]
+    fn dynamic_infers_range_and_lexes() {
+        let mut d = DynamicTable::new();
+        d.add("get", "GET").add("put", "PUT").add("[0-9]+", "NUM");
+
+        // the range contributes its chars, not `-`
+        assert_eq!(d.alphabet(), "0123456789egptu");
+        let t = d.build().unwrap();
+        let tokens: Vec<_> = t
+            .lexer("put7get190")
+            .unwrap()
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert_eq!(
+            tokens,
+            vec![
+                (&"PUT", "put"),
+                (&"NUM", "7"),
+                (&"GET", "get"),
+                (&"NUM", "190")
+            ]
+        );
+    }
+
+    #[test]
+    fn dynamic_plus_operator_and_literal() {
+        let mut d = DynamicTable::new();
+        d.add("a+", 1).add("+b", 2);
+
+        // `+` after an atom is an operator, at the start it is a literal
+        assert_eq!(d.alphabet(), "+ab");
+        let t = d.build().unwrap();
+        assert_eq!(t.get("aaa").unwrap(), Some(&1));
+        assert_eq!(t.get("+b").unwrap(), Some(&2));
+    }
+
+    #[test]
+    fn dynamic_capture_syntax_not_in_alphabet() {
+        let mut d = DynamicTable::new();
+        d.add("(?<sign>[+-])1", 1).add("(", 2);
+
+        assert_eq!(d.alphabet(), "(+-1");
+    }
+
+    #[test]
+    fn dynamic_alternation_not_in_alphabet() {
+        let mut d = DynamicTable::new();
+        d.add("yes|no", true).add("[|]", false);
+        assert_eq!(d.alphabet(), "enosy|");
+    }
+
+    #[test]
+    fn dynamic_build_reports_errors() {
+        let mut d = DynamicTable::new();
+        d.add("[abc", 1);
+        assert!(matches!(d.build(), Err(TableError::UnclosedClass)));
+
+        let mut d = DynamicTable::new();
+        d.add("héllo", 1);
+        assert!(matches!(d.build(), Err(TableError::InvalidString(_))));
+    }
+}
@@ -6,7 +6,17 @@ pub enum TableError<T> {
     InvalidInput(char),
     AmbiguousPattern(char),
     InvalidRange,
+    UnclosedClass,
+    EmptyClass,
+    UnclosedGroup,
+    AlphabetMismatch,
+    UnknownPosixClass(String),
     ValueAlreadyDefined { current: T, requested: T },
+    ConflictingValues(Vec<(String, T)>),
+    NodeBudgetExceeded { budget: usize },
+    PatternTooLarge { limit: usize },
+    InternalValues(Vec<(String, T)>),
+    StaleHandle,
 }
 
 impl<T: Debug> std::fmt::Display for TableError<T> {
@@ -14,7 +24,12 @@ impl<T: Debug> std::fmt::Display for TableError<T> {
         match self {
             TableError::InvalidInput(ch) => write!(f, "Invalid input character: '{}'", ch),
             TableError::InvalidString(s) => write!(f, "Invalid string (non-ASCII): '{}'", s),
-            TableError::InvalidRange => write!(f, "Invalid range: unclosed or empty bracket"),
+            TableError::InvalidRange => write!(f, "Invalid range in character class"),
+            TableError::UnclosedClass => write!(f, "Unclosed character class: missing ']'"),
+            TableError::EmptyClass => write!(f, "Empty character class"),
+            TableError::UnclosedGroup => write!(f, "Unclosed group: missing '>' or ')'"),
+            TableError::AlphabetMismatch => write!(f, "Tables have different alphabets"),
+            TableError::UnknownPosixClass(name) => write!(f, "Unknown POSIX class: '{name}'"),
             TableError::ValueAlreadyDefined { current, requested } => {
                 write!(
                     f,
@@ -23,6 +38,29 @@ impl<T: Debug> std::fmt::Display for TableError<T> {
                 )
             }
             TableError::AmbiguousPattern(ch) => write!(f, "Ambiguous pattern found: '{ch}'"),
+            TableError::NodeBudgetExceeded { budget } => {
+                write!(f, "Node budget exceeded: at most {budget} nodes allowed")
+            }
+            TableError::StaleHandle => {
+                write!(f, "Stale handle: the table was pruned or restored since")
+            }
+            TableError::PatternTooLarge { limit } => {
+                write!(f, "Pattern too large: more than {limit} transitions")
+            }
+            TableError::InternalValues(values) => {
+                write!(f, "Values inside the pattern:")?;
+                for (key, value) in values {
+                    write!(f, " {key:?}={value:?}")?;
+                }
+                Ok(())
+            }
+            TableError::ConflictingValues(conflicts) => {
+                write!(f, "Conflicting values:")?;
+                for (key, value) in conflicts {
+                    write!(f, " {key:?}={value:?}")?;
+                }
+                Ok(())
+            }
         }
     }
 }
@@ -34,6 +72,8 @@ pub enum LexerError {
     InvalidString(String),
     UnknownChar { char: char, position: usize },
     UnexpectedEnd { position: usize }, // se ti serve
+    InputTooLarge { len: usize, max: usize },
+    Io(String),
 }
 
 impl Display for LexerError {
@@ -46,6 +86,10 @@ impl Display for LexerError {
             LexerError::UnexpectedEnd { position } => {
                 write!(f, "Unexpected end at position {position}")
             }
+            LexerError::InputTooLarge { len, max } => {
+                write!(f, "Input too large: {len} bytes, at most {max} allowed")
+            }
+            LexerError::Io(e) => write!(f, "I/O error: {e}"),
         }
     }
 }
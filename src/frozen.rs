@@ -0,0 +1,276 @@
+use crate::error::{LexerError, TableError};
+use crate::table::byte_lookup;
+use std::fmt::Debug;
+
+#[derive(Debug)]
+pub struct FrozenTable<T> {
+    alphabet: String,
+    lookup: [Option<usize>; 256],
+    transitions: Vec<Option<usize>>,
+    values: Vec<Option<T>>,
+    // values emitted only by a match reaching the end of input
+    eof_only: Vec<bool>,
+    // ends of skip patterns, consumed without a token
+    skip: Vec<bool>,
+    fallback: Option<T>,
+}
+
+impl<T: Debug + Clone> FrozenTable<T> {
+    pub(crate) fn new(
+        alphabet: String,
+        transitions: Vec<Option<usize>>,
+        values: Vec<Option<T>>,
+        eof_only: Vec<bool>,
+        skip: Vec<bool>,
+        fallback: Option<T>,
+    ) -> Self {
+        Self {
+            lookup: byte_lookup(&alphabet),
+            alphabet,
+            transitions,
+            values,
+            eof_only,
+            skip,
+            fallback,
+        }
+    }
+
+    #[inline]
+    fn position(&self, ch: u8) -> Option<usize> {
+        self.lookup[ch as usize]
+    }
+
+    #[inline]
+    fn next_node(&self, node: usize, pos: usize) -> Option<usize> {
+        self.transitions[node * self.alphabet.len() + pos]
+    }
+
+    pub fn get(&self, s: &str) -> Result<Option<&T>, TableError<T>> {
+        if !s.is_ascii() {
+            return Err(TableError::InvalidString(s.to_string()));
+        }
+        let mut current: usize = 0;
+        for ch in s.bytes() {
+            let pos = self
+                .position(ch)
+                .ok_or(TableError::<T>::InvalidInput(ch as char))?;
+            match self.next_node(current, pos) {
+                Some(next) => current = next,
+                None => return Ok(None),
+            }
+        }
+        Ok(self.values[current].as_ref())
+    }
+
+    pub fn lexer<'a>(&'a self, s: &'a str) -> Result<FrozenIterator<'a, T>, LexerError> {
+        if !s.is_ascii() {
+            return Err(LexerError::InvalidString(s.to_string()));
+        }
+        Ok(FrozenIterator {
+            table: self,
+            input: s,
+            index: 0,
+        })
+    }
+}
+
+pub struct FrozenIterator<'a, T> {
+    table: &'a FrozenTable<T>,
+    input: &'a str,
+    index: usize,
+}
+
+impl<'a, T: Debug + Clone> FrozenIterator<'a, T> {
+    // The longest match at the current index: the value (`None` for a skip
+    // pattern) and the end offset.
+    fn next_match(&self) -> Result<(Option<&'a T>, usize), LexerError> {
+        let bytes = self.input.as_bytes();
+        let mut node_id = 0;
+        let mut progress = self.index;
+        let mut last_match = None;
+        while progress < bytes.len() {
+            let ch = bytes[progress];
+            let pos = self.table.position(ch).ok_or(LexerError::UnknownChar {
+                char: ch as char,
+                position: progress,
+            })?;
+            match self.table.next_node(node_id, pos) {
+                Some(next) => {
+                    let value = self.table.values[next].as_ref();
+                    if (value.is_some() || self.table.skip[next])
+                        && (!self.table.eof_only[next] || progress + 1 == bytes.len())
+                    {
+                        last_match = Some((value, progress + 1));
+                    }
+                    progress += 1;
+                    node_id = next;
+                }
+                None => {
+                    if progress == self.index {
+                        last_match = self
+                            .table
+                            .fallback
+                            .as_ref()
+                            .map(|value| (Some(value), progress + 1));
+                    }
+                    break;
+                }
+            }
+        }
+        last_match.ok_or(LexerError::UnexpectedEnd {
+            position: self.index,
+        })
+    }
+}
+
+impl<'a, T: Debug + Clone> Iterator for FrozenIterator<'a, T> {
+    type Item = Result<(&'a T, &'a str), LexerError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        // skip matches are consumed until a token or an error
+        while self.index < self.input.len() {
+            match self.next_match() {
+                Ok((Some(value), end)) => {
+                    let content = &self.input[self.index..end];
+                    self.index = end;
+                    return Some(Ok((value, content)));
+                }
+                Ok((None, end)) => self.index = end,
+                Err(e) => {
+                    self.index = self.input.len();
+                    return Some(Err(e));
+                }
+            }
+        }
+        None
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (0, Some(self.input.len() - self.index))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::table::Table;
+
+    use super::*;
+
+    fn operators() -> Table<&'static str> {
+        let mut t = Table::new("0123456789+-*/=<>".to_string());
+        t.add("[0123456789]+", "num").unwrap();
+        t.add("+", "add").unwrap();
+        t.add("-", "sub").unwrap();
+        t.add("*", "mul").unwrap();
+        t.add("/", "div").unwrap();
+        t.add("=", "eq").unwrap();
+        t.add("==", "eqeq").unwrap();
+        t.add("<", "lt").unwrap();
+        t.add("<=", "le").unwrap();
+        t.add("<<", "shl").unwrap();
+        t
+    }
+
+    #[test]
+    fn frozen_get_matches_table() {
+        let t = operators();
+        let f = t.freeze();
+
+        for input in [
+            "", "1", "123", "+", "==", "=", "<=", "<<", "<<=", "12+", "9",
+        ] {
+            assert_eq!(f.get(input), t.get(input), "input {input:?}");
+        }
+    }
+
+    #[test]
+    fn frozen_get_errors_match_table() {
+        let t = operators();
+        let f = t.freeze();
+
+        assert_eq!(f.get("1a"), t.get("1a"));
+        assert!(matches!(f.get("1a"), Err(TableError::InvalidInput('a'))));
+        assert!(matches!(f.get("héllo"), Err(TableError::InvalidString(_))));
+    }
+
+    #[test]
+    fn frozen_lexer_matches_table() {
+        let t = operators();
+        let f = t.freeze();
+
+        for input in ["1+2*3", "10==10", "1<=2<<3", "<<=", "12@3", "1=a", "+-*/"] {
+            let expected: Result<Vec<_>, _> = t.lexer(input).unwrap().collect();
+            let actual: Result<Vec<_>, _> = f.lexer(input).unwrap().collect();
+            assert_eq!(actual, expected, "input {input:?}");
+        }
+    }
+
+    #[test]
+    fn frozen_lexer_non_ascii() {
+        let f = operators().freeze();
+        assert!(matches!(f.lexer("1+é"), Err(LexerError::InvalidString(_))));
+    }
+
+    #[test]
+    fn frozen_plus_self_loop() {
+        let mut t: Table<i32> = Table::new("ab".to_string());
+        t.add("a+b", 1).unwrap();
+        let f = t.freeze();
+
+        assert_eq!(f.get("ab").unwrap(), Some(&1));
+        assert_eq!(f.get("aaaab").unwrap(), Some(&1));
+        assert_eq!(f.get("a").unwrap(), None);
+    }
+
+    #[test]
+    fn frozen_fallback_matches_table() {
+        let mut t = operators();
+        t.add_fallback("other");
+        let f = t.freeze();
+
+        for input in ["1>2", "<>", ">>=", "1<"] {
+            let expected: Result<Vec<_>, _> = t.lexer(input).unwrap().collect();
+            let actual: Result<Vec<_>, _> = f.lexer(input).unwrap().collect();
+            assert_eq!(actual, expected, "input {input:?}");
+        }
+    }
+
+    #[test]
+    fn frozen_eof_only_matches_table() {
+        let mut t: Table<&str> = Table::new("foEND".to_string());
+        t.add("[fo]+", "word").unwrap();
+        t.add("[EN]", "letter").unwrap();
+        t.add_eof_only("END", "end").unwrap();
+        let f = t.freeze();
+
+        for input in ["fooEND", "ENDfo", "END", "EN", "fEND"] {
+            let expected: Result<Vec<_>, _> = t.lexer(input).unwrap().collect();
+            let actual: Result<Vec<_>, _> = f.lexer(input).unwrap().collect();
+            assert_eq!(actual, expected, "input {input:?}");
+        }
+    }
+
+    #[test]
+    fn frozen_skip_matches_table() {
+        let mut t: Table<&str> = Table::new("ab ".to_string());
+        t.add("[ab]+", "w").unwrap();
+        t.add_skip(" +").unwrap();
+        let f = t.freeze();
+
+        for input in ["ab ba", "  ab", "ab  ", "   ", "a b c"] {
+            let expected: Result<Vec<_>, _> = t.lexer(input).unwrap().collect();
+            let actual: Result<Vec<_>, _> = f.lexer(input).unwrap().collect();
+            assert_eq!(actual, expected, "input {input:?}");
+        }
+    }
+
+    #[test]
+    fn frozen_empty_alphabet() {
+        let mut t: Table<&str> = Table::new("".to_string());
+        t.add("", "empty").unwrap();
+        let f = t.freeze();
+
+        assert_eq!(f.get("").unwrap(), Some(&"empty"));
+        assert!(matches!(f.get("a"), Err(TableError::InvalidInput('a'))));
+    }
+}
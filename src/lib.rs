@@ -1,2 +1,7 @@
+pub mod dynamic;
 pub mod error;
+pub mod frozen;
+mod macros;
+pub mod pattern;
 pub mod table;
+pub mod unicode;
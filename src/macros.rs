@@ -0,0 +1,65 @@
+// Builds a `Table` from an alphabet and `pattern => value` pairs, panicking
+// on an invalid pattern as `Extend` does. For a table built once, wrap it in
+// a `static` with `std::sync::LazyLock`.
+#[macro_export]
+macro_rules! mtable {
+    ($alphabet:expr $(, $pattern:expr => $value:expr)* $(,)?) => {{
+        let mut table = $crate::table::Table::new(::std::string::String::from($alphabet));
+        table.extend([$(($pattern, $value)),*]);
+        table
+    }};
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::table::Table;
+    use std::sync::LazyLock;
+
+    #[derive(Debug, Clone, PartialEq)]
+    enum Op {
+        Plus,
+        Minus,
+        Num,
+    }
+
+    static OPS: LazyLock<Table<Op>> = LazyLock::new(|| {
+        mtable!("+-0123456789",
+            "+" => Op::Plus,
+            "-" => Op::Minus,
+            "[0-9]+" => Op::Num,
+        )
+    });
+
+    #[test]
+    fn macro_builds_operator_table() {
+        let tokens: Vec<_> = OPS
+            .lexer("12+3-4")
+            .unwrap()
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert_eq!(
+            tokens,
+            vec![
+                (&Op::Num, "12"),
+                (&Op::Plus, "+"),
+                (&Op::Num, "3"),
+                (&Op::Minus, "-"),
+                (&Op::Num, "4"),
+            ]
+        );
+    }
+
+    #[test]
+    fn macro_empty_and_single() {
+        let t: Table<i32> = mtable!("ab");
+        assert_eq!(t.get("a").unwrap(), None);
+        let t = mtable!("ab", "a+b" => 1);
+        assert_eq!(t.get("aab").unwrap(), Some(&1));
+    }
+
+    #[test]
+    #[should_panic(expected = "cannot add pattern \"c\"")]
+    fn macro_panics_on_invalid_pattern() {
+        let _ = mtable!("ab", "c" => 1);
+    }
+}
@@ -0,0 +1,810 @@
+use crate::error::TableError;
+use std::{iter::Peekable, ops::Range, str::Chars};
+
+// Characters with a meaning in pattern strings.
+pub(crate) const METACHARS: [char; 9] = ['[', ']', '+', '*', '?', '(', '{', '|', '\\'];
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum Repeat {
+    Once,
+    OneOrMore,
+    ZeroOrMore,
+    ZeroOrOne,
+}
+
+impl Repeat {
+    // The atom can match again after matching once.
+    pub(crate) fn loops(self) -> bool {
+        matches!(self, Repeat::OneOrMore | Repeat::ZeroOrMore)
+    }
+
+    // The atom can be skipped.
+    pub(crate) fn optional(self) -> bool {
+        matches!(self, Repeat::ZeroOrMore | Repeat::ZeroOrOne)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct Atom {
+    pub(crate) chars: Vec<char>,
+    pub(crate) repeat: Repeat,
+    pub(crate) capture: Option<String>,
+    // members from POSIX classes, kept only if they are in the alphabet
+    pub(crate) posix: Vec<char>,
+    // `\A`: every alphabet char
+    pub(crate) any: bool,
+    // a `.` outside a class: every alphabet char on a table with a wildcard
+    pub(crate) dot: bool,
+    // `a-z` inside a class, expanded by alphabet position
+    pub(crate) ranges: Vec<(char, char)>,
+    // `[^...]`: every alphabet char except the members
+    pub(crate) negated: bool,
+}
+
+impl Atom {
+    fn new(chars: Vec<char>) -> Self {
+        Self {
+            chars,
+            repeat: Repeat::Once,
+            capture: None,
+            posix: vec![],
+            any: false,
+            dot: false,
+            ranges: vec![],
+            negated: false,
+        }
+    }
+}
+
+// `(...)` followed by a repetition: the atoms in `atoms` repeat together.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct Group {
+    pub(crate) atoms: Range<usize>,
+    pub(crate) repeat: Repeat,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Pattern {
+    atoms: Vec<Atom>,
+    groups: Vec<Group>,
+}
+
+impl Pattern {
+    pub fn builder() -> PatternBuilder {
+        PatternBuilder { atoms: vec![] }
+    }
+
+    pub fn literal(s: &str) -> PatternBuilder {
+        Self::builder().literal(s)
+    }
+
+    pub fn class(chars: &[char]) -> PatternBuilder {
+        Self::builder().class(chars)
+    }
+
+    pub(crate) fn atoms(&self) -> &[Atom] {
+        &self.atoms
+    }
+
+    // Sorted, they never nest or overlap.
+    pub(crate) fn groups(&self) -> &[Group] {
+        &self.groups
+    }
+
+    // Turns every run of literal spaces into zero or more spaces.
+    pub(crate) fn with_flexible_spaces(self) -> Self {
+        let mut atoms: Vec<Atom> = vec![];
+        // where each atom lands, for the group bounds
+        let mut moved = vec![];
+        for atom in self.atoms {
+            moved.push(atoms.len());
+            let space = atom.chars == [' '] && atom.posix.is_empty() && atom.capture.is_none();
+            if space && atom.repeat == Repeat::Once {
+                if atoms
+                    .last()
+                    .is_some_and(|last| last.repeat == Repeat::ZeroOrMore && last.chars == [' '])
+                {
+                    continue;
+                }
+                atoms.push(Atom {
+                    repeat: Repeat::ZeroOrMore,
+                    ..atom
+                });
+            } else {
+                atoms.push(atom);
+            }
+        }
+        moved.push(atoms.len());
+        let groups = self
+            .groups
+            .into_iter()
+            .map(|group| Group {
+                atoms: moved[group.atoms.start]..moved[group.atoms.end],
+                ..group
+            })
+            .collect();
+        Self { atoms, groups }
+    }
+
+    // `[` opens a class, `(?<name>` opens a capture of a single atom, `\A`
+    // is any alphabet char, `+`, `*` and `?` after an atom repeat it one or
+    // more, zero or more, and zero or one times, `{n}`, `{n,m}` and `{n,}`
+    // after an atom repeat it a bounded number of times, `(...)` followed by
+    // one of these repeats as a whole, every other character, and every
+    // character in `literals`, is a literal. Groups don't nest: inside a
+    // group `(` and `|` are literals.
+    pub(crate) fn parse<T>(s: &str, literals: &[char]) -> Result<Self, TableError<T>> {
+        let mut atoms: Vec<Atom> = vec![];
+        let mut groups = vec![];
+        let mut iter = s.chars().peekable();
+        while let Some(ch) = iter.next() {
+            let start = atoms.len();
+            if ch == '(' && !literals.contains(&'(') && !opens_capture(&iter, literals) {
+                let checkpoint = iter.clone();
+                while let Some(next) = iter.next_if(|n| *n != ')') {
+                    parse_item(next, &mut iter, literals, &mut atoms)?;
+                }
+                if iter.next_if_eq(&')').is_some()
+                    && atoms.len() > start
+                    && let Some((min, max)) = quantifier(&mut iter, literals)?
+                {
+                    let body = atoms.split_off(start);
+                    for _ in 0..min {
+                        atoms.extend(body.iter().cloned());
+                    }
+                    let copies = match max {
+                        // the last copy loops
+                        None if min > 0 => {
+                            atoms.truncate(atoms.len() - body.len());
+                            vec![Repeat::OneOrMore]
+                        }
+                        None => vec![Repeat::ZeroOrMore],
+                        Some(max) => vec![Repeat::ZeroOrOne; max - min],
+                    };
+                    for repeat in copies {
+                        let start = atoms.len();
+                        atoms.extend(body.iter().cloned());
+                        groups.push(Group {
+                            atoms: start..atoms.len(),
+                            repeat,
+                        });
+                    }
+                    continue;
+                }
+                // not a repeated group: `(` is a literal
+                atoms.truncate(start);
+                iter = checkpoint;
+            }
+            parse_item(ch, &mut iter, literals, &mut atoms)?;
+        }
+        Ok(Self { atoms, groups })
+    }
+}
+
+// Parses an atom and its repetition, pushing the atoms it unrolls to.
+fn parse_item<T>(
+    ch: char,
+    iter: &mut Peekable<Chars>,
+    literals: &[char],
+    atoms: &mut Vec<Atom>,
+) -> Result<(), TableError<T>> {
+    let atom = if ch == '(' && opens_capture(iter, literals) {
+        iter.nth(1);
+        let mut name = String::new();
+        while let Some(next) = iter.next_if(|n| *n != '>') {
+            name.push(next);
+        }
+        let first = iter.nth(1).ok_or(TableError::UnclosedGroup)?;
+        let mut atom = parse_atom(first, iter, literals)?;
+        if iter.next() != Some(')') {
+            return Err(TableError::UnclosedGroup);
+        }
+        atom.capture = Some(name);
+        atom
+    } else {
+        parse_atom(ch, iter, literals)?
+    };
+    match quantifier(iter, literals)? {
+        None => atoms.push(atom),
+        Some((min, max)) => unroll(atoms, atom, min, max),
+    }
+    Ok(())
+}
+
+// The repetition after an atom or a group, as a minimum and an optional
+// maximum: `+` is `{1,}`, `*` is `{0,}` and `?` is `{0,1}`.
+fn quantifier<T>(
+    iter: &mut Peekable<Chars>,
+    literals: &[char],
+) -> Result<Option<(usize, Option<usize>)>, TableError<T>> {
+    if let Some(op) = iter.next_if(|n| is_operator(*n, literals)) {
+        return Ok(Some(match op {
+            '+' => (1, None),
+            '*' => (0, None),
+            _ => (0, Some(1)),
+        }));
+    }
+    let Some(bounds) = bounds_ahead(iter, literals) else {
+        return Ok(None);
+    };
+    iter.nth(bounds.len() + 1);
+    parse_bounds(&bounds)
+        .map(Some)
+        .ok_or(TableError::InvalidRange)
+}
+
+// The `n`, `n,` or `n,m` inside the braces that follow; any other `{` is a
+// literal.
+fn bounds_ahead(iter: &Peekable<Chars>, literals: &[char]) -> Option<String> {
+    let mut ahead = iter.clone();
+    if literals.contains(&'{') || ahead.next() != Some('{') {
+        return None;
+    }
+    let bounds: String = ahead.by_ref().take_while(|n| *n != '}').collect();
+    let digits = |s: &str| !s.is_empty() && s.bytes().all(|b| b.is_ascii_digit());
+    let numeric = match bounds.split_once(',') {
+        None => digits(&bounds),
+        Some((min, max)) => digits(min) && (max.is_empty() || digits(max)),
+    };
+    // `take_while` also consumed the `}`, if there was one
+    (numeric && iter.clone().nth(bounds.len() + 1) == Some('}')).then_some(bounds)
+}
+
+// The largest count in `{n,m}`: the atom is unrolled that many times.
+const MAX_BOUND: usize = 1000;
+
+// The `n`, `n,m` or `n,` inside the braces, as a minimum and an optional
+// maximum, each at most `MAX_BOUND`.
+fn parse_bounds(s: &str) -> Option<(usize, Option<usize>)> {
+    let number = |s: &str| {
+        (!s.is_empty() && s.bytes().all(|b| b.is_ascii_digit()))
+            .then(|| s.parse().ok())
+            .flatten()
+    };
+    let (min, max) = match s.split_once(',') {
+        None => (number(s)?, number(s)),
+        Some((min, "")) => (number(min)?, None),
+        Some((min, max)) => (number(min)?, Some(number(max)?)),
+    };
+    (min <= MAX_BOUND && max.is_none_or(|max| min <= max && max <= MAX_BOUND)).then_some((min, max))
+}
+
+// `min` copies of the atom, then `max - min` optional copies, or a loop
+// when there is no maximum.
+fn unroll(atoms: &mut Vec<Atom>, atom: Atom, min: usize, max: Option<usize>) {
+    let once = Atom {
+        repeat: Repeat::Once,
+        ..atom
+    };
+    atoms.extend(std::iter::repeat_n(once.clone(), min));
+    match max {
+        None if min == 0 => atoms.push(Atom {
+            repeat: Repeat::ZeroOrMore,
+            ..once
+        }),
+        None => atoms.last_mut().unwrap().repeat = Repeat::OneOrMore,
+        Some(max) => atoms.extend(std::iter::repeat_n(
+            Atom {
+                repeat: Repeat::ZeroOrOne,
+                ..once
+            },
+            max - min,
+        )),
+    }
+}
+
+// A repetition operator, when not configured as a literal.
+fn is_operator(ch: char, literals: &[char]) -> bool {
+    "+*?".contains(ch) && !literals.contains(&ch)
+}
+
+fn opens_capture(iter: &Peekable<Chars>, literals: &[char]) -> bool {
+    let mut ahead = iter.clone();
+    !literals.contains(&'(') && ahead.next() == Some('?') && ahead.next() == Some('<')
+}
+
+fn parse_atom<T>(
+    ch: char,
+    iter: &mut Peekable<Chars>,
+    literals: &[char],
+) -> Result<Atom, TableError<T>> {
+    if ch == '\\' && !literals.contains(&'\\') && iter.next_if_eq(&'A').is_some() {
+        let mut atom = Atom::new(vec![]);
+        atom.any = true;
+        return Ok(atom);
+    }
+    if ch == '.' && !literals.contains(&'.') {
+        let mut atom = Atom::new(vec![ch]);
+        atom.dot = true;
+        return Ok(atom);
+    }
+    if ch != '[' || literals.contains(&'[') {
+        return Ok(Atom::new(vec![ch]));
+    }
+    let mut atom = Atom::new(vec![]);
+    // a lone `[^]` is the literal `^`
+    if !literals.contains(&'^') && iter.peek() == Some(&'^') {
+        let mut ahead = iter.clone();
+        ahead.next();
+        if ahead
+            .peek()
+            .is_some_and(|n| *n != ']' || literals.contains(n))
+        {
+            iter.next();
+            atom.negated = true;
+        }
+    }
+    // a leading `]` is a member when another `]` closes the class
+    if !literals.contains(&']')
+        && iter.peek() == Some(&']')
+        && iter.clone().skip(1).any(|n| n == ']')
+    {
+        iter.next();
+        atom.chars.push(']');
+    }
+    while let Some(next) = iter.next_if(|n| *n != ']' || literals.contains(n)) {
+        if next == '[' && iter.next_if_eq(&':').is_some() {
+            let mut name = String::new();
+            while let Some(c) = iter.next_if(|n| *n != ':' && *n != ']') {
+                name.push(c);
+            }
+            if iter.next() != Some(':') || iter.next() != Some(']') {
+                return Err(TableError::UnknownPosixClass(name));
+            }
+            let members = posix_class(&name).ok_or(TableError::UnknownPosixClass(name))?;
+            atom.posix.extend(members);
+        } else if let Some(end) = range_end(next, iter, literals) {
+            atom.ranges.push((next, end));
+        } else {
+            atom.chars.push(next);
+        }
+    }
+    if iter.next() != Some(']') {
+        return Err(TableError::UnclosedClass);
+    }
+    Ok(atom)
+}
+
+// Consumes `-end` after `start` when it forms a range; a `-` before the
+// closing `]` is a literal.
+fn range_end(start: char, iter: &mut Peekable<Chars>, literals: &[char]) -> Option<char> {
+    if start == '-' || literals.contains(&'-') || iter.peek() != Some(&'-') {
+        return None;
+    }
+    let mut ahead = iter.clone();
+    ahead.next();
+    match ahead.next() {
+        Some(end) if end != ']' || literals.contains(&']') => {
+            iter.next();
+            iter.next();
+            Some(end)
+        }
+        _ => None,
+    }
+}
+
+// The ASCII members of a POSIX class such as `[:alpha:]`.
+fn posix_class(name: &str) -> Option<Vec<char>> {
+    let member: fn(&u8) -> bool = match name {
+        "alpha" => u8::is_ascii_alphabetic,
+        "digit" => u8::is_ascii_digit,
+        "alnum" => u8::is_ascii_alphanumeric,
+        "space" => |ch| b" \t\n\r\x0b\x0c".contains(ch),
+        "punct" => u8::is_ascii_punctuation,
+        _ => return None,
+    };
+    Some((0..128u8).filter(member).map(char::from).collect())
+}
+
+// Splits a pattern into the source text of its atoms, following the same
+// rules as `Pattern::parse`; an unclosed class takes the rest of the string.
+pub(crate) fn atom_sources<'a>(s: &'a str, literals: &[char]) -> Vec<&'a str> {
+    let mut sources = vec![];
+    let mut iter = s.char_indices().peekable();
+    while let Some((start, ch)) = iter.next() {
+        if let Some(len) = group_len(&s[start..], literals) {
+            sources.push(&s[start..start + len]);
+            while iter.next_if(|(i, _)| *i < start + len).is_some() {}
+            continue;
+        }
+        if ch == '(' && !literals.contains(&'(') && s[start..].starts_with("(?<") {
+            while iter.next_if(|(_, n)| *n != ')').is_some() {}
+            let _ = iter.next();
+        } else if ch == '\\' && !literals.contains(&'\\') {
+            let _ = iter.next_if(|(_, n)| *n == 'A');
+        } else if ch == '[' && !literals.contains(&'[') {
+            if !literals.contains(&']')
+                && iter.peek().is_some_and(|(_, n)| *n == ']')
+                && iter.clone().skip(1).any(|(_, n)| n == ']')
+            {
+                let _ = iter.next();
+            }
+            while let Some((_, n)) = iter.next_if(|(_, n)| *n != ']' || literals.contains(n)) {
+                if n == '[' && iter.next_if(|(_, c)| *c == ':').is_some() {
+                    while iter.next_if(|(_, n)| *n != ']').is_some() {}
+                    let _ = iter.next();
+                }
+            }
+            let _ = iter.next();
+        }
+        if iter.next_if(|(_, n)| is_operator(*n, literals)).is_none()
+            && let Some(&(i, _)) = iter.peek()
+            && let Some(bounds) = bounds_ahead(&s[i..].chars().peekable(), literals)
+        {
+            iter.nth(bounds.len() + 1);
+        }
+        let end = iter.peek().map_or(s.len(), |(i, _)| *i);
+        sources.push(&s[start..end]);
+    }
+    sources
+}
+
+// The length of the repeated group at the start of `s`, if there is one:
+// the atoms after `(` up to a `)` with a repetition.
+fn group_len(s: &str, literals: &[char]) -> Option<usize> {
+    if !s.starts_with('(') || literals.contains(&'(') || s.starts_with("(?<") {
+        return None;
+    }
+    let mut len = 1;
+    for source in atom_sources(&s[1..], literals) {
+        len += source.len();
+        if source.starts_with(')') {
+            return (source.len() > 1 && len > 2).then_some(len);
+        }
+    }
+    None
+}
+
+// Splits a pattern on the `|` atoms, so a `|` inside a class or a capture
+// is not a separator. An alternative can be empty: `abc|` also matches "".
+pub(crate) fn alternatives<'a>(s: &'a str, literals: &[char]) -> Vec<&'a str> {
+    if literals.contains(&'|') {
+        return vec![s];
+    }
+    let mut parts = vec![];
+    let (mut start, mut end) = (0, 0);
+    for source in atom_sources(s, literals) {
+        if source == "|" {
+            parts.push(&s[start..end]);
+            start = end + 1;
+        }
+        end += source.len();
+    }
+    parts.push(&s[start..]);
+    parts
+}
+
+#[derive(Debug, Clone)]
+pub struct PatternBuilder {
+    atoms: Vec<Atom>,
+}
+
+impl PatternBuilder {
+    pub fn literal(mut self, s: &str) -> Self {
+        self.atoms.extend(s.chars().map(|ch| Atom::new(vec![ch])));
+        self
+    }
+
+    pub fn class(mut self, chars: &[char]) -> Self {
+        self.atoms.push(Atom::new(chars.to_vec()));
+        self
+    }
+
+    // Repeats the last atom one or more times; does nothing on an empty pattern.
+    pub fn plus(mut self) -> Self {
+        if let Some(atom) = self.atoms.last_mut() {
+            atom.repeat = Repeat::OneOrMore;
+        }
+        self
+    }
+
+    // Repeats the last atom zero or more times; does nothing on an empty pattern.
+    pub fn star(mut self) -> Self {
+        if let Some(atom) = self.atoms.last_mut() {
+            atom.repeat = Repeat::ZeroOrMore;
+        }
+        self
+    }
+
+    // Makes the last atom optional; does nothing on an empty pattern.
+    pub fn optional(mut self) -> Self {
+        if let Some(atom) = self.atoms.last_mut() {
+            atom.repeat = Repeat::ZeroOrOne;
+        }
+        self
+    }
+
+    pub fn build(self) -> Pattern {
+        Pattern {
+            atoms: self.atoms,
+            groups: vec![],
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(s: &str) -> Result<Pattern, TableError<()>> {
+        Pattern::parse(s, &[])
+    }
+
+    #[test]
+    fn parse_matches_builder() {
+        assert_eq!(
+            parse("ab[xy]+").unwrap(),
+            Pattern::literal("ab").class(&['x', 'y']).plus().build()
+        );
+        assert_eq!(
+            parse("a+b").unwrap(),
+            Pattern::literal("a").plus().literal("b").build()
+        );
+    }
+
+    #[test]
+    fn parse_plus_at_start_is_literal() {
+        assert_eq!(parse("+a").unwrap(), Pattern::literal("+a").build());
+        assert_eq!(
+            parse("a++").unwrap(),
+            Pattern::literal("a").plus().literal("+").build()
+        );
+    }
+
+    #[test]
+    fn parse_unclosed_class() {
+        assert_eq!(parse("a[bc"), Err(TableError::UnclosedClass));
+    }
+
+    #[test]
+    fn atom_sources_split() {
+        assert_eq!(
+            atom_sources("ab[xy]+c+", &[]),
+            vec!["a", "b", "[xy]+", "c+"]
+        );
+        assert_eq!(atom_sources("+a[b", &[]), vec!["+", "a", "[b"]);
+        assert!(atom_sources("", &[]).is_empty());
+    }
+
+    #[test]
+    fn parse_with_literals() {
+        let literals: Result<Pattern, TableError<()>> = Pattern::parse("a+[b]", &['+', '[']);
+        assert_eq!(literals.unwrap(), Pattern::literal("a+[b]").build());
+
+        // a literal `]` is a class member, so it can't close the class
+        let members: Result<Pattern, TableError<()>> = Pattern::parse("[a]b", &[']']);
+        assert_eq!(members, Err(TableError::UnclosedClass));
+        assert_eq!(atom_sources("a+[b]", &['+']), vec!["a", "+", "[b]"]);
+    }
+
+    #[test]
+    fn parse_capture() {
+        let mut sign = Atom::new(vec!['+', '-']);
+        sign.capture = Some("sign".to_string());
+        let mut expected = Pattern::builder().build();
+        expected.atoms.push(sign);
+        expected.atoms.extend(Pattern::literal("1").build().atoms);
+        assert_eq!(parse("(?<sign>[+-])1").unwrap(), expected);
+
+        // a `(` not followed by `?<` is a literal
+        assert_eq!(parse("(a)").unwrap(), Pattern::literal("(a)").build());
+        assert_eq!(parse("(?<s>a"), Err(TableError::UnclosedGroup));
+        assert_eq!(parse("(?<s"), Err(TableError::UnclosedGroup));
+        assert_eq!(atom_sources("(?<s>[ab])+c", &[]), vec!["(?<s>[ab])+", "c"]);
+    }
+
+    #[test]
+    fn parse_posix_classes() {
+        let digits = parse("[[:digit:]x]").unwrap();
+        assert_eq!(digits.atoms[0].chars, vec!['x']);
+        assert_eq!(
+            digits.atoms[0].posix,
+            "0123456789".chars().collect::<Vec<_>>()
+        );
+        assert_eq!(parse("[[:space:]]").unwrap().atoms[0].posix.len(), 6);
+        assert_eq!(parse("[[:punct:]]").unwrap().atoms[0].posix.len(), 32);
+
+        assert_eq!(
+            parse("[[:word:]]"),
+            Err(TableError::UnknownPosixClass("word".to_string()))
+        );
+        assert_eq!(
+            parse("[[:alpha]"),
+            Err(TableError::UnknownPosixClass("alpha".to_string()))
+        );
+        assert_eq!(
+            atom_sources("[[:digit:]]+a", &[]),
+            vec!["[[:digit:]]+", "a"]
+        );
+    }
+
+    #[test]
+    fn flexible_spaces_collapse_runs() {
+        let flexible = parse("go  to ").unwrap().with_flexible_spaces();
+        let repeats: Vec<Repeat> = flexible.atoms.iter().map(|a| a.repeat).collect();
+        assert_eq!(
+            repeats,
+            vec![
+                Repeat::Once,
+                Repeat::Once,
+                Repeat::ZeroOrMore,
+                Repeat::Once,
+                Repeat::Once,
+                Repeat::ZeroOrMore
+            ]
+        );
+        // a space repeated with `+` is left alone
+        let kept = parse("a +b").unwrap().with_flexible_spaces();
+        assert_eq!(kept, parse("a +b").unwrap());
+    }
+
+    #[test]
+    fn parse_any_escape() {
+        let any = parse("a\\A+").unwrap();
+        assert!(any.atoms[1].any && any.atoms[1].chars.is_empty());
+        assert_eq!(any.atoms[1].repeat, Repeat::OneOrMore);
+        // a backslash before anything else is a literal
+        assert_eq!(parse("\\b").unwrap(), Pattern::literal("\\b").build());
+        assert_eq!(atom_sources("a\\A+\\b", &[]), vec!["a", "\\A+", "\\", "b"]);
+    }
+
+    #[test]
+    fn parse_star() {
+        assert_eq!(
+            parse("a*b[cd]*").unwrap(),
+            Pattern::literal("a")
+                .star()
+                .literal("b")
+                .class(&['c', 'd'])
+                .star()
+                .build()
+        );
+        // at the start, or after another repetition, `*` is a literal
+        assert_eq!(parse("*a").unwrap(), Pattern::literal("*a").build());
+        assert_eq!(
+            parse("a+*").unwrap(),
+            Pattern::literal("a").plus().literal("*").build()
+        );
+        assert_eq!(atom_sources("a*b+*", &[]), vec!["a*", "b+", "*"]);
+        let literal: Result<Pattern, TableError<()>> = Pattern::parse("a*", &['*']);
+        assert_eq!(literal.unwrap(), Pattern::literal("a*").build());
+    }
+
+    #[test]
+    fn parse_optional() {
+        assert_eq!(
+            parse("-?[01]+").unwrap(),
+            Pattern::literal("-")
+                .optional()
+                .class(&['0', '1'])
+                .plus()
+                .build()
+        );
+        assert_eq!(parse("?a").unwrap(), Pattern::literal("?a").build());
+        assert_eq!(
+            parse("a??").unwrap(),
+            Pattern::literal("a").optional().literal("?").build()
+        );
+        assert_eq!(atom_sources("a?b?", &[]), vec!["a?", "b?"]);
+    }
+
+    #[test]
+    fn parse_range() {
+        let atoms = parse("[a-c_]").unwrap().atoms;
+        assert_eq!(atoms[0].ranges, vec![('a', 'c')]);
+        assert_eq!(atoms[0].chars, vec!['_']);
+        // a hyphen first or last is a literal
+        assert_eq!(parse("[-+]").unwrap(), Pattern::class(&['-', '+']).build());
+        assert_eq!(parse("[+-]").unwrap(), Pattern::class(&['+', '-']).build());
+        assert_eq!(parse("[-]").unwrap(), Pattern::literal("-").build());
+    }
+
+    #[test]
+    fn parse_negated_class() {
+        let atoms = parse("[^ab]+").unwrap().atoms;
+        assert!(atoms[0].negated);
+        assert_eq!(atoms[0].chars, vec!['a', 'b']);
+        assert_eq!(atoms[0].repeat, Repeat::OneOrMore);
+        // `^` elsewhere, or alone, is a member
+        assert_eq!(parse("[a^]").unwrap(), Pattern::class(&['a', '^']).build());
+        assert_eq!(parse("[^]").unwrap(), Pattern::literal("^").build());
+    }
+
+    #[test]
+    fn parse_bounds_unrolled() {
+        assert_eq!(parse("a{3}").unwrap(), Pattern::literal("aaa").build());
+        assert_eq!(
+            parse("a{1,3}").unwrap(),
+            Pattern::literal("a")
+                .literal("a")
+                .optional()
+                .literal("a")
+                .optional()
+                .build()
+        );
+        assert_eq!(
+            parse("a{2,}").unwrap(),
+            Pattern::literal("aa").plus().build()
+        );
+        assert_eq!(
+            parse("a{0,}").unwrap(),
+            Pattern::literal("a").star().build()
+        );
+        assert_eq!(parse("{2}a{").unwrap(), Pattern::literal("{2}a{").build());
+        assert_eq!(atom_sources("a{2}b", &[]), vec!["a{2}", "b"]);
+    }
+
+    #[test]
+    fn parse_bounds_malformed() {
+        for s in ["a{2,1}", "a{1001}", "a{1,1001}", "a{99999999999999999999}"] {
+            assert_eq!(parse(s), Err(TableError::InvalidRange), "{s}");
+        }
+        assert_eq!(parse("a{1000}").unwrap().atoms.len(), 1000);
+        // braces that are not numeric bounds are literals
+        for s in ["a{}", "a{x}", "a{,2}", "a{1,x}", "a{b}"] {
+            assert_eq!(parse(s).unwrap(), Pattern::literal(s).build(), "{s}");
+        }
+        assert_eq!(atom_sources("a{b}", &[]), vec!["a", "{", "b", "}"]);
+        assert_eq!(atom_sources("a{2}b", &[]), vec!["a{2}", "b"]);
+    }
+
+    #[test]
+    fn split_alternatives() {
+        assert_eq!(alternatives("get|put", &[]), vec!["get", "put"]);
+        assert_eq!(alternatives("[a|]|b", &[]), vec!["[a|]", "b"]);
+        assert_eq!(alternatives("(?<x>[|])|b", &[]), vec!["(?<x>[|])", "b"]);
+        assert_eq!(alternatives("a||b", &[]), vec!["a", "", "b"]);
+        assert_eq!(alternatives("|a", &[]), vec!["", "a"]);
+        assert_eq!(alternatives("abc|", &[]), vec!["abc", ""]);
+        assert_eq!(alternatives("a|b", &['|']), vec!["a|b"]);
+    }
+
+    #[test]
+    fn parse_group_repetition() {
+        let list = parse("a(,b)*").unwrap();
+        assert_eq!(list.atoms, Pattern::literal("a,b").build().atoms);
+        assert_eq!(
+            list.groups,
+            vec![Group {
+                atoms: 1..3,
+                repeat: Repeat::ZeroOrMore
+            }]
+        );
+        // `{1,2}` is one copy, then an optional one
+        let bounded = parse("(ab){1,2}").unwrap();
+        assert_eq!(bounded.atoms, Pattern::literal("abab").build().atoms);
+        assert_eq!(
+            bounded.groups,
+            vec![Group {
+                atoms: 2..4,
+                repeat: Repeat::ZeroOrOne
+            }]
+        );
+        assert!(parse("(ab){2}").unwrap().groups.is_empty());
+        // without a repetition, or empty, the parentheses are literals
+        assert_eq!(parse("(ab)").unwrap(), Pattern::literal("(ab)").build());
+        assert_eq!(parse("()+").unwrap(), Pattern::literal("()").plus().build());
+        assert_eq!(parse("(a").unwrap(), Pattern::literal("(a").build());
+
+        assert_eq!(
+            atom_sources("[0-9]+(,[0-9]+)*x", &[]),
+            vec!["[0-9]+", "(,[0-9]+)*", "x"]
+        );
+        assert_eq!(atom_sources("(ab)", &[]), vec!["(", "a", "b", ")"]);
+        assert_eq!(alternatives("(a|b)+|c", &[]), vec!["(a|b)+", "c"]);
+    }
+
+    #[test]
+    fn parse_leading_bracket_member() {
+        assert_eq!(parse("[]a]").unwrap(), Pattern::class(&[']', 'a']).build());
+        assert_eq!(parse("[]]").unwrap(), Pattern::literal("]").build());
+        // an empty class, reported when the table resolves it
+        assert!(parse("[]").unwrap().atoms[0].chars.is_empty());
+        assert_eq!(atom_sources("[]a]b", &[]), vec!["[]a]", "b"]);
+    }
+
+    #[test]
+    fn builder_plus_on_empty_pattern() {
+        assert_eq!(Pattern::builder().plus().build(), parse("").unwrap());
+    }
+}
@@ -1,18 +1,48 @@
 #![allow(unused)]
 
+use crate::dynamic::DynamicTable;
 use crate::error::{LexerError, TableError};
-use std::{collections::HashSet, fmt::Debug, marker::PhantomData};
-
-#[derive(Debug)]
+use crate::frozen::FrozenTable;
+use crate::pattern::{Atom, METACHARS, Pattern, Repeat, alternatives, atom_sources};
+use std::{
+    collections::{HashMap, HashSet, VecDeque},
+    fmt::Debug,
+    hash::Hash,
+    io::{ErrorKind, Read},
+    marker::PhantomData,
+    ops::Range,
+    sync::Arc,
+};
+
+// The nodes reached by walking atoms, and the transitions taken from where
+// the walk started.
+type Walk = (Vec<usize>, Vec<(usize, usize)>);
+
+// The most transitions one pattern may walk or create: each copy of a class
+// multiplies the paths, so `[0-9]{8}` alone would need 10^8 nodes.
+const MAX_PATTERN_NODES: usize = 1 << 21;
+
+#[derive(Debug, Clone)]
 struct Node<T> {
     children: Vec<Option<usize>>,
     value: Option<T>,
+    // the capture names set here, each with the id of its pattern
+    captures: Vec<(String, usize)>,
+    // the ids of the capturing patterns ending here
+    ends: Vec<usize>,
+    eof_only: bool,
+    // the end of a pattern the lexer consumes without emitting a token
+    skip: bool,
 }
 impl<T: Clone> Node<T> {
     fn new(capacity: usize) -> Self {
         Self {
             children: vec![None; capacity],
             value: None,
+            captures: vec![],
+            ends: vec![],
+            eof_only: false,
+            skip: false,
         }
     }
 
@@ -35,10 +65,19 @@ impl<T: Clone> Node<T> {
         child.as_ref()
     }
 
-    fn set_value(&mut self, value: T) -> Result<(), TableError<T>> {
-        if self.value.is_some() {
+    // When `keep_current(current, requested)` holds, the current value stays
+    // and setting is a no-op.
+    fn set_value(
+        &mut self,
+        value: T,
+        keep_current: Option<fn(&T, &T) -> bool>,
+    ) -> Result<(), TableError<T>> {
+        if let Some(current) = &self.value {
+            if keep_current.is_some_and(|keep| keep(current, &value)) {
+                return Ok(());
+            }
             return Err(TableError::<T>::ValueAlreadyDefined {
-                current: self.value.as_ref().unwrap().clone(),
+                current: current.clone(),
                 requested: value.clone(),
             });
         }
@@ -59,31 +98,190 @@ impl<T: Clone> Node<T> {
 pub struct Table<T> {
     alphabet: String,
     nodes: Vec<Node<T>>,
+    fallback: Option<T>,
+    literals: Vec<char>,
+    lookup: [Option<usize>; 256],
+    // no class or repetition added yet: `get` takes the literal walk
+    has_only_literals: bool,
+    keep_current: Option<fn(&T, &T) -> bool>,
+    node_budget: Option<usize>,
+    repetition: RepetitionMode,
+    wildcard: bool,
+    // each node as it was before its first change since the latest
+    // snapshot, undone in reverse by `restore`
+    journal: Vec<(usize, Node<T>)>,
+    // per node, the snapshot epoch it was last journaled in
+    journaled: Vec<usize>,
+    epoch: usize,
+    // shared with the live snapshots: the journal is kept only while one is
+    snapshots: Arc<()>,
+    // bumped when the nodes are renumbered or restored, see `TerminalHandle`
+    generation: usize,
+    // the capturing patterns added so far, each named by its index
+    patterns: usize,
+}
+
+// How a repeated atom treats an identical atom right after it.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum RepetitionMode {
+    /// The loop absorbs the next atom: `a+a` is the same as `a+`.
+    #[default]
+    Absorb,
+    /// The next atom is still required: `a+a` matches two or more `a`.
+    Strict,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum StepResult<T> {
+    /// The longest token at the start of the input: no further input can extend it.
+    Token { value: T, len: usize },
+    /// The longest match is a skip pattern: the caller drops `len` bytes.
+    Skip { len: usize },
+    /// The input ended while the match could still be extended (or completed):
+    /// the caller should retry with more input, or use `lexer` at end of stream.
+    NeedMore,
+    /// No pattern matches at the start of the input; `at` is the offset where
+    /// the walk found no transition.
+    NoMatch { at: usize },
+    /// `ch` at offset `at` is not in the alphabet.
+    Unknown { ch: char, at: usize },
+}
+
+// A warning from `Table::lint`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Lint {
+    /// A value the lexer never emits: on the root, reached by the empty key,
+    /// or on a node no key reaches (`key` is `None`).
+    Shadowed { key: Option<String> },
+    /// Maximal munch reads `long` as one token, even though it splits into
+    /// `short` and tokens for the rest.
+    Overlap { short: String, long: String },
+    /// An alphabet char used by no transition.
+    UnusedChar(char),
+    /// The node reached by `key` repeats on `chars`.
+    SelfLoop { key: String, chars: Vec<char> },
+}
+
+// The terminal nodes of a pattern, see `Table::add_returning`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TerminalHandle {
+    nodes: Vec<usize>,
+    generation: usize,
+}
+
+// A point `Table::restore` returns to, see `Table::snapshot`.
+#[derive(Debug, Clone)]
+pub struct Snapshot<T> {
+    alphabet: String,
+    lookup: [Option<usize>; 256],
+    len: usize,
+    journal_len: usize,
+    fallback: Option<T>,
+    has_only_literals: bool,
+    _live: Arc<()>,
+}
+
+impl<T: Debug + Clone> Default for Table<T> {
+    fn default() -> Self {
+        Table::new(String::new())
+    }
 }
 
 impl<T: Debug + Clone> Table<T> {
     pub fn new(alphabet: String) -> Self {
         let capacity = alphabet.len();
+        let lookup = byte_lookup(&alphabet);
         Self {
             alphabet,
             nodes: vec![Node::new(capacity)],
+            fallback: None,
+            literals: vec![],
+            lookup,
+            has_only_literals: true,
+            keep_current: None,
+            node_budget: None,
+            repetition: RepetitionMode::Absorb,
+            wildcard: false,
+            journal: vec![],
+            journaled: vec![],
+            epoch: 0,
+            snapshots: Arc::new(()),
+            generation: 0,
+            patterns: 0,
         }
     }
 
-    fn calculate_position(&self, ch: u8) -> Result<usize, TableError<T>> {
-        self.alphabet
-            .find(ch as char)
-            .ok_or(TableError::<T>::InvalidInput(ch as char))
+    // Patterns matching the same string don't conflict: the value of the one
+    // added first is kept, for `get` and for the lexer.
+    pub fn new_first_wins(alphabet: String) -> Self {
+        let mut table = Self::new(alphabet);
+        table.keep_current = Some(|_, _| true);
+        table
+    }
+
+    // An add that would grow the table past `budget` nodes, the root
+    // included, fails with `NodeBudgetExceeded` and changes nothing.
+    pub fn new_with_node_budget(alphabet: String, budget: usize) -> Self {
+        let mut table = Self::new(alphabet);
+        table.node_budget = Some(budget);
+        table
+    }
+
+    pub fn new_with_repetition_mode(alphabet: String, mode: RepetitionMode) -> Self {
+        let mut table = Self::new(alphabet);
+        table.repetition = mode;
+        table
+    }
+
+    // A `.` outside a class matches any alphabet char, `[.]` still matches
+    // the `.` itself.
+    pub fn new_with_wildcard(alphabet: String) -> Self {
+        let mut table = Self::new(alphabet);
+        table.wildcard = true;
+        table
+    }
+
+    // The listed characters are never operators in the patterns given to `add`.
+    pub fn new_with_literals(alphabet: String, literal_chars: &[char]) -> Self {
+        let mut table = Self::new(alphabet);
+        table.literals = literal_chars.to_vec();
+        table
+    }
+
+    pub fn normalize_alphabet(&mut self) {
+        let mut chars: Vec<u8> = self.alphabet.bytes().collect();
+        chars.sort_unstable();
+        chars.dedup();
+        let mapping: Vec<usize> = chars
+            .iter()
+            .filter_map(|ch| self.alphabet.find(*ch as char))
+            .collect();
+        for id in 0..self.nodes.len() {
+            let node = self.node_mut(id);
+            node.children = mapping.iter().map(|old| node.children[*old]).collect();
+        }
+        self.alphabet = chars.into_iter().map(char::from).collect();
+        self.rebuild_lookup();
+    }
+
+    // Recomputes the byte lookup from the current alphabet.
+    pub fn rebuild_lookup(&mut self) {
+        self.lookup = byte_lookup(&self.alphabet);
+    }
+
+    fn calculate_position(&self, ch: char) -> Result<usize, TableError<T>> {
+        ch.is_ascii()
+            .then(|| self.alphabet.find(ch))
+            .flatten()
+            .ok_or(TableError::<T>::InvalidInput(ch))
     }
 
     fn append_node(&mut self, current: usize, child: usize) -> Result<usize, TableError<T>> {
         match self.nodes[current].get_children(child) {
             Some(next) => Ok(*next),
             None => {
-                let new_node = Node::<T>::new(self.alphabet.len());
-                self.nodes.push(new_node);
-                let new_child = self.nodes.len() - 1;
-                self.nodes[current].set_children(child, new_child);
+                let new_child = self.push_node(Node::new(self.alphabet.len()))?;
+                self.node_mut(current).set_children(child, new_child);
                 Ok(new_child)
             }
         }
@@ -106,58 +304,582 @@ impl<T: Debug + Clone> Table<T> {
     }
 
     pub fn add(&mut self, s: &str, value: T) -> Result<(), TableError<T>> {
+        self.add_returning(s, value).map(|_| ())
+    }
+
+    // Like `add`, returning a handle to the terminals of `s`. It stays valid
+    // across later adds; `prune_empty` and `restore` renumber the nodes and
+    // make it stale.
+    pub fn add_returning(&mut self, s: &str, value: T) -> Result<TerminalHandle, TableError<T>> {
+        let nodes = self.insert(s)?;
+        for current in &nodes {
+            let keep_current = self.keep_current;
+            self.node_mut(*current)
+                .set_value(value.clone(), keep_current)?;
+        }
+        Ok(TerminalHandle {
+            nodes,
+            generation: self.generation,
+        })
+    }
+
+    // The value of the first terminal of the handle, `None` if it is stale.
+    pub fn value_of(&self, handle: &TerminalHandle) -> Option<&T> {
+        if handle.generation != self.generation {
+            return None;
+        }
+        self.nodes.get(*handle.nodes.first()?)?.get_value()
+    }
+
+    pub fn set_value_of(&mut self, handle: &TerminalHandle, value: T) -> Result<(), TableError<T>> {
+        if handle.generation != self.generation {
+            return Err(TableError::StaleHandle);
+        }
+        for &node in &handle.nodes {
+            self.node_mut(node).value = Some(value.clone());
+        }
+        Ok(())
+    }
+
+    // Every run of spaces in `s` matches zero or more spaces, so "go to"
+    // also matches "goto" and "go  to"; a space must be in the alphabet.
+    pub fn add_flexible(&mut self, s: &str, value: T) -> Result<(), TableError<T>> {
+        if !s.is_ascii() {
+            return Err(TableError::InvalidString(s.to_string()));
+        }
+        let pattern = Pattern::parse(s, &self.literals)?.with_flexible_spaces();
+        self.add_pattern(&pattern, value)
+    }
+
+    // The lexers consume the matches of `s` without emitting them, as they
+    // do for whitespace or comments; a value on the same node takes priority.
+    pub fn add_skip(&mut self, s: &str) -> Result<(), TableError<T>> {
+        for current in self.insert(s)? {
+            self.node_mut(current).skip = true;
+        }
+        Ok(())
+    }
+
+    // Each option is an exact string, metacharacters included; the options
+    // share their common prefixes.
+    pub fn add_any_of(&mut self, options: &[&str], value: T) -> Result<(), TableError<T>> {
+        for option in options {
+            if !option.is_ascii() {
+                return Err(TableError::InvalidString(option.to_string()));
+            }
+            self.add_pattern(&Pattern::literal(option).build(), value.clone())?;
+        }
+        Ok(())
+    }
+
+    // The lexer only emits `value` for a match that reaches the end of input.
+    pub fn add_eof_only(&mut self, s: &str, value: T) -> Result<(), TableError<T>> {
+        for current in self.insert(s)? {
+            let keep_current = self.keep_current;
+            let node = self.node_mut(current);
+            node.set_value(value.clone(), keep_current)?;
+            node.eof_only = true;
+        }
+        Ok(())
+    }
+
+    pub fn add_combining<F: FnMut(&T, T) -> T>(
+        &mut self,
+        s: &str,
+        value: T,
+        mut combine: F,
+    ) -> Result<(), TableError<T>> {
+        for current in self.insert(s)? {
+            let node = self.node_mut(current);
+            let combined = match node.get_value() {
+                Some(existing) => combine(existing, value.clone()),
+                None => value.clone(),
+            };
+            node.value = Some(combined);
+        }
+        Ok(())
+    }
+
+    pub fn add_pattern(&mut self, pattern: &Pattern, value: T) -> Result<(), TableError<T>> {
+        for current in self.insert_pattern(pattern)? {
+            let keep_current = self.keep_current;
+            self.node_mut(current)
+                .set_value(value.clone(), keep_current)?;
+        }
+        Ok(())
+    }
+
+    // Gives `word` its own path so its terminal can hold `kw_value` while
+    // every other string keeps the value of the patterns it already matched:
+    // a node reached along the word that is also reached in another way
+    // (another edge or a self-loop) is cloned, and the clone takes its place.
+    // On error the table is left unchanged.
+    pub fn reserve(&mut self, word: &str, kw_value: T) -> Result<(), TableError<T>> {
+        let snapshot = self.snapshot();
+        let reserved = self.reserve_path(word, kw_value);
+        if reserved.is_err() {
+            self.undo(snapshot);
+        }
+        reserved
+    }
+
+    fn reserve_path(&mut self, word: &str, kw_value: T) -> Result<(), TableError<T>> {
+        if !word.is_ascii() {
+            return Err(TableError::InvalidString(word.to_string()));
+        }
+        let positions = word
+            .chars()
+            .map(|ch| self.calculate_position(ch))
+            .collect::<Result<Vec<_>, _>>()?;
+        let mut in_degree = self.in_degrees();
+        let mut current = 0;
+        let mut shared = false;
+        for pos in positions {
+            let next = match self.nodes[current].get_children(pos) {
+                Some(&next) => next,
+                None => {
+                    let next = self.append_node(current, pos)?;
+                    in_degree.push(1);
+                    current = next;
+                    shared = false;
+                    continue;
+                }
+            };
+            shared = in_degree[next] > 1;
+            current = if shared {
+                let clone = self.nodes[next].clone();
+                for child in clone.children.iter().flatten() {
+                    in_degree[*child] += 1;
+                }
+                in_degree[next] -= 1;
+                in_degree.push(1);
+                let clone = self.push_node(clone)?;
+                self.node_mut(current).children[pos] = Some(clone);
+                clone
+            } else {
+                next
+            };
+        }
+        if shared {
+            // the value was copied from the shared node, the keyword wins
+            let node = self.node_mut(current);
+            node.value = None;
+            node.ends.clear();
+        }
+        let keep_current = self.keep_current;
+        self.node_mut(current).set_value(kw_value, keep_current)
+    }
+
+    // Records the alphabet, the node count and the fallback. While the snapshot
+    // is alive, each node is journaled before its first change, so `restore`
+    // also undoes replaced values and transitions added to existing nodes.
+    pub fn snapshot(&mut self) -> Snapshot<T> {
+        self.epoch += 1;
+        Snapshot {
+            alphabet: self.alphabet.clone(),
+            lookup: self.lookup,
+            len: self.nodes.len(),
+            journal_len: self.journal.len(),
+            fallback: self.fallback.clone(),
+            has_only_literals: self.has_only_literals,
+            _live: Arc::clone(&self.snapshots),
+        }
+    }
+
+    pub fn restore(&mut self, snapshot: Snapshot<T>) {
+        self.undo(snapshot);
+        self.generation += 1;
+    }
+
+    // `restore`, keeping the handles: the failed adds that use it only
+    // drop what they created themselves.
+    fn undo(&mut self, snapshot: Snapshot<T>) {
+        // `normalize_alphabet` reorders the children of every node
+        self.alphabet = snapshot.alphabet;
+        self.lookup = snapshot.lookup;
+        let undo = self
+            .journal
+            .split_off(snapshot.journal_len.min(self.journal.len()));
+        for (id, node) in undo.into_iter().rev() {
+            // `prune_empty` may have removed the slot
+            if id >= self.nodes.len() {
+                self.nodes
+                    .resize_with(id + 1, || Node::new(self.alphabet.len()));
+            }
+            self.nodes[id] = node;
+        }
+        self.nodes.truncate(snapshot.len);
+        self.fallback = snapshot.fallback;
+        self.has_only_literals = snapshot.has_only_literals;
+        // the undone changes are no longer journaled
+        self.epoch += 1;
+    }
+
+    // The node, journaled first if a snapshot is alive.
+    fn node_mut(&mut self, id: usize) -> &mut Node<T> {
+        if Arc::strong_count(&self.snapshots) == 1 {
+            self.journal.clear();
+        } else if self.journaled.get(id) != Some(&self.epoch) {
+            if self.journaled.len() <= id {
+                self.journaled.resize(id + 1, 0);
+            }
+            self.journaled[id] = self.epoch;
+            self.journal.push((id, self.nodes[id].clone()));
+        }
+        &mut self.nodes[id]
+    }
+
+    // A node added after every live snapshot: `restore` truncates it, so it
+    // is never journaled. Fails as soon as the node budget would be exceeded,
+    // before a large pattern is built.
+    fn push_node(&mut self, node: Node<T>) -> Result<usize, TableError<T>> {
+        if let Some(budget) = self.node_budget
+            && self.nodes.len() >= budget
+        {
+            return Err(TableError::NodeBudgetExceeded { budget });
+        }
+        let id = self.nodes.len();
+        self.nodes.push(node);
+        if self.journaled.len() <= id {
+            self.journaled.resize(id + 1, 0);
+        }
+        self.journaled[id] = self.epoch;
+        Ok(id)
+    }
+
+    // Removes the nodes that hold no value and lead nowhere else, such as
+    // those left by a failed add, until none is left; the remaining nodes
+    // are renumbered. Returns how many nodes were removed.
+    pub fn prune_empty(&mut self) -> usize {
+        for id in 0..self.nodes.len() {
+            self.node_mut(id);
+        }
+        let mut removed = vec![false; self.nodes.len()];
+        loop {
+            let dead: Vec<usize> = (1..self.nodes.len())
+                .filter(|&node| {
+                    !removed[node]
+                        && !self.nodes[node].has_value()
+                        && !self.nodes[node].skip
+                        && self.nodes[node]
+                            .children
+                            .iter()
+                            .flatten()
+                            .all(|c| *c == node)
+                })
+                .collect();
+            if dead.is_empty() {
+                break;
+            }
+            for node in dead {
+                removed[node] = true;
+            }
+            for node in &mut self.nodes {
+                for child in &mut node.children {
+                    if child.is_some_and(|c| removed[c]) {
+                        *child = None;
+                    }
+                }
+            }
+        }
+        let mut renumbered = vec![0; self.nodes.len()];
+        let mut next = 0;
+        for (node, removed) in removed.iter().enumerate() {
+            if !removed {
+                renumbered[node] = next;
+                next += 1;
+            }
+        }
+        let nodes = std::mem::take(&mut self.nodes);
+        self.nodes = nodes
+            .into_iter()
+            .zip(&removed)
+            .filter(|(_, removed)| !**removed)
+            .map(|(mut node, _)| {
+                for child in node.children.iter_mut().flatten() {
+                    *child = renumbered[*child];
+                }
+                node
+            })
+            .collect();
+        let count = removed.iter().filter(|removed| **removed).count();
+        if count > 0 {
+            self.generation += 1;
+        }
+        count
+    }
+
+    // Single-char token for an alphabet char that no pattern starts with;
+    // it never applies inside a partial match.
+    pub fn add_fallback(&mut self, value: T) {
+        self.fallback = Some(value);
+    }
+
+    // Each alternative of `a|b` is walked from the root; alternatives that
+    // end on the same node share a single terminal.
+    fn insert(&mut self, s: &str) -> Result<Vec<usize>, TableError<T>> {
         if !s.is_ascii() {
             return Err(TableError::InvalidString(s.to_string()));
         }
+        let mut terminals = vec![];
+        for alternative in alternatives(s, &self.literals) {
+            let pattern = Pattern::parse(alternative, &self.literals)?;
+            terminals.extend(self.insert_pattern(&pattern)?);
+        }
+        terminals.sort_unstable();
+        terminals.dedup();
+        Ok(terminals)
+    }
+
+    fn resolve(&self, atom: &Atom) -> Result<Vec<usize>, TableError<T>> {
+        if atom.dot && self.wildcard {
+            return self.resolve(&Atom {
+                chars: vec![],
+                any: true,
+                dot: false,
+                ..atom.clone()
+            });
+        }
+        let mut range = Vec::with_capacity(atom.chars.len());
+        for ch in &atom.chars {
+            let pos = self.calculate_position(*ch)?;
+            if !range.contains(&pos) {
+                range.push(pos);
+            }
+        }
+        if atom.any {
+            for ch in self.alphabet.chars() {
+                let pos = self.calculate_position(ch)?;
+                if !range.contains(&pos) {
+                    range.push(pos);
+                }
+            }
+        }
+        for ch in &atom.posix {
+            if let Ok(pos) = self.calculate_position(*ch)
+                && !range.contains(&pos)
+            {
+                range.push(pos);
+            }
+        }
+        for (start, end) in &atom.ranges {
+            let start = self.calculate_position(*start);
+            let end = self.calculate_position(*end);
+            let (Ok(start), Ok(end)) = (start, end) else {
+                return Err(TableError::InvalidRange);
+            };
+            if start > end {
+                return Err(TableError::InvalidRange);
+            }
+            for ch in self.alphabet[start..=end].chars() {
+                let pos = self.calculate_position(ch)?;
+                if !range.contains(&pos) {
+                    range.push(pos);
+                }
+            }
+        }
+        if atom.negated {
+            let mut complement = vec![];
+            for ch in self.alphabet.chars() {
+                let pos = self.calculate_position(ch)?;
+                if !range.contains(&pos) && !complement.contains(&pos) {
+                    complement.push(pos);
+                }
+            }
+            if complement.is_empty() {
+                return Err(TableError::InvalidRange);
+            }
+            range = complement;
+        }
+        if range.is_empty() {
+            return Err(TableError::EmptyClass);
+        }
+        // nodes are created in alphabet order, whatever the order in the class
+        range.sort_unstable();
+        Ok(range)
+    }
+
+    // With a node budget, a pattern that fails, for instance on reaching the
+    // budget, is undone. A pattern too large to build is always undone: it
+    // may leave a million dead nodes.
+    fn insert_pattern(&mut self, pattern: &Pattern) -> Result<Vec<usize>, TableError<T>> {
+        let snapshot = self.snapshot();
+        let terminals = self.walk_pattern(pattern);
+        if matches!(terminals, Err(TableError::PatternTooLarge { .. }))
+            || (terminals.is_err() && self.node_budget.is_some())
+        {
+            self.undo(snapshot);
+        }
+        terminals
+    }
+
+    // Walks the pattern creating the missing nodes, returns the terminal nodes.
+    fn walk_pattern(&mut self, pattern: &Pattern) -> Result<Vec<usize>, TableError<T>> {
+        let atoms = pattern.atoms();
+        let mut repeats: Vec<Repeat> = atoms.iter().map(|atom| atom.repeat).collect();
+        if self.repetition == RepetitionMode::Strict {
+            // `a+a` is walked as `aa+`, so the loop can't absorb the second `a`
+            for i in 1..atoms.len() {
+                let boundary = pattern
+                    .groups()
+                    .iter()
+                    .any(|group| group.atoms.start == i || group.atoms.end == i);
+                if !boundary
+                    && repeats[i - 1].loops()
+                    && repeats[i] == Repeat::Once
+                    && self.resolve(&atoms[i - 1])? == self.resolve(&atoms[i])?
+                {
+                    repeats.swap(i - 1, i);
+                }
+            }
+        }
+        // the captures belong to this pattern, not to the nodes it shares
+        let capture = atoms.iter().any(|atom| atom.capture.is_some()).then(|| {
+            self.patterns += 1;
+            self.patterns - 1
+        });
+        if !pattern.groups().is_empty() {
+            self.has_only_literals = false;
+        }
         let mut currents = vec![0];
-        let mut iter = s.bytes().peekable();
-        while let Some(ch) = iter.next() {
-            let mut range = Vec::with_capacity(self.alphabet.len());
-            match ch {
-                b'[' => {
-                    while let Some(next) = iter.next_if(|n| *n != b']') {
-                        let pos = self.calculate_position(next)?;
-                        if !range.contains(&pos) {
-                            range.push(pos);
+        let mut work = 0;
+        let mut done = 0;
+        for group in pattern.groups() {
+            let before = done..group.atoms.start;
+            (currents, _) = self.walk_atoms(
+                &atoms[before.clone()],
+                &repeats[before],
+                currents,
+                capture,
+                &mut work,
+            )?;
+            // each start of the group gets its own copy of it, so the end
+            // loops back to the nodes that follow that start
+            let mut exits = vec![];
+            for entry in currents {
+                let body = group.atoms.clone();
+                let (ends, edges) = self.walk_atoms(
+                    &atoms[body.clone()],
+                    &repeats[body],
+                    vec![entry],
+                    capture,
+                    &mut work,
+                )?;
+                if group.repeat.loops() {
+                    for end in &ends {
+                        for (pos, next) in &edges {
+                            self.node_mut(*end).set_children(*pos, *next)?;
                         }
                     }
-                    if Some(b']') != iter.next() || range.is_empty() {
-                        return Err(TableError::InvalidRange);
-                    }
-                    currents = self.add_from_range(&range, &currents)?;
                 }
-                _ => {
-                    range = vec![self.calculate_position(ch)?];
-                    currents = self.add_from_range(&range, &currents)?;
+                if group.repeat.optional() {
+                    exits.push(entry);
                 }
+                exits.extend(ends);
+            }
+            exits.sort_unstable();
+            exits.dedup();
+            currents = exits;
+            done = group.atoms.end;
+        }
+        let (currents, _) = self.walk_atoms(
+            &atoms[done..],
+            &repeats[done..],
+            currents,
+            capture,
+            &mut work,
+        )?;
+        if let Some(id) = capture {
+            for current in &currents {
+                self.node_mut(*current).ends.push(id);
+            }
+        }
+        Ok(currents)
+    }
+
+    // Walks the atoms from the sorted `currents`, returns the nodes reached
+    // and the transitions taken from `currents` themselves.
+    fn walk_atoms(
+        &mut self,
+        atoms: &[Atom],
+        repeats: &[Repeat],
+        mut currents: Vec<usize>,
+        capture: Option<usize>,
+        work: &mut usize,
+    ) -> Result<Walk, TableError<T>> {
+        let starts = currents.clone();
+        let mut edges = vec![];
+        for (atom, repeat) in atoms.iter().zip(repeats.iter().copied()) {
+            let range = self.resolve(atom)?;
+            if range.len() > 1 || repeat != Repeat::Once {
+                self.has_only_literals = false;
+            }
+            *work += currents.len() * range.len();
+            if *work > MAX_PATTERN_NODES {
+                return Err(TableError::PatternTooLarge {
+                    limit: MAX_PATTERN_NODES,
+                });
+            }
+            let skipped = if repeat.optional() {
+                currents.clone()
+            } else {
+                vec![]
             };
-            if let Some(b'+') = iter.peek() {
-                let _ = iter.next();
+            let from_starts: Vec<usize> = currents
+                .iter()
+                .filter(|current| starts.binary_search(current).is_ok())
+                .copied()
+                .collect();
+            currents = self.add_from_range(&range, &currents)?;
+            for start in from_starts {
+                for pos in &range {
+                    if let Some(next) = self.nodes[start].children[*pos] {
+                        edges.push((*pos, next));
+                    }
+                }
+            }
+            if let (Some(name), Some(id)) = (&atom.capture, capture) {
+                for current in &currents {
+                    self.node_mut(*current).captures.push((name.clone(), id));
+                }
+            }
+            if repeat.loops() {
                 for current in &currents {
+                    // no transition ever targets the root, so after an atom the
+                    // currents never contain it and the lexer always progresses
+                    debug_assert_ne!(*current, 0);
                     for pos in &range {
-                        self.nodes[*current].set_children(*pos, *current);
+                        self.node_mut(*current).set_children(*pos, *current);
                     }
                 }
             }
+            // zero repetitions: the atom can also be skipped
+            currents.extend(skipped);
+            // remove duplicated, or each optional atom doubles the currents
+            currents.sort_unstable();
+            currents.dedup();
         }
-        // remove duplicated
-        let unique_currents: HashSet<_> = currents.into_iter().collect();
-        for current in unique_currents {
-            self.nodes[current].set_value(value.clone())?;
-        }
-        Ok(())
+        Ok((currents, edges))
     }
 
     pub fn get(&self, s: &str) -> Result<Option<&T>, TableError<T>> {
+        if self.has_only_literals
+            && let Some(value) = self.get_literal(s)
+        {
+            return Ok(value);
+        }
         if !s.is_ascii() {
             return Err(TableError::InvalidString(s.to_string()));
         }
+        self.get_bytes(s.as_bytes())
+    }
+
+    // Like `get` on raw bytes, a byte outside the alphabet (any byte from
+    // 128 up) is an `InvalidInput` error when the walk reaches it.
+    pub fn get_bytes(&self, bytes: &[u8]) -> Result<Option<&T>, TableError<T>> {
         let mut current: usize = 0;
-        for ch in s.bytes() {
-            let pos = self
-                .alphabet
-                .find(ch as char)
-                .ok_or(TableError::<T>::InvalidInput(ch as char))?;
+        for &ch in bytes {
+            let pos = self.lookup[ch as usize].ok_or(TableError::<T>::InvalidInput(ch as char))?;
 
             if let Some(next) = self.nodes[current].get_children(pos) {
                 current = *next;
@@ -168,570 +890,2256 @@ impl<T: Debug + Clone> Table<T> {
         Ok(self.nodes[current].get_value())
     }
 
-    pub fn lexer<'a>(&'a self, s: &'a str) -> Result<TableIterator<'a, T>, LexerError> {
-        if !s.is_ascii() {
-            return Err(LexerError::InvalidString(s.to_string()));
+    // Keyword lookup without error building: `None` only for a char outside
+    // the alphabet, leaving `get` to report it.
+    fn get_literal(&self, s: &str) -> Option<Option<&T>> {
+        let mut current = 0;
+        for ch in s.bytes() {
+            let pos = self.lookup[ch as usize]?;
+            match self.nodes[current].children[pos] {
+                Some(next) => current = next,
+                // a miss is final, unless non-ASCII input must be an error
+                None => return s.is_ascii().then_some(None),
+            }
         }
-        Ok(TableIterator {
-            table: self,
-            input: s,
-            index: 0,
-            _phantom: PhantomData,
-        })
+        Some(self.nodes[current].get_value())
     }
-}
 
-pub struct TableIterator<'a, T> {
-    table: &'a Table<T>,
-    input: &'a str,
-    index: usize,
-    _phantom: PhantomData<T>,
-}
+    // Value-bearing nodes reachable from the end of `prefix`, each counted
+    // once: a `+` loop contributes a single key.
+    pub fn count_with_prefix(&self, prefix: &str) -> Result<usize, TableError<T>> {
+        if !prefix.is_ascii() {
+            return Err(TableError::InvalidString(prefix.to_string()));
+        }
+        let mut current = 0;
+        for ch in prefix.chars() {
+            let pos = self.calculate_position(ch)?;
+            match self.nodes[current].get_children(pos) {
+                Some(&next) => current = next,
+                None => return Ok(0),
+            }
+        }
+        let mut visited = vec![false; self.nodes.len()];
+        visited[current] = true;
+        let mut stack = vec![current];
+        let mut count = 0;
+        while let Some(node) = stack.pop() {
+            if self.nodes[node].has_value() {
+                count += 1;
+            }
+            for &next in self.nodes[node].children.iter().flatten() {
+                if !visited[next] {
+                    visited[next] = true;
+                    stack.push(next);
+                }
+            }
+        }
+        Ok(count)
+    }
 
-impl<'a, T: Clone> Iterator for TableIterator<'a, T> {
-    type Item = Result<(&'a T, &'a str), LexerError>;
+    // Walks both tables together: they share a key if a pair of nodes
+    // reachable by the same string both hold a value.
+    pub fn is_disjoint(&self, other: &Table<T>) -> Result<bool, TableError<T>> {
+        let mut mine: Vec<char> = self.alphabet.chars().collect();
+        let mut theirs: Vec<char> = other.alphabet.chars().collect();
+        mine.sort_unstable();
+        mine.dedup();
+        theirs.sort_unstable();
+        theirs.dedup();
+        if mine != theirs {
+            return Err(TableError::AlphabetMismatch);
+        }
+        let mut visited = HashSet::from([(0, 0)]);
+        let mut queue = VecDeque::from([(0, 0)]);
+        while let Some((a, b)) = queue.pop_front() {
+            if self.nodes[a].has_value() && other.nodes[b].has_value() {
+                return Ok(false);
+            }
+            for (pos, ch) in self.alphabet.bytes().enumerate() {
+                let next_a = self.nodes[a].get_children(pos);
+                let next_b = other.lookup[ch as usize].and_then(|p| other.nodes[b].get_children(p));
+                if let (Some(&next_a), Some(&next_b)) = (next_a, next_b)
+                    && visited.insert((next_a, next_b))
+                {
+                    queue.push_back((next_a, next_b));
+                }
+            }
+        }
+        Ok(true)
+    }
 
-    fn next(&mut self) -> Option<Self::Item> {
-        if self.index >= self.input.len() {
-            return None;
+    // A table over both alphabets accepting the strings of both tables. A
+    // string with a value in each is a `ValueAlreadyDefined` error, unless
+    // this table keeps its current values.
+    pub fn union_alphabet(self, other: Table<T>) -> Result<Table<T>, TableError<T>> {
+        let mut alphabet = self.alphabet.clone();
+        for ch in other.alphabet.chars() {
+            if !alphabet.contains(ch) {
+                alphabet.push(ch);
+            }
         }
-        let mut node_id = 0;
-        let mut progress = self.index;
-        let mut last_match = vec![];
-        loop {
-            if progress >= self.input.len() {
-                return match last_match.pop() {
-                    Some((last_index, value)) => {
-                        let content = &self.input[self.index..last_index + 1];
-                        self.index = last_index + 1;
-                        Some(Ok((value, content)))
-                    }
-                    None => Some(Err(LexerError::UnexpectedEnd {
-                        position: self.index,
-                    })),
+        let mut table = Table::new(alphabet.clone());
+        table.literals = self.literals.clone();
+        table.keep_current = self.keep_current;
+        table.fallback = self.fallback.clone().or(other.fallback.clone());
+        table.patterns = self.patterns + other.patterns;
+        table.has_only_literals = self.has_only_literals && other.has_only_literals;
+        let (mine, theirs) = (self.transition_fn(), other.transition_fn());
+        // each node of the union stands for a pair of nodes, one per table
+        let mut ids = HashMap::from([((Some(0), Some(0)), 0)]);
+        let mut queue = VecDeque::from([(Some(0), Some(0))]);
+        while let Some((a, b)) = queue.pop_front() {
+            let id = ids[&(a, b)];
+            let sources = [a.map(|a| &self.nodes[a]), b.map(|b| &other.nodes[b])];
+            // the pattern ids of `other` follow those of this table
+            for (offset, node) in [0, self.patterns].into_iter().zip(sources) {
+                let Some(node) = node else {
+                    continue;
                 };
-            }
-            let ch = self.input.as_bytes()[progress];
-            let pos = match self.table.alphabet.find(ch as char) {
-                Some(p) => p,
-                None => {
-                    return Some(Err(LexerError::UnknownChar {
-                        char: ch as char,
-                        position: progress,
-                    }));
+                if let Some(value) = node.get_value() {
+                    table.nodes[id].set_value(value.clone(), table.keep_current)?;
                 }
-            };
-            if let Some(next) = self.table.nodes[node_id].get_children(pos) {
-                if self.table.nodes[*next].has_value() {
-                    last_match.push((progress, self.table.nodes[*next].get_value().unwrap()));
+                let target = &mut table.nodes[id];
+                let captures = node
+                    .captures
+                    .iter()
+                    .map(|(name, id)| (name.clone(), id + offset));
+                target.captures.extend(captures);
+                target.ends.extend(node.ends.iter().map(|id| id + offset));
+                target.eof_only |= node.eof_only;
+                target.skip |= node.skip;
+            }
+            for (pos, ch) in alphabet.chars().enumerate() {
+                let pair = (a.and_then(|a| mine(a, ch)), b.and_then(|b| theirs(b, ch)));
+                if pair == (None, None) || table.lookup[ch as usize] != Some(pos) {
+                    continue;
                 }
-                progress += 1;
-                node_id = *next;
-            } else {
-                return match last_match.pop() {
-                    Some((last_progress, value)) => {
-                        let content = &self.input[self.index..last_progress + 1];
-                        self.index = last_progress + 1;
-                        Some(Ok((value, content)))
-                    }
-                    None => Some(Err(LexerError::UnexpectedEnd {
-                        position: self.index,
-                    })),
-                };
+                let next = *ids.entry(pair).or_insert_with(|| {
+                    table.nodes.push(Node::new(alphabet.len()));
+                    queue.push_back(pair);
+                    table.nodes.len() - 1
+                });
+                table.nodes[id].children[pos] = Some(next);
             }
         }
+        Ok(table)
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+    // The matched key with its value; for `get` the key is the whole input.
+    pub fn get_entry<'a>(&'a self, s: &'a str) -> Result<Option<(&'a str, &'a T)>, TableError<T>> {
+        Ok(self.get(s)?.map(|value| (s, value)))
+    }
 
-    fn alpha() -> Table<&'static str> {
-        Table::new("abcdefghijklmnopqrstuvwxyz".to_string())
+    pub fn common_prefix_len(&self) -> usize {
+        let mut current = 0;
+        let mut len = 0;
+        while !self.nodes[current].has_value() {
+            let mut children = self.nodes[current].children.iter().flatten();
+            match (children.next(), children.next()) {
+                (Some(&next), None) if next != current => current = next,
+                _ => break,
+            }
+            len += 1;
+        }
+        len
     }
 
-    fn digits() -> Table<i32> {
-        Table::new("0123456789".to_string())
+    pub fn is_match(&self, s: &str) -> bool {
+        matches!(self.get(s), Ok(Some(_)))
     }
 
-    fn alphanum() -> Table<&'static str> {
-        Table::new("abcdefghijklmnopqrstuvwxyz0123456789".to_string())
+    pub fn lexer<'a>(&'a self, s: &'a str) -> Result<TableIterator<'a, T>, LexerError> {
+        if !s.is_ascii() {
+            return Err(LexerError::InvalidString(s.to_string()));
+        }
+        Ok(TableIterator {
+            table: self,
+            input: s,
+            index: 0,
+            separators: None,
+            min_len: 0,
+            last_token: "",
+            _phantom: PhantomData,
+        })
     }
 
-    // ========================================================================
-    // LITERAL STRINGS
-    // ========================================================================
-
-    #[test]
-    fn literal_simple() {
-        let mut t = alpha();
-        t.add("hello", "greeting").unwrap();
-        assert_eq!(t.get("hello").unwrap(), Some(&"greeting"));
+    // Tokenizes a byte stream read in chunks, a token can span several
+    // chunks. Bytes outside the alphabet, non-ASCII ones included, are
+    // unknown characters.
+    pub fn lexer_reader<R: Read>(&self, reader: R) -> ReaderLexer<'_, T, R> {
+        ReaderLexer {
+            table: self,
+            reader,
+            buffer: vec![],
+            position: 0,
+            eof: false,
+            done: false,
+        }
     }
 
-    #[test]
-    fn literal_single_char() {
-        let mut t = alpha();
-        t.add("a", "letter").unwrap();
-        assert_eq!(t.get("a").unwrap(), Some(&"letter"));
-        assert_eq!(t.get("b").unwrap(), None);
+    // Owned tokens whose equal texts share a single allocation.
+    pub fn lexer_interned<'a>(&'a self, s: &'a str) -> Result<InternedLexer<'a, T>, LexerError> {
+        Ok(InternedLexer {
+            tokens: self.lexer(s)?,
+            interned: HashSet::new(),
+        })
     }
 
-    #[test]
-    fn literal_empty_string() {
-        let mut t = alpha();
-        t.add("", "empty").unwrap();
-        assert_eq!(t.get("").unwrap(), Some(&"empty"));
+    // Rejects inputs longer than `max_input` bytes before tokenizing.
+    pub fn lexer_bounded<'a>(
+        &'a self,
+        s: &'a str,
+        max_input: usize,
+    ) -> Result<TableIterator<'a, T>, LexerError> {
+        if s.len() > max_input {
+            return Err(LexerError::InputTooLarge {
+                len: s.len(),
+                max: max_input,
+            });
+        }
+        self.lexer(s)
     }
 
-    #[test]
-    fn literal_multiple_patterns() {
-        let mut t = alpha();
-        t.add("cat", "animal").unwrap();
-        t.add("car", "vehicle").unwrap();
-        t.add("card", "object").unwrap();
+    // Tokenizes each input on its own, an error only affects its input.
+    pub fn tokenize_many<'a>(
+        &'a self,
+        inputs: &'a [&'a str],
+    ) -> Vec<Result<Vec<(&'a T, &'a str)>, LexerError>> {
+        inputs
+            .iter()
+            .map(|input| self.lexer(input)?.collect())
+            .collect()
+    }
 
-        assert_eq!(t.get("cat").unwrap(), Some(&"animal"));
-        assert_eq!(t.get("car").unwrap(), Some(&"vehicle"));
-        assert_eq!(t.get("card").unwrap(), Some(&"object"));
-        assert_eq!(t.get("ca").unwrap(), None);
+    // A separator reached after the start of a token ends it, accepting the
+    // longest match found so far: separators can still be tokens on their own.
+    pub fn lexer_with_separators<'a>(
+        &'a self,
+        s: &'a str,
+        seps: &'a HashSet<char>,
+    ) -> Result<TableIterator<'a, T>, LexerError> {
+        let mut iter = self.lexer(s)?;
+        iter.separators = Some(seps);
+        Ok(iter)
     }
 
-    #[test]
-    fn literal_shared_prefix() {
-        let mut t = alpha();
-        t.add("test", "v1").unwrap();
-        t.add("testing", "v2").unwrap();
+    // Matches shorter than `min` bytes are never tokens: the lexer falls
+    // back to a longer one, or fails where there is none.
+    pub fn lexer_min_len<'a>(
+        &'a self,
+        s: &'a str,
+        min: usize,
+    ) -> Result<TableIterator<'a, T>, LexerError> {
+        let mut iter = self.lexer(s)?;
+        iter.min_len = min;
+        Ok(iter)
+    }
 
-        assert_eq!(t.get("test").unwrap(), Some(&"v1"));
-        assert_eq!(t.get("testing").unwrap(), Some(&"v2"));
-        assert_eq!(t.get("testi").unwrap(), None);
+    // Like `lexer`, also reporting the characters consumed by the capture
+    // atoms of the matched pattern, in order.
+    pub fn lexer_capturing<'a>(
+        &'a self,
+        s: &'a str,
+    ) -> Result<impl Iterator<Item = Result<CapturedToken<'a, T>, LexerError>> + 'a, LexerError>
+    {
+        Ok(self.lexer(s)?.map(move |token| {
+            let (value, text) = token?;
+            let mut path = vec![];
+            let mut current = 0;
+            for ch in text.chars() {
+                // a fallback token has no path in the table
+                let Some(&next) =
+                    self.lookup[ch as usize].and_then(|pos| self.nodes[current].get_children(pos))
+                else {
+                    path.clear();
+                    break;
+                };
+                current = next;
+                path.push((current, ch));
+            }
+            // only the captures of the patterns ending on the matched node
+            let ends = path
+                .last()
+                .map_or(&[][..], |(node, _)| &self.nodes[*node].ends);
+            let mut captures = vec![];
+            for (node, ch) in path {
+                for (name, id) in &self.nodes[node].captures {
+                    if ends.contains(id) {
+                        captures.push((name.as_str(), ch));
+                    }
+                }
+            }
+            Ok(CapturedToken {
+                value,
+                text,
+                captures,
+            })
+        }))
     }
 
-    #[test]
-    fn literal_prefix_not_matched() {
-        let mut t = alpha();
-        t.add("hello", "greeting").unwrap();
-        assert_eq!(t.get("hel").unwrap(), None);
-        assert_eq!(t.get("helloo").unwrap(), None);
+    pub fn lexer_map<'a, U, F: FnMut(&T) -> U + 'a>(
+        &'a self,
+        s: &'a str,
+        mut f: F,
+    ) -> Result<impl Iterator<Item = Result<(U, &'a str), LexerError>> + 'a, LexerError> {
+        Ok(self.lexer(s)?.map(move |token| {
+            let (value, content) = token?;
+            Ok((f(value), content))
+        }))
     }
 
-    // ========================================================================
-    // CHARACTER CLASSES [abc]
-    // ========================================================================
+    // Like `lexer`, with the number of bytes consumed by skip patterns
+    // right before each token.
+    pub fn lexer_with_gaps<'a>(
+        &'a self,
+        s: &'a str,
+    ) -> Result<impl Iterator<Item = Result<(&'a T, &'a str, usize), LexerError>> + 'a, LexerError>
+    {
+        let mut end = 0;
+        Ok(self.lexer(s)?.map(move |token| {
+            let (value, content) = token?;
+            let start = content.as_ptr() as usize - s.as_ptr() as usize;
+            let gap = start - end;
+            end = start + content.len();
+            Ok((value, content, gap))
+        }))
+    }
 
-    #[test]
-    fn class_simple() {
-        let mut t = alpha();
-        t.add("[abc]", "first_three").unwrap();
+    // Like `lexer`, numbering the tokens: errors don't consume an index.
+    pub fn lexer_indexed<'a>(
+        &'a self,
+        s: &'a str,
+    ) -> Result<impl Iterator<Item = Result<(usize, &'a T, &'a str), LexerError>> + 'a, LexerError>
+    {
+        let mut count = 0;
+        Ok(self.lexer(s)?.map(move |token| {
+            let (value, content) = token?;
+            count += 1;
+            Ok((count - 1, value, content))
+        }))
+    }
 
-        assert_eq!(t.get("a").unwrap(), Some(&"first_three"));
-        assert_eq!(t.get("b").unwrap(), Some(&"first_three"));
-        assert_eq!(t.get("c").unwrap(), Some(&"first_three"));
-        assert_eq!(t.get("d").unwrap(), None);
+    // Owns both the table and the input, so the lexer can outlive the scope
+    // that built them.
+    pub fn into_lexer(self, input: String) -> Result<OwnedLexer<T>, LexerError> {
+        if !input.is_ascii() {
+            return Err(LexerError::InvalidString(input));
+        }
+        Ok(OwnedLexer {
+            table: self,
+            input,
+            index: 0,
+        })
     }
 
-    #[test]
-    fn class_single_element() {
-        let mut t = alpha();
-        t.add("[a]", "just_a").unwrap();
-        assert_eq!(t.get("a").unwrap(), Some(&"just_a"));
-        assert_eq!(t.get("b").unwrap(), None);
+    // Recovers from errors instead of stopping. Each unknown character is
+    // reported as an error span, with `coalesce_unknown` a run of them is
+    // reported as a single span. Where no pattern matches, the bytes are
+    // skipped until a token can start and reported as one span.
+    pub fn lexer_lossy<'a>(
+        &'a self,
+        s: &'a str,
+        coalesce_unknown: bool,
+    ) -> Result<LossyIterator<'a, T>, LexerError> {
+        if !s.is_ascii() {
+            return Err(LexerError::InvalidString(s.to_string()));
+        }
+        Ok(LossyIterator {
+            table: self,
+            input: s,
+            index: 0,
+            coalesce_unknown,
+            max_errors: None,
+            errors: 0,
+        })
     }
 
-    #[test]
-    fn class_in_middle() {
-        let mut t = alpha();
-        t.add("c[aou]t", "words").unwrap();
+    // Bytes inside the tokens `lexer_lossy` recovers, 0 for a non-ASCII input.
+    pub fn matched_byte_count(&self, s: &str) -> usize {
+        let Ok(iter) = self.lexer_lossy(s, false) else {
+            return 0;
+        };
+        iter.filter_map(|item| item.ok())
+            .map(|(_, text)| text.len())
+            .sum()
+    }
 
-        assert_eq!(t.get("cat").unwrap(), Some(&"words"));
-        assert_eq!(t.get("cot").unwrap(), Some(&"words"));
-        assert_eq!(t.get("cut").unwrap(), Some(&"words"));
-        assert_eq!(t.get("cet").unwrap(), None);
-        assert_eq!(t.get("cit").unwrap(), None);
+    // Like `lexer_lossy`, ending the iteration after `max_errors` errors.
+    pub fn lexer_lossy_max_errors<'a>(
+        &'a self,
+        s: &'a str,
+        max_errors: usize,
+    ) -> Result<LossyIterator<'a, T>, LexerError> {
+        let mut iter = self.lexer_lossy(s, false)?;
+        iter.max_errors = Some(max_errors);
+        Ok(iter)
     }
 
-    #[test]
-    fn class_multiple() {
-        let mut t = alpha();
-        t.add("[ab][xy]", "combo").unwrap();
+    // `lexer_lossy` without coalescing, tokens and errors as a single type.
+    pub fn lexer_events<'a>(
+        &'a self,
+        s: &'a str,
+    ) -> Result<impl Iterator<Item = LexerEvent<'a, T>> + 'a, LexerError> {
+        Ok(self.lexer_lossy(s, false)?.map(move |item| {
+            let text = match &item {
+                Ok((_, text)) | Err((_, text)) => text,
+            };
+            // skipped input can precede the text
+            let span = span_in(s, text);
+            match item {
+                Ok((kind, text)) => LexerEvent::Token { kind, text, span },
+                Err((error, _)) => LexerEvent::Error { error, span },
+            }
+        }))
+    }
 
-        assert_eq!(t.get("ax").unwrap(), Some(&"combo"));
-        assert_eq!(t.get("ay").unwrap(), Some(&"combo"));
-        assert_eq!(t.get("bx").unwrap(), Some(&"combo"));
-        assert_eq!(t.get("by").unwrap(), Some(&"combo"));
-        assert_eq!(t.get("cx").unwrap(), None);
-        assert_eq!(t.get("az").unwrap(), None);
+    // The token whose span contains `offset`, lexing from the start; `None`
+    // when the offset is in skipped input, past the end, or at or after the
+    // first lexer error.
+    pub fn token_at<'a>(
+        &'a self,
+        s: &'a str,
+        offset: usize,
+    ) -> Result<Option<(&'a T, Range<usize>)>, LexerError> {
+        for item in self.lexer(s)? {
+            let Ok((value, text)) = item else {
+                return Ok(None);
+            };
+            let span = span_in(s, text);
+            if span.contains(&offset) {
+                return Ok(Some((value, span)));
+            }
+            if span.start > offset {
+                return Ok(None);
+            }
+        }
+        Ok(None)
     }
 
-    #[test]
-    fn class_consecutive() {
-        let mut t = alpha();
-        t.add("[a][b][c]", "abc").unwrap();
-        assert_eq!(t.get("abc").unwrap(), Some(&"abc"));
-        assert_eq!(t.get("aaa").unwrap(), None);
+    // Every token of `s` with its byte span, or the first error.
+    pub fn lex_spanned<'a>(&'a self, s: &'a str) -> Result<Vec<SpannedToken<'a, T>>, LexerError> {
+        self.lexer(s)?
+            .map(|token| {
+                let (value, text) = token?;
+                Ok((value, text, span_in(s, text)))
+            })
+            .collect()
     }
 
-    #[test]
-    fn class_with_duplicates() {
-        let mut t = alpha();
-        // [aaa] should be treated as [a]
-        t.add("[aaa]", "triple").unwrap();
-        assert_eq!(t.get("a").unwrap(), Some(&"triple"));
-        assert_eq!(t.get("aa").unwrap(), None);
+    // Longest match starting at `start`, returning the value (`None` for a
+    // skip pattern) and the end offset (exclusive) of the matched token.
+    // Only nodes reached through a transition are considered, so a value on
+    // the root (the empty string) never produces a zero-length token.
+    fn longest_match(
+        &self,
+        input: &str,
+        start: usize,
+        separators: Option<&HashSet<char>>,
+    ) -> Result<(Option<&T>, usize), LexerError> {
+        self.longest_match_min(input, start, separators, 0)
     }
 
-    #[test]
-    fn class_preserves_order() {
-        let mut t = alpha();
-        // duplicates should be ignored, keeping first occurrence
-        t.add("[abab]", "val").unwrap();
-        assert_eq!(t.get("a").unwrap(), Some(&"val"));
-        assert_eq!(t.get("b").unwrap(), Some(&"val"));
+    // Like `longest_match`, ignoring the matches shorter than `min_len`.
+    fn longest_match_min(
+        &self,
+        input: &str,
+        start: usize,
+        separators: Option<&HashSet<char>>,
+        min_len: usize,
+    ) -> Result<(Option<&T>, usize), LexerError> {
+        self.match_until(input, start, input.len(), separators, min_len)
     }
 
-    // ========================================================================
-    // PLUS OPERATOR (+)
-    // ========================================================================
+    // Like `longest_match_min`, walking no further than `stop`; only the end
+    // of `input` completes an end-of-input match.
+    fn match_until(
+        &self,
+        input: &str,
+        start: usize,
+        stop: usize,
+        separators: Option<&HashSet<char>>,
+        min_len: usize,
+    ) -> Result<(Option<&T>, usize), LexerError> {
+        let mut node_id = 0;
+        // the last value-bearing node on the walk: when a longer partial path
+        // fails, however deep, the token falls back to it
+        let mut last_match = None;
+        for (progress, &ch) in input.as_bytes()[..stop].iter().enumerate().skip(start) {
+            if progress > start && separators.is_some_and(|seps| seps.contains(&(ch as char))) {
+                break;
+            }
+            let pos = self
+                .alphabet
+                .find(ch as char)
+                .ok_or(LexerError::UnknownChar {
+                    char: ch as char,
+                    position: progress,
+                })?;
+            match self.nodes[node_id].get_children(pos) {
+                Some(&next) => {
+                    let node = &self.nodes[next];
+                    if (node.has_value() || node.skip)
+                        && (!node.eof_only || progress + 1 == input.len())
+                        && progress + 1 - start >= min_len
+                    {
+                        last_match = Some((node.get_value(), progress + 1));
+                    }
+                    node_id = next;
+                }
+                None => {
+                    if progress == start && min_len <= 1 {
+                        last_match = self.fallback.as_ref().map(|value| (Some(value), start + 1));
+                    }
+                    break;
+                }
+            }
+        }
+        last_match.ok_or(LexerError::UnexpectedEnd { position: start })
+    }
 
-    #[test]
-    fn plus_single_char() {
-        let mut t = alpha();
-        t.add("a+", "as").unwrap();
+    pub fn step_token(&self, s: &str) -> StepResult<&T> {
+        let mut node_id = 0;
+        let mut last_match = None;
+        for (at, ch) in s.char_indices() {
+            let pos = match ch.is_ascii().then(|| self.alphabet.find(ch)).flatten() {
+                Some(pos) => pos,
+                None => return StepResult::Unknown { ch, at },
+            };
+            match self.nodes[node_id].get_children(pos) {
+                Some(&next) => {
+                    let node = &self.nodes[next];
+                    // an end-of-input value can only count once the input
+                    // is known to end, see the `NeedMore` below
+                    if (node.has_value() || node.skip) && !node.eof_only {
+                        last_match = Some((node.get_value(), at + 1));
+                    }
+                    node_id = next;
+                }
+                None => {
+                    return match last_match {
+                        Some((Some(value), len)) => StepResult::Token { value, len },
+                        Some((None, len)) => StepResult::Skip { len },
+                        None => StepResult::NoMatch { at },
+                    };
+                }
+            }
+        }
+        let node = &self.nodes[node_id];
+        if node.children.iter().any(Option::is_some) || (node.eof_only && node.has_value()) {
+            return StepResult::NeedMore;
+        }
+        match last_match {
+            Some((Some(value), len)) => StepResult::Token { value, len },
+            Some((None, len)) => StepResult::Skip { len },
+            None => StepResult::NoMatch { at: s.len() },
+        }
+    }
 
-        assert_eq!(t.get("a").unwrap(), Some(&"as"));
-        assert_eq!(t.get("aa").unwrap(), Some(&"as"));
-        assert_eq!(t.get("aaa").unwrap(), Some(&"as"));
-        assert_eq!(t.get("").unwrap(), None);
+    // Whether some pattern starts with `ch`; false for a char outside the
+    // alphabet.
+    pub fn can_start_token(&self, ch: char) -> bool {
+        (self.transition_fn())(0, ch).is_some()
     }
 
-    #[test]
-    fn plus_with_prefix() {
-        let mut t = alpha();
-        t.add("ba+", "b_then_as").unwrap();
+    pub fn is_tokenizable(&self, s: &str) -> bool {
+        if !s.is_ascii() {
+            return false;
+        }
+        let mut index = 0;
+        while index < s.len() {
+            match self.longest_match(s, index, None) {
+                Ok((_, end)) => index = end,
+                Err(_) => return false,
+            }
+        }
+        true
+    }
 
-        assert_eq!(t.get("ba").unwrap(), Some(&"b_then_as"));
-        assert_eq!(t.get("baa").unwrap(), Some(&"b_then_as"));
-        assert_eq!(t.get("baaa").unwrap(), Some(&"b_then_as"));
-        assert_eq!(t.get("b").unwrap(), None);
+    // Offset where the lexer reports its first error: the unknown char, or
+    // the start of the input no pattern matches; `s.len()` if there is none.
+    pub fn coverage(&self, s: &str) -> Result<usize, LexerError> {
+        for token in self.lexer(s)? {
+            match token {
+                Err(LexerError::UnknownChar { position, .. })
+                | Err(LexerError::UnexpectedEnd { position }) => return Ok(position),
+                Err(e) => return Err(e),
+                Ok(_) => {}
+            }
+        }
+        Ok(s.len())
     }
 
-    #[test]
-    fn plus_with_suffix() {
-        let mut t = alpha();
-        t.add("a+b", "as_then_b").unwrap();
+    // The longest prefix of `s` that `lexer` tokenizes without errors. It
+    // ends at the first error at the latest, but the tokens of a truncated
+    // input can differ, so each candidate is checked.
+    pub fn longest_tokenizable_prefix<'a>(&self, s: &'a str) -> &'a str {
+        let ascii = &s[..s.find(|ch: char| !ch.is_ascii()).unwrap_or(s.len())];
+        let end = self.coverage(ascii).unwrap_or(0);
+        let end = (0..=end)
+            .rev()
+            .find(|end| self.is_tokenizable(&ascii[..*end]))
+            .unwrap_or(0);
+        &s[..end]
+    }
 
-        assert_eq!(t.get("ab").unwrap(), Some(&"as_then_b"));
-        assert_eq!(t.get("aab").unwrap(), Some(&"as_then_b"));
-        assert_eq!(t.get("aaab").unwrap(), Some(&"as_then_b"));
-        assert_eq!(t.get("a").unwrap(), None);
-        assert_eq!(t.get("b").unwrap(), None);
+    pub fn explain(&self, s: &str) -> String {
+        let iter = match self.lexer(s) {
+            Ok(iter) => iter,
+            Err(e) => return format!("{e}\n"),
+        };
+        let mut report = String::new();
+        let mut start = 0;
+        for token in iter {
+            match token {
+                Ok((value, content)) => {
+                    // skipped input sits between the tokens
+                    start = content.as_ptr() as usize - s.as_ptr() as usize;
+                    let end = start + content.len();
+                    report.push_str(&format!("{start}..{end} {value:?} {content:?}\n"));
+                    start = end;
+                }
+                Err(LexerError::UnknownChar { char, position }) => {
+                    report.push_str(&format!(
+                        "error at {position}: '{char}' is not in the alphabet"
+                    ));
+                    // the lexer fails immediately, even inside a valid match
+                    if let Ok((Some(value), end)) = self.match_until(s, start, position, None, 0) {
+                        report.push_str(&format!(
+                            ", interrupting {value:?} {:?} at {start}..{end}",
+                            &s[start..end]
+                        ));
+                    }
+                    report.push('\n');
+                }
+                Err(LexerError::UnexpectedEnd { position }) => report.push_str(&format!(
+                    "error at {position}: no pattern matches {:?}\n",
+                    &s[position..]
+                )),
+                Err(e) => report.push_str(&format!("error: {e}\n")),
+            }
+        }
+        report
     }
 
-    #[test]
-    fn plus_with_class() {
-        let mut t = alpha();
-        t.add("[ab]+", "ab_repeated").unwrap();
+    // The same automaton with every value replaced by `()`, a pure
+    // membership test.
+    pub fn into_acceptor(self) -> Table<()> {
+        Table {
+            alphabet: self.alphabet,
+            nodes: self
+                .nodes
+                .into_iter()
+                .map(|node| Node {
+                    children: node.children,
+                    value: node.value.map(|_| ()),
+                    captures: node.captures,
+                    ends: node.ends,
+                    eof_only: node.eof_only,
+                    skip: node.skip,
+                })
+                .collect(),
+            fallback: self.fallback.map(|_| ()),
+            literals: self.literals,
+            lookup: self.lookup,
+            has_only_literals: self.has_only_literals,
+            // all the values are equal now
+            keep_current: self
+                .keep_current
+                .map(|_| (|_, _| true) as fn(&(), &()) -> bool),
+            node_budget: self.node_budget,
+            repetition: self.repetition,
+            wildcard: self.wildcard,
+            journal: vec![],
+            journaled: vec![],
+            epoch: 0,
+            snapshots: Arc::new(()),
+            generation: 0,
+            patterns: self.patterns,
+        }
+    }
 
-        assert_eq!(t.get("a").unwrap(), Some(&"ab_repeated"));
-        assert_eq!(t.get("b").unwrap(), Some(&"ab_repeated"));
-        assert_eq!(t.get("ab").unwrap(), Some(&"ab_repeated"));
-        assert_eq!(t.get("ba").unwrap(), Some(&"ab_repeated"));
-        assert_eq!(t.get("aabb").unwrap(), Some(&"ab_repeated"));
-        assert_eq!(t.get("abab").unwrap(), Some(&"ab_repeated"));
-        assert_eq!(t.get("c").unwrap(), None);
-        assert_eq!(t.get("").unwrap(), None);
+    pub fn freeze(&self) -> FrozenTable<T> {
+        let (transitions, _) = self.dense_matrix();
+        let values = self
+            .terminal_values()
+            .into_iter()
+            .map(|v| v.cloned())
+            .collect();
+        FrozenTable::new(
+            self.alphabet.clone(),
+            transitions,
+            values,
+            self.nodes.iter().map(|node| node.eof_only).collect(),
+            self.nodes.iter().map(|node| node.skip).collect(),
+            self.fallback.clone(),
+        )
     }
 
-    #[test]
-    fn plus_multiple() {
-        let mut t = alpha();
-        t.add("a+b+", "as_then_bs").unwrap();
+    pub fn dense_matrix(&self) -> (Vec<Option<usize>>, usize) {
+        let width = self.alphabet.len();
+        let mut transitions = Vec::with_capacity(self.nodes.len() * width);
+        for node in &self.nodes {
+            transitions.extend_from_slice(&node.children);
+        }
+        (transitions, width)
+    }
 
-        assert_eq!(t.get("ab").unwrap(), Some(&"as_then_bs"));
-        assert_eq!(t.get("aab").unwrap(), Some(&"as_then_bs"));
-        assert_eq!(t.get("abb").unwrap(), Some(&"as_then_bs"));
-        assert_eq!(t.get("aabb").unwrap(), Some(&"as_then_bs"));
-        assert_eq!(t.get("aaabbb").unwrap(), Some(&"as_then_bs"));
+    pub fn terminal_values(&self) -> Vec<Option<&T>> {
+        self.nodes.iter().map(|node| node.get_value()).collect()
     }
 
-    #[test]
-    fn plus_in_middle() {
-        let mut t = alpha();
-        t.add("a+bc", "pattern").unwrap();
+    pub fn node_count(&self) -> usize {
+        self.nodes.len()
+    }
 
-        assert_eq!(t.get("abc").unwrap(), Some(&"pattern"));
-        assert_eq!(t.get("aabc").unwrap(), Some(&"pattern"));
-        assert_eq!(t.get("aaabc").unwrap(), Some(&"pattern"));
-        assert_eq!(t.get("bc").unwrap(), None);
+    // Nodes still reachable from the root; the difference from `node_count`
+    // is dead weight left behind by removals.
+    pub fn reachable_node_count(&self) -> usize {
+        let mut visited = vec![false; self.nodes.len()];
+        visited[0] = true;
+        let mut queue = VecDeque::from([0]);
+        let mut count = 0;
+        while let Some(node) = queue.pop_front() {
+            count += 1;
+            for &next in self.nodes[node].children.iter().flatten() {
+                if !visited[next] {
+                    visited[next] = true;
+                    queue.push_back(next);
+                }
+            }
+        }
+        count
     }
 
-    #[test]
-    fn plus_long_match() {
-        let mut t = alpha();
-        t.add("a+", "many").unwrap();
+    pub fn transition_count(&self) -> usize {
+        self.node_fanout().into_iter().sum()
+    }
 
-        let long = "a".repeat(10000);
-        assert_eq!(t.get(&long).unwrap(), Some(&"many"));
+    // Outgoing transitions of each node, self-loops included.
+    pub fn node_fanout(&self) -> Vec<usize> {
+        self.nodes
+            .iter()
+            .map(|node| node.children.iter().flatten().count())
+            .collect()
     }
 
-    // ========================================================================
-    // AMBIGUITY / CONVERGENCE (paths converging to same node)
-    // ========================================================================
+    // The next node from `node` on `ch`, `None` for an unknown node or char.
+    pub fn transition_fn(&self) -> impl Fn(usize, char) -> Option<usize> + '_ {
+        move |node, ch| {
+            let pos = *self.lookup.get(ch as usize)?.as_ref()?;
+            self.nodes.get(node)?.get_children(pos).copied()
+        }
+    }
 
-    #[test]
-    fn ambiguity_plus_followed_by_class() {
-        let mut t: Table<&str> = Table::new("ab".to_string());
-        // [ab]+[ab] creates duplicate currents due to self-loops
-        let result = t.add("[ab]+[ab]", "value");
-        assert!(result.is_ok());
+    // Every transition from a node to itself, as left by a repetition.
+    pub fn self_loops(&self) -> Vec<(usize, char)> {
+        (0..self.nodes.len())
+            .flat_map(|node| {
+                self.loop_chars(node)
+                    .into_iter()
+                    .map(move |ch| (node, ch as char))
+            })
+            .collect()
+    }
 
-        // Semantically equivalent to [ab]+
-        assert_eq!(t.get("a").unwrap(), Some(&"value"));
-        assert_eq!(t.get("b").unwrap(), Some(&"value"));
-        assert_eq!(t.get("ab").unwrap(), Some(&"value"));
-        assert_eq!(t.get("ba").unwrap(), Some(&"value"));
+    // How many transitions use each alphabet char.
+    pub fn char_frequency(&self) -> HashMap<char, usize> {
+        let mut frequency = HashMap::new();
+        for node in &self.nodes {
+            for (pos, ch) in self.alphabet.chars().enumerate() {
+                if node.get_children(pos).is_some() {
+                    *frequency.entry(ch).or_insert(0) += 1;
+                }
+            }
+        }
+        frequency
     }
 
-    #[test]
-    fn ambiguity_plus_then_same_char() {
-        let mut t: Table<&str> = Table::new("a".to_string());
-        // a+a: the second 'a' is absorbed by the self-loop
-        // so it's semantically equivalent to a+
-        t.add("a+a", "value").unwrap();
-
-        assert_eq!(t.get("a").unwrap(), Some(&"value"));
-        assert_eq!(t.get("aa").unwrap(), Some(&"value"));
-        assert_eq!(t.get("aaa").unwrap(), Some(&"value"));
+    // Incoming transitions of each node, self-loops included.
+    fn in_degrees(&self) -> Vec<usize> {
+        let mut in_degree = vec![0; self.nodes.len()];
+        for node in &self.nodes {
+            for child in node.children.iter().flatten() {
+                in_degree[*child] += 1;
+            }
+        }
+        in_degree
     }
 
-    #[test]
-    fn ambiguity_class_plus_then_subset() {
-        let mut t: Table<&str> = Table::new("ab".to_string());
-        // a+[ab]:
-        // - 'a' after + loops back, so a+a = a+
-        // - 'b' after + creates new final state
-        // Result: matches "a", "aa", "ab", "aab", etc.
-        t.add("a+[ab]", "value").unwrap();
-
-        assert_eq!(t.get("a").unwrap(), Some(&"value")); // a+ part
-        assert_eq!(t.get("aa").unwrap(), Some(&"value")); // a+ looping
-        assert_eq!(t.get("ab").unwrap(), Some(&"value")); // a then b
-        assert_eq!(t.get("aaa").unwrap(), Some(&"value"));
-        assert_eq!(t.get("aab").unwrap(), Some(&"value"));
+    // Nodes with more than one incoming transition, where the paths of a
+    // repetition or a class meet.
+    pub fn convergences(&self) -> Vec<usize> {
+        let in_degree = self.in_degrees();
+        (0..self.nodes.len())
+            .filter(|node| in_degree[*node] > 1)
+            .collect()
     }
 
-    // ========================================================================
-    // ERROR HANDLING
-    // ========================================================================
-
-    #[test]
-    fn error_invalid_char_not_in_alphabet() {
-        let mut t = alpha();
-        let result = t.add("hello1", "with_digit");
-        assert!(matches!(result, Err(TableError::InvalidInput('1'))));
+    pub fn terminal_indices(&self) -> Vec<usize> {
+        (0..self.nodes.len())
+            .filter(|i| self.nodes[*i].has_value())
+            .collect()
     }
 
-    #[test]
-    fn error_invalid_char_in_class() {
-        let mut t = alpha();
-        let result = t.add("[abc1]", "invalid");
-        assert!(matches!(result, Err(TableError::InvalidInput('1'))));
+    pub fn accepted_of_length(&self, n: usize) -> Vec<String> {
+        let mut accepted = vec![];
+        let mut path = String::with_capacity(n);
+        self.collect_of_length(0, n, &mut path, &mut accepted);
+        accepted
     }
 
-    #[test]
-    fn error_unclosed_bracket() {
-        let mut t = alpha();
-        let result = t.add("[abc", "unclosed");
-        assert!(matches!(result, Err(TableError::InvalidRange)));
+    fn collect_of_length(
+        &self,
+        node: usize,
+        remaining: usize,
+        path: &mut String,
+        accepted: &mut Vec<String>,
+    ) {
+        if remaining == 0 {
+            if self.nodes[node].has_value() {
+                accepted.push(path.clone());
+            }
+            return;
+        }
+        for (pos, ch) in self.alphabet.bytes().enumerate() {
+            if let Some(next) = self.nodes[node].get_children(pos) {
+                path.push(ch as char);
+                self.collect_of_length(*next, remaining - 1, path, accepted);
+                path.pop();
+            }
+        }
     }
 
-    #[test]
-    fn error_empty_class() {
-        let mut t = alpha();
-        let result = t.add("[]", "empty");
-        assert!(matches!(result, Err(TableError::InvalidRange)));
+    // Each value-bearing node is reported once, with the shortest path
+    // (alphabet order on ties) as its representative key.
+    pub fn entries(&self) -> Vec<(String, &T)> {
+        self.node_keys()
+            .into_iter()
+            .filter_map(|(node, key)| Some((key, self.nodes[node].get_value()?)))
+            .collect()
     }
 
-    #[test]
-    fn error_empty_class_with_plus() {
-        let mut t = alpha();
-        let result = t.add("[]+", "empty_plus");
-        assert!(matches!(result, Err(TableError::InvalidRange)));
+    // The reachable nodes in breadth-first order, each with its shortest key.
+    fn node_keys(&self) -> Vec<(usize, String)> {
+        let mut keys = vec![];
+        let mut visited = vec![false; self.nodes.len()];
+        let mut queue = VecDeque::from([(0, String::new())]);
+        visited[0] = true;
+        while let Some((node, key)) = queue.pop_front() {
+            keys.push((node, key.clone()));
+            for (pos, ch) in self.alphabet.bytes().enumerate() {
+                if let Some(&next) = self.nodes[node].get_children(pos)
+                    && !visited[next]
+                {
+                    visited[next] = true;
+                    let mut next_key = key.clone();
+                    next_key.push(ch as char);
+                    queue.push_back((next, next_key));
+                }
+            }
+        }
+        keys
     }
 
-    #[test]
-    fn error_non_ascii_add() {
-        let mut t = alpha();
-        let result = t.add("héllo", "accented");
-        assert!(matches!(result, Err(TableError::InvalidString(_))));
+    // Clears the value of every node whose key, as reported by `entries`,
+    // fails `f`.
+    pub fn retain_keys<F: FnMut(&str) -> bool>(&mut self, mut f: F) {
+        for (node, key) in self.node_keys() {
+            if self.nodes[node].has_value() && !f(&key) {
+                self.node_mut(node).value = None;
+            }
+        }
     }
 
-    #[test]
-    fn error_non_ascii_get() {
-        let t = alpha();
-        let result = t.get("héllo");
-        assert!(matches!(result, Err(TableError::InvalidString(_))));
+    // The keys of `entries`, sorted, one per line.
+    pub fn dump_keys(&self) -> String {
+        let mut keys: Vec<String> = self.keys().collect();
+        keys.sort_unstable();
+        keys.join("\n")
     }
 
-    #[test]
-    fn error_emoji() {
-        let mut t = alpha();
-        let result = t.add("hello😀", "emoji");
-        assert!(matches!(result, Err(TableError::InvalidString(_))));
+    pub fn keys(&self) -> impl Iterator<Item = String> + '_ {
+        self.entries().into_iter().map(|(key, _)| key)
     }
 
-    #[test]
-    fn error_duplicate_value() {
-        let mut t = alpha();
-        t.add("hello", "first").unwrap();
-        let result = t.add("hello", "second");
-        assert!(matches!(
-            result,
-            Err(TableError::ValueAlreadyDefined { .. })
-        ));
+    pub fn values(&self) -> impl Iterator<Item = &T> {
+        self.entries().into_iter().map(|(_, value)| value)
     }
 
-    #[test]
-    fn error_duplicate_via_class_overlap() {
-        let mut t = alpha();
-        t.add("a", "literal").unwrap();
-        let result = t.add("[abc]", "class");
-        assert!(matches!(
-            result,
-            Err(TableError::ValueAlreadyDefined { .. })
-        ));
+    // Alphabet characters that pattern strings treat as operators; they can
+    // still be matched through `add_pattern`.
+    pub fn metachar_conflicts(&self) -> Vec<char> {
+        self.alphabet
+            .chars()
+            .filter(|ch| {
+                (METACHARS.contains(ch) || (*ch == '.' && self.wildcard))
+                    && !self.literals.contains(ch)
+            })
+            .collect()
     }
 
-    #[test]
-    fn error_duplicate_via_plus_overlap() {
-        let mut t = alpha();
-        t.add("[ab]+", "pattern1").unwrap();
-        let result = t.add("a", "pattern2");
-        assert!(matches!(
-            result,
-            Err(TableError::ValueAlreadyDefined { .. })
-        ));
+    // The remainders of two patterns after their longest common prefix of
+    // atoms, so a class or a repetition is never split in the middle.
+    pub fn distinguishing_suffix(&self, a: &str, b: &str) -> Option<(String, String)> {
+        if a == b {
+            return None;
+        }
+        let common: usize = atom_sources(a, &self.literals)
+            .iter()
+            .zip(atom_sources(b, &self.literals))
+            .take_while(|(x, y)| **x == *y)
+            .map(|(x, _)| x.len())
+            .sum();
+        Some((a[common..].to_string(), b[common..].to_string()))
     }
 
-    #[test]
-    fn error_get_char_not_in_alphabet() {
-        let mut t = alpha();
-        t.add("hello", "greeting").unwrap();
-        let result = t.get("hello!");
-        assert!(matches!(result, Err(TableError::InvalidInput('!'))));
+    // Ties between keys of the same length go to the first in alphabet order,
+    // the order of the characters in the alphabet string.
+    pub fn shortest_key(&self) -> Option<String> {
+        self.keys().next()
     }
 
-    // ========================================================================
-    // EDGE CASES
-    // ========================================================================
-
-    #[test]
-    fn edge_star_is_literal() {
-        let mut t: Table<&str> = Table::new("a*".to_string());
-        // * is not an operator, just a literal
-        t.add("a*", "star").unwrap();
-        assert_eq!(t.get("a*").unwrap(), Some(&"star"));
+    pub fn overlaps(&self) -> Vec<(String, String)> {
+        let entries = self.entries();
+        let mut overlaps = vec![];
+        for (short, _) in &entries {
+            for (long, _) in &entries {
+                if long.len() > short.len() && long.starts_with(short.as_str()) {
+                    overlaps.push((short.clone(), long.clone()));
+                }
+            }
+        }
+        overlaps
     }
 
-    #[test]
-    fn edge_plus_at_start_is_literal() {
-        let mut t: Table<&str> = Table::new("+a".to_string());
-        t.add("+a", "plus").unwrap();
-        assert_eq!(t.get("+a").unwrap(), Some(&"plus"));
+    // Every warning of the other diagnostics, in a single list.
+    pub fn lint(&self) -> Vec<Lint> {
+        let keys = self.node_keys();
+        let mut reached = vec![false; self.nodes.len()];
+        for (node, _) in &keys {
+            reached[*node] = true;
+        }
+        let mut lints = vec![];
+        if self.nodes[0].has_value() {
+            lints.push(Lint::Shadowed {
+                key: Some(String::new()),
+            });
+        }
+        for (node, _) in self.nodes.iter().enumerate().skip(1) {
+            if !reached[node] && self.nodes[node].has_value() {
+                lints.push(Lint::Shadowed { key: None });
+            }
+        }
+        for (short, long) in self.overlaps() {
+            // the empty key is reported as shadowed
+            if !short.is_empty() && self.is_tokenizable(&long[short.len()..]) {
+                lints.push(Lint::Overlap { short, long });
+            }
+        }
+        let frequency = self.char_frequency();
+        for (pos, ch) in self.alphabet.char_indices() {
+            if self.alphabet.find(ch) == Some(pos) && !frequency.contains_key(&ch) {
+                lints.push(Lint::UnusedChar(ch));
+            }
+        }
+        for (node, key) in keys {
+            let chars: Vec<char> = self.loop_chars(node).into_iter().map(char::from).collect();
+            if !chars.is_empty() {
+                lints.push(Lint::SelfLoop { key, chars });
+            }
+        }
+        lints
     }
 
-    #[test]
-    fn edge_bracket_in_alphabet() {
-        let mut t: Table<&str> = Table::new("a[]".to_string());
-        // When [ is in alphabet but used as operator, it's still parsed as operator
-        let result = t.add("[a]", "test");
-        assert!(result.is_ok());
-        assert_eq!(t.get("a").unwrap(), Some(&"test"));
+    // Groups the transitions of a node by target, one label per edge.
+    fn edges(&self, node: usize, collapse: bool) -> Vec<(String, usize)> {
+        let mut edges: Vec<(Vec<u8>, usize)> = vec![];
+        for (pos, ch) in self.alphabet.bytes().enumerate() {
+            if let Some(&next) = self.nodes[node].get_children(pos) {
+                match edges
+                    .iter_mut()
+                    .find(|(_, target)| collapse && *target == next)
+                {
+                    Some((chars, _)) => chars.push(ch),
+                    None => edges.push((vec![ch], next)),
+                }
+            }
+        }
+        edges
+            .into_iter()
+            .map(|(chars, target)| (class_label(chars), target))
+            .collect()
     }
 
-    #[test]
-    fn edge_nested_brackets() {
-        let mut t = alpha();
-        // [[ab]] - inner [ is looked up in alphabet, not found
-        let result = t.add("[[ab]]", "nested");
-        assert!(matches!(result, Err(TableError::InvalidInput('['))));
+    pub fn to_dot(&self, collapse: bool) -> String {
+        let mut dot = String::from("digraph mtable {\n");
+        for (id, node) in self.nodes.iter().enumerate() {
+            match node.get_value() {
+                Some(value) => dot.push_str(&format!(
+                    "    {id} [shape=doublecircle, label=\"{id}: {}\"];\n",
+                    escape_dot(&format!("{value:?}"))
+                )),
+                None => dot.push_str(&format!("    {id} [shape=circle];\n")),
+            }
+        }
+        for id in 0..self.nodes.len() {
+            for (label, target) in self.edges(id, collapse) {
+                dot.push_str(&format!(
+                    "    {id} -> {target} [label=\"{}\"];\n",
+                    escape_dot(&label)
+                ));
+            }
+        }
+        dot.push_str("}\n");
+        dot
     }
 
-    #[test]
-    fn edge_empty_alphabet() {
-        let mut t: Table<&str> = Table::new("".to_string());
-        t.add("", "empty").unwrap();
-        assert_eq!(t.get("").unwrap(), Some(&"empty"));
+    pub fn debug_tree(&self, collapse: bool) -> String {
+        let mut tree = String::from("0\n");
+        let mut visited = vec![false; self.nodes.len()];
+        visited[0] = true;
+        self.write_tree(0, 1, collapse, &mut visited, &mut tree);
+        tree
+    }
 
-        let result = t.add("a", "should_fail");
-        assert!(matches!(result, Err(TableError::InvalidInput('a'))));
+    // One `NAME = pattern` line per path to a value, in alphabet order.
+    // Transitions to equivalent nodes are merged into a class, and a class
+    // whose nodes loop over the same class becomes `[...]+`; any other
+    // self-loop is written as an optional `[...]+` atom, with and without it.
+    pub fn to_grammar<F: Fn(&T) -> String>(&self, name_of: F) -> String {
+        let mut grammar = String::new();
+        let mut on_path = vec![false; self.nodes.len()];
+        self.write_grammar(
+            0,
+            String::new(),
+            false,
+            &mut on_path,
+            &name_of,
+            &mut grammar,
+        );
+        grammar
     }
 
-    #[test]
-    fn edge_single_char_alphabet() {
-        let mut t: Table<i32> = Table::new("a".to_string());
-        t.add("a+", 42).unwrap();
+    fn loop_chars(&self, node: usize) -> Vec<u8> {
+        self.alphabet
+            .bytes()
+            .enumerate()
+            .filter(|(pos, _)| self.nodes[node].get_children(*pos) == Some(&node))
+            .map(|(_, ch)| ch)
+            .collect()
+    }
 
-        assert_eq!(t.get("a").unwrap(), Some(&42));
-        assert_eq!(t.get("aaa").unwrap(), Some(&42));
+    fn write_grammar<F: Fn(&T) -> String>(
+        &self,
+        node: usize,
+        prefix: String,
+        looped: bool,
+        on_path: &mut [bool],
+        name_of: &F,
+        grammar: &mut String,
+    ) {
+        if on_path[node] {
+            return;
+        }
+        on_path[node] = true;
+        let loop_chars = self.loop_chars(node);
+        let repeat =
+            (!looped && !loop_chars.is_empty()).then(|| format!("{}+", pattern_atom(&loop_chars)));
+        if let Some(value) = self.nodes[node].get_value() {
+            grammar.push_str(&format!("{} = {prefix}\n", name_of(value)));
+            if let Some(repeat) = &repeat {
+                grammar.push_str(&format!("{} = {prefix}{repeat}\n", name_of(value)));
+            }
+        }
+        // nodes with the same value and transitions, self-loops included
+        let signature = |target: usize| {
+            let children: Vec<Option<usize>> = self.nodes[target]
+                .children
+                .iter()
+                .map(|c| c.map(|c| if c == target { usize::MAX } else { c }))
+                .collect();
+            (self.nodes[target].get_value().map(name_of), children)
+        };
+        let mut groups: Vec<(Vec<u8>, usize)> = vec![];
+        for (pos, ch) in self.alphabet.bytes().enumerate() {
+            let next = match self.nodes[node].get_children(pos) {
+                Some(&next) if next != node => next,
+                _ => continue,
+            };
+            match groups
+                .iter_mut()
+                .find(|(_, target)| signature(*target) == signature(next))
+            {
+                Some((chars, _)) => chars.push(ch),
+                None => groups.push((vec![ch], next)),
+            }
+        }
+        for (chars, next) in groups {
+            let looped = self.loop_chars(next) == chars;
+            let mut atom = pattern_atom(&chars);
+            if looped {
+                atom.push('+');
+            }
+            let prefixes = match &repeat {
+                Some(repeat) => vec![format!("{prefix}{atom}"), format!("{prefix}{repeat}{atom}")],
+                None => vec![format!("{prefix}{atom}")],
+            };
+            for prefix in prefixes {
+                self.write_grammar(next, prefix, looped, on_path, name_of, grammar);
+            }
+        }
+        on_path[node] = false;
+    }
 
-        let result = t.get("b");
-        assert!(matches!(result, Err(TableError::InvalidInput('b'))));
+    fn write_tree(
+        &self,
+        node: usize,
+        depth: usize,
+        collapse: bool,
+        visited: &mut [bool],
+        tree: &mut String,
+    ) {
+        for (label, target) in self.edges(node, collapse) {
+            tree.push_str(&"  ".repeat(depth));
+            tree.push_str(&format!("{label} -> {target}"));
+            if let Some(value) = self.nodes[target].get_value() {
+                tree.push_str(&format!(" = {value:?}"));
+            }
+            if target == node {
+                tree.push_str(" (loop)\n");
+            } else if visited[target] {
+                tree.push_str(" (seen)\n");
+            } else {
+                tree.push('\n');
+                visited[target] = true;
+                self.write_tree(target, depth + 1, collapse, visited, tree);
+            }
+        }
     }
+}
 
-    #[test]
-    fn edge_very_long_pattern() {
-        let mut t = alpha();
-        let long_pattern = "a".repeat(1000);
-        t.add(&long_pattern, "long").unwrap();
+impl TryFrom<&str> for Table<()> {
+    type Error = TableError<()>;
 
-        assert_eq!(t.get(&long_pattern).unwrap(), Some(&"long"));
-        assert_eq!(t.get(&"a".repeat(999)).unwrap(), None);
+    fn try_from(alphabet: &str) -> Result<Self, Self::Error> {
+        if !alphabet.is_ascii() {
+            return Err(TableError::InvalidString(alphabet.to_string()));
+        }
+        Ok(Table::new(alphabet.to_string()))
     }
+}
 
-    #[test]
-    fn edge_special_chars_in_alphabet() {
-        let mut t: Table<&str> = Table::new("abc.+-_@#".to_string());
-        t.add("a.b", "dot").unwrap();
-        t.add("a-b", "dash").unwrap();
-        t.add("a@b", "at").unwrap();
-
-        assert_eq!(t.get("a.b").unwrap(), Some(&"dot"));
-        assert_eq!(t.get("a-b").unwrap(), Some(&"dash"));
-        assert_eq!(t.get("a@b").unwrap(), Some(&"at"));
+// Adds each pattern like `add`; panics on the first error, since `extend`
+// can't return it.
+impl<'a, T: Debug + Clone> Extend<(&'a str, T)> for Table<T> {
+    fn extend<I: IntoIterator<Item = (&'a str, T)>>(&mut self, iter: I) {
+        for (pattern, value) in iter {
+            if let Err(e) = self.add(pattern, value) {
+                panic!("cannot add pattern {pattern:?}: {e}");
+            }
+        }
     }
+}
 
-    // ========================================================================
-    // GENERIC TYPES
-    // ========================================================================
+impl Table<()> {
+    // An acceptor for the patterns, over the alphabet they use; patterns
+    // may overlap, all their values are `()`.
+    pub fn matcher(patterns: &[&str]) -> Result<Self, TableError<()>> {
+        let mut dynamic = DynamicTable::new();
+        for pattern in patterns {
+            dynamic.add(pattern, ());
+        }
+        let mut table = Self::new_idempotent(dynamic.alphabet());
+        for pattern in patterns {
+            table.add(pattern, ())?;
+        }
+        Ok(table)
+    }
+}
 
-    #[test]
-    fn generic_integer() {
-        let mut t: Table<i32> = Table::new("abc".to_string());
-        t.add("a", 1).unwrap();
-        t.add("b", 2).unwrap();
-        t.add("c", 3).unwrap();
+impl<T: Debug + Clone + PartialEq> Table<T> {
+    // Adding a pattern again with an equal value succeeds without changes,
+    // only a different value is a `ValueAlreadyDefined` error.
+    pub fn new_idempotent(alphabet: String) -> Self {
+        let mut table = Self::new(alphabet);
+        table.keep_current = Some(T::eq);
+        table
+    }
 
-        assert_eq!(t.get("a").unwrap(), Some(&1));
-        assert_eq!(t.get("b").unwrap(), Some(&2));
-        assert_eq!(t.get("c").unwrap(), Some(&3));
+    // Checks `get` on every case, reporting all the mismatches one per line.
+    pub fn assert_matches(&self, cases: &[(&str, Option<&T>)]) -> Result<(), String> {
+        let failures: Vec<String> = cases
+            .iter()
+            .filter_map(|(input, expected)| match self.get(input) {
+                Ok(actual) if actual == *expected => None,
+                Ok(actual) => Some(format!("{input:?}: expected {expected:?}, got {actual:?}")),
+                Err(e) => Some(format!("{input:?}: expected {expected:?}, got error: {e}")),
+            })
+            .collect();
+        if failures.is_empty() {
+            Ok(())
+        } else {
+            Err(failures.join("\n"))
+        }
     }
 
-    #[test]
-    fn generic_struct() {
-        #[derive(Debug, Clone, PartialEq)]
-        struct Token {
-            kind: String,
-            priority: u8,
+    // Adds every pattern produced by `factory`, a pattern generated again with
+    // the same value is skipped. Returns how many patterns were added.
+    pub fn add_generated<I, F>(&mut self, factory: F) -> Result<usize, TableError<T>>
+    where
+        I: IntoIterator<Item = (String, T)>,
+        F: FnOnce() -> I,
+    {
+        let mut added: Vec<(String, T)> = vec![];
+        for (pattern, value) in factory() {
+            if added.iter().any(|(p, v)| *p == pattern && *v == value) {
+                continue;
+            }
+            self.add(&pattern, value.clone())?;
+            added.push((pattern, value));
         }
+        Ok(added.len())
+    }
 
-        let mut t: Table<Token> = Table::new("+-*/".to_string());
-        t.add(
-            "+",
-            Token {
-                kind: "add".into(),
+    // Checks that only the terminals of `s` hold a value: any other node on
+    // its paths, set by another pattern sharing it, would let the lexer emit
+    // a token for an incomplete match. The error lists those nodes.
+    pub fn verify_no_internal_values(&self, s: &str) -> Result<(), TableError<T>> {
+        if !s.is_ascii() {
+            return Err(TableError::InvalidString(s.to_string()));
+        }
+        let pattern = Pattern::parse(s, &self.literals)?;
+        let mut currents = vec![0];
+        let mut internal = vec![];
+        let mut group_starts = vec![];
+        for (i, atom) in pattern.atoms().iter().enumerate() {
+            let groups = pattern.groups();
+            if groups.iter().any(|group| group.atoms.start == i) {
+                group_starts = currents.clone();
+            }
+            let range = self.resolve(atom)?;
+            let mut next: Vec<usize> = currents
+                .iter()
+                .flat_map(|current| {
+                    range
+                        .iter()
+                        .filter_map(|pos| self.nodes[*current].get_children(*pos).copied())
+                })
+                .collect();
+            if atom.repeat.optional() {
+                next.extend(&currents);
+            }
+            // a skipped group
+            if groups
+                .iter()
+                .any(|group| group.atoms.end == i + 1 && group.repeat.optional())
+            {
+                next.extend(&group_starts);
+            }
+            internal.extend(currents);
+            next.sort_unstable();
+            next.dedup();
+            currents = next;
+        }
+        let stray: Vec<(String, T)> = self
+            .node_keys()
+            .into_iter()
+            .filter(|(node, _)| *node != 0 && internal.contains(node) && !currents.contains(node))
+            .filter_map(|(node, key)| Some((key, self.nodes[node].get_value()?.clone())))
+            .collect();
+        if stray.is_empty() {
+            Ok(())
+        } else {
+            Err(TableError::InternalValues(stray))
+        }
+    }
+
+    // Sets `value` on every terminal of `s`, replacing the current ones. The
+    // terminals must not hold different values: then nothing is replaced and
+    // the error lists the key and value of each terminal. On error the
+    // table is left unchanged.
+    pub fn add_or_replace(&mut self, s: &str, value: T) -> Result<(), TableError<T>> {
+        let snapshot = self.snapshot();
+        let terminals = match self.insert(s) {
+            Ok(terminals) => terminals,
+            Err(e) => {
+                self.undo(snapshot);
+                return Err(e);
+            }
+        };
+        let mut current: Vec<&T> = terminals
+            .iter()
+            .filter_map(|t| self.nodes[*t].get_value())
+            .collect();
+        current.dedup();
+        if current.len() > 1 {
+            let conflicts = self
+                .node_keys()
+                .into_iter()
+                .filter(|(node, _)| terminals.contains(node))
+                .filter_map(|(node, key)| Some((key, self.nodes[node].get_value()?.clone())))
+                .collect();
+            self.undo(snapshot);
+            return Err(TableError::ConflictingValues(conflicts));
+        }
+        for terminal in terminals {
+            self.node_mut(terminal).value = Some(value.clone());
+        }
+        Ok(())
+    }
+
+    // Lexes `input` and reports the first token that differs from `expected`.
+    pub fn verify_against(&self, input: &str, expected: &[(T, &str)]) -> Result<(), String> {
+        let mut tokens = self.lexer(input).map_err(|e| e.to_string())?;
+        for (index, (value, text)) in expected.iter().enumerate() {
+            match tokens.next() {
+                Some(Ok((v, t))) if v == value && t == *text => {}
+                Some(Ok((v, t))) => {
+                    return Err(format!(
+                        "token {index}: expected ({value:?}, {text:?}), got ({v:?}, {t:?})"
+                    ));
+                }
+                Some(Err(e)) => {
+                    return Err(format!(
+                        "token {index}: expected ({value:?}, {text:?}), got error: {e}"
+                    ));
+                }
+                None => {
+                    return Err(format!("expected {} tokens, got {index}", expected.len()));
+                }
+            }
+        }
+        let extra = tokens.count();
+        if extra > 0 {
+            return Err(format!(
+                "expected {} tokens, got {}",
+                expected.len(),
+                expected.len() + extra
+            ));
+        }
+        Ok(())
+    }
+}
+
+impl<T: Debug + Clone + Eq + Hash> Table<T> {
+    pub fn token_histogram<'a>(&'a self, s: &'a str) -> Result<HashMap<&'a T, usize>, LexerError> {
+        self.lexer(s)?
+            .try_fold(HashMap::new(), |mut histogram, token| {
+                let (value, _) = token?;
+                *histogram.entry(value).or_insert(0) += 1;
+                Ok(histogram)
+            })
+    }
+}
+
+// The chars in the alphabet of `a` but not of `b`, and the other way round,
+// each once in alphabet order: why a check such as `is_disjoint` fails with
+// `AlphabetMismatch`.
+pub fn alphabet_diff<T>(a: &Table<T>, b: &Table<T>) -> (Vec<char>, Vec<char>) {
+    let missing = |from: &str, other: &str| -> Vec<char> {
+        from.char_indices()
+            .filter(|(i, ch)| from.find(*ch) == Some(*i) && !other.contains(*ch))
+            .map(|(_, ch)| ch)
+            .collect()
+    };
+    (
+        missing(&a.alphabet, &b.alphabet),
+        missing(&b.alphabet, &a.alphabet),
+    )
+}
+
+// The byte range of `text`, a slice of `s`.
+fn span_in(s: &str, text: &str) -> Range<usize> {
+    let start = text.as_ptr() as usize - s.as_ptr() as usize;
+    start..start + text.len()
+}
+
+// Position in the alphabet of each byte, the first one for duplicated chars.
+pub(crate) fn byte_lookup(alphabet: &str) -> [Option<usize>; 256] {
+    let mut lookup = [None; 256];
+    for (pos, ch) in alphabet.bytes().enumerate() {
+        if lookup[ch as usize].is_none() {
+            lookup[ch as usize] = Some(pos);
+        }
+    }
+    lookup
+}
+
+// Renders a set of characters as a single char or a class such as `[a-cx]`.
+fn class_label(mut chars: Vec<u8>) -> String {
+    if chars.len() == 1 {
+        return (chars[0] as char).to_string();
+    }
+    chars.sort_unstable();
+    chars.dedup();
+    let mut label = String::from("[");
+    let mut i = 0;
+    while i < chars.len() {
+        let mut j = i;
+        while j + 1 < chars.len() && chars[j + 1] == chars[j] + 1 {
+            j += 1;
+        }
+        if j - i >= 2 {
+            label.push(chars[i] as char);
+            label.push('-');
+            label.push(chars[j] as char);
+        } else {
+            chars[i..=j].iter().for_each(|c| label.push(*c as char));
+        }
+        i = j + 1;
+    }
+    label.push(']');
+    label
+}
+
+// Renders characters as a pattern atom, using a class for the
+// metacharacters. In a class a leading `]` is a member, a `-` right after it
+// can't be read as a range, a `[` at the end can't open `[:`, and a trailing
+// `^` can't be read as a negation.
+fn pattern_atom(chars: &[u8]) -> String {
+    match chars {
+        [ch] if !b"[+*?{|.\\(".contains(ch) => (*ch as char).to_string(),
+        _ => {
+            let mut class = String::from("[");
+            for first in b"]-" {
+                if chars.contains(first) {
+                    class.push(*first as char);
+                }
+            }
+            class.extend(
+                chars
+                    .iter()
+                    .filter(|ch| !b"]-[^".contains(ch))
+                    .map(|ch| *ch as char),
+            );
+            for last in b"[^" {
+                if chars.contains(last) {
+                    class.push(*last as char);
+                }
+            }
+            class.push(']');
+            class
+        }
+    }
+}
+
+fn escape_dot(s: &str) -> String {
+    s.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+#[derive(Debug, PartialEq)]
+pub struct CapturedToken<'a, T> {
+    pub value: &'a T,
+    pub text: &'a str,
+    pub captures: Vec<(&'a str, char)>,
+}
+
+// A token of `Table::lex_spanned`: value, text and byte span.
+pub type SpannedToken<'a, T> = (&'a T, &'a str, Range<usize>);
+
+#[derive(Debug, PartialEq)]
+pub enum LexerEvent<'a, T> {
+    Token {
+        kind: &'a T,
+        text: &'a str,
+        span: Range<usize>,
+    },
+    Error {
+        error: LexerError,
+        span: Range<usize>,
+    },
+}
+
+pub struct TableIterator<'a, T> {
+    table: &'a Table<T>,
+    input: &'a str,
+    index: usize,
+    separators: Option<&'a HashSet<char>>,
+    min_len: usize,
+    last_token: &'a str,
+    _phantom: PhantomData<T>,
+}
+
+impl<'a, T> TableIterator<'a, T> {
+    // Alphabet position of each char of the last token returned, showing
+    // which member of a class each char matched.
+    pub fn last_token_positions(&self) -> Vec<usize> {
+        self.last_token
+            .bytes()
+            .filter_map(|ch| self.table.lookup[ch as usize])
+            .collect()
+    }
+}
+
+impl<'a, T: Debug + Clone> Iterator for TableIterator<'a, T> {
+    type Item = Result<(&'a T, &'a str), LexerError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while self.index < self.input.len() {
+            match self.table.longest_match_min(
+                self.input,
+                self.index,
+                self.separators,
+                self.min_len,
+            ) {
+                Ok((Some(value), end)) => {
+                    let content = &self.input[self.index..end];
+                    self.index = end;
+                    self.last_token = content;
+                    return Some(Ok((value, content)));
+                }
+                Ok((None, end)) => self.index = end,
+                Err(e) => {
+                    // an error ends the tokenization
+                    self.index = self.input.len();
+                    return Some(Err(e));
+                }
+            }
+        }
+        None
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        // every token consumes at least one byte
+        (0, Some(self.input.len() - self.index))
+    }
+}
+
+const READ_CHUNK: usize = 4096;
+
+pub struct ReaderLexer<'a, T, R> {
+    table: &'a Table<T>,
+    reader: R,
+    // unconsumed input, starting at byte `position` of the stream
+    buffer: Vec<u8>,
+    position: usize,
+    eof: bool,
+    done: bool,
+}
+
+impl<'a, T: Debug + Clone, R: Read> ReaderLexer<'a, T, R> {
+    // Makes `buffer[index]` available unless the stream ends first.
+    fn fill(&mut self, index: usize) -> Result<bool, LexerError> {
+        while index >= self.buffer.len() && !self.eof {
+            let len = self.buffer.len();
+            self.buffer.resize(len + READ_CHUNK, 0);
+            let read = loop {
+                match self.reader.read(&mut self.buffer[len..]) {
+                    Err(e) if e.kind() == ErrorKind::Interrupted => continue,
+                    read => break read,
+                }
+            };
+            let read = read.map_err(|e| LexerError::Io(e.to_string()));
+            self.buffer.truncate(len + *read.as_ref().unwrap_or(&0));
+            if read? == 0 {
+                self.eof = true;
+            }
+        }
+        Ok(index < self.buffer.len())
+    }
+
+    // The next match, with no value for a skip pattern.
+    fn next_match(&mut self) -> Result<Option<(Option<T>, String)>, LexerError> {
+        if !self.fill(0)? {
+            return Ok(None);
+        }
+        let table = self.table;
+        let mut node_id = 0;
+        let mut last_match = None;
+        let mut index = 0;
+        while self.fill(index)? {
+            let ch = self.buffer[index];
+            let pos = table.lookup[ch as usize].ok_or(LexerError::UnknownChar {
+                char: ch as char,
+                position: self.position + index,
+            })?;
+            match table.nodes[node_id].get_children(pos) {
+                Some(&next) => {
+                    let node = &table.nodes[next];
+                    if (node.has_value() || node.skip)
+                        && (!node.eof_only || !self.fill(index + 1)?)
+                    {
+                        last_match = Some((node.get_value(), index + 1));
+                    }
+                    node_id = next;
+                    index += 1;
+                }
+                None => {
+                    if index == 0 {
+                        last_match = table.fallback.as_ref().map(|value| (Some(value), 1));
+                    }
+                    break;
+                }
+            }
+        }
+        let (value, end) = last_match.ok_or(LexerError::UnexpectedEnd {
+            position: self.position,
+        })?;
+        let content: String = self.buffer.drain(..end).map(char::from).collect();
+        self.position += end;
+        Ok(Some((value.cloned(), content)))
+    }
+
+    fn next_token(&mut self) -> Result<Option<(T, String)>, LexerError> {
+        while let Some((value, content)) = self.next_match()? {
+            if let Some(value) = value {
+                return Ok(Some((value, content)));
+            }
+        }
+        Ok(None)
+    }
+}
+
+impl<'a, T: Debug + Clone, R: Read> Iterator for ReaderLexer<'a, T, R> {
+    type Item = Result<(T, String), LexerError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        let token = self.next_token().transpose();
+        // an error ends the tokenization
+        if !matches!(token, Some(Ok(_))) {
+            self.done = true;
+        }
+        token
+    }
+}
+
+pub struct InternedLexer<'a, T> {
+    tokens: TableIterator<'a, T>,
+    interned: HashSet<Arc<str>>,
+}
+
+impl<'a, T: Debug + Clone> Iterator for InternedLexer<'a, T> {
+    type Item = Result<(T, Arc<str>), LexerError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        Some(self.tokens.next()?.map(|(value, content)| {
+            let text = match self.interned.get(content) {
+                Some(text) => Arc::clone(text),
+                None => {
+                    let text: Arc<str> = Arc::from(content);
+                    self.interned.insert(Arc::clone(&text));
+                    text
+                }
+            };
+            (value.clone(), text)
+        }))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.tokens.size_hint()
+    }
+}
+
+pub struct OwnedLexer<T> {
+    table: Table<T>,
+    input: String,
+    index: usize,
+}
+
+impl<T: Debug + Clone> Iterator for OwnedLexer<T> {
+    type Item = Result<(T, String), LexerError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while self.index < self.input.len() {
+            match self.table.longest_match(&self.input, self.index, None) {
+                Ok((Some(value), end)) => {
+                    let content = self.input[self.index..end].to_string();
+                    self.index = end;
+                    return Some(Ok((value.clone(), content)));
+                }
+                Ok((None, end)) => self.index = end,
+                Err(e) => {
+                    self.index = self.input.len();
+                    return Some(Err(e));
+                }
+            }
+        }
+        None
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (0, Some(self.input.len() - self.index))
+    }
+}
+
+pub struct LossyIterator<'a, T> {
+    table: &'a Table<T>,
+    input: &'a str,
+    index: usize,
+    coalesce_unknown: bool,
+    max_errors: Option<usize>,
+    errors: usize,
+}
+
+impl<'a, T: Debug + Clone> LossyIterator<'a, T> {
+    pub fn errors_emitted(&self) -> usize {
+        self.errors
+    }
+
+    fn match_at(&self, start: usize) -> Result<(Option<&'a T>, usize), LexerError> {
+        match self.table.longest_match(self.input, start, None) {
+            // the token before an unknown character is still emitted
+            Err(LexerError::UnknownChar { position, .. }) if position > start => {
+                self.table.match_until(self.input, start, position, None, 0)
+            }
+            result => result,
+        }
+    }
+
+    fn recover(&mut self, start: usize) -> Result<(Option<&'a T>, &'a str), (LexerError, &'a str)> {
+        match self.match_at(start) {
+            Ok((value, end)) => {
+                self.index = end;
+                Ok((value, &self.input[start..end]))
+            }
+            Err(e @ LexerError::UnknownChar { .. }) => {
+                let bytes = self.input.as_bytes();
+                let mut end = start + 1;
+                if self.coalesce_unknown {
+                    while end < bytes.len() && !self.table.alphabet.contains(bytes[end] as char) {
+                        end += 1;
+                    }
+                }
+                self.index = end;
+                Err((e, &self.input[start..end]))
+            }
+            Err(e) => {
+                // skip one byte at a time until a token (or an unknown char)
+                // can start: the error always covers at least one byte
+                let mut end = start + 1;
+                while end < self.input.len()
+                    && matches!(self.match_at(end), Err(LexerError::UnexpectedEnd { .. }))
+                {
+                    end += 1;
+                }
+                self.index = end;
+                Err((e, &self.input[start..end]))
+            }
+        }
+    }
+}
+
+impl<'a, T: Debug + Clone> Iterator for LossyIterator<'a, T> {
+    type Item = Result<(&'a T, &'a str), (LexerError, &'a str)>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while self.index < self.input.len() && self.max_errors != Some(self.errors) {
+            match self.recover(self.index) {
+                Ok((Some(value), text)) => return Some(Ok((value, text))),
+                Ok((None, _)) => {}
+                Err(e) => {
+                    self.errors += 1;
+                    return Some(Err(e));
+                }
+            }
+        }
+        None
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (0, Some(self.input.len() - self.index))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn alpha() -> Table<&'static str> {
+        Table::new("abcdefghijklmnopqrstuvwxyz".to_string())
+    }
+
+    fn digits() -> Table<i32> {
+        Table::new("0123456789".to_string())
+    }
+
+    fn alphanum() -> Table<&'static str> {
+        Table::new("abcdefghijklmnopqrstuvwxyz0123456789".to_string())
+    }
+
+    // ========================================================================
+    // LITERAL STRINGS
+    // ========================================================================
+
+    #[test]
+    fn literal_simple() {
+        let mut t = alpha();
+        t.add("hello", "greeting").unwrap();
+        assert_eq!(t.get("hello").unwrap(), Some(&"greeting"));
+    }
+
+    #[test]
+    fn literal_single_char() {
+        let mut t = alpha();
+        t.add("a", "letter").unwrap();
+        assert_eq!(t.get("a").unwrap(), Some(&"letter"));
+        assert_eq!(t.get("b").unwrap(), None);
+    }
+
+    #[test]
+    fn literal_empty_string() {
+        let mut t = alpha();
+        t.add("", "empty").unwrap();
+        assert_eq!(t.get("").unwrap(), Some(&"empty"));
+    }
+
+    #[test]
+    fn literal_multiple_patterns() {
+        let mut t = alpha();
+        t.add("cat", "animal").unwrap();
+        t.add("car", "vehicle").unwrap();
+        t.add("card", "object").unwrap();
+
+        assert_eq!(t.get("cat").unwrap(), Some(&"animal"));
+        assert_eq!(t.get("car").unwrap(), Some(&"vehicle"));
+        assert_eq!(t.get("card").unwrap(), Some(&"object"));
+        assert_eq!(t.get("ca").unwrap(), None);
+    }
+
+    #[test]
+    fn literal_shared_prefix() {
+        let mut t = alpha();
+        t.add("test", "v1").unwrap();
+        t.add("testing", "v2").unwrap();
+
+        assert_eq!(t.get("test").unwrap(), Some(&"v1"));
+        assert_eq!(t.get("testing").unwrap(), Some(&"v2"));
+        assert_eq!(t.get("testi").unwrap(), None);
+    }
+
+    #[test]
+    fn literal_prefix_not_matched() {
+        let mut t = alpha();
+        t.add("hello", "greeting").unwrap();
+        assert_eq!(t.get("hel").unwrap(), None);
+        assert_eq!(t.get("helloo").unwrap(), None);
+    }
+
+    // ========================================================================
+    // CHARACTER CLASSES [abc]
+    // ========================================================================
+
+    #[test]
+    fn class_simple() {
+        let mut t = alpha();
+        t.add("[abc]", "first_three").unwrap();
+
+        assert_eq!(t.get("a").unwrap(), Some(&"first_three"));
+        assert_eq!(t.get("b").unwrap(), Some(&"first_three"));
+        assert_eq!(t.get("c").unwrap(), Some(&"first_three"));
+        assert_eq!(t.get("d").unwrap(), None);
+    }
+
+    #[test]
+    fn class_single_element() {
+        let mut t = alpha();
+        t.add("[a]", "just_a").unwrap();
+        assert_eq!(t.get("a").unwrap(), Some(&"just_a"));
+        assert_eq!(t.get("b").unwrap(), None);
+    }
+
+    #[test]
+    fn class_in_middle() {
+        let mut t = alpha();
+        t.add("c[aou]t", "words").unwrap();
+
+        assert_eq!(t.get("cat").unwrap(), Some(&"words"));
+        assert_eq!(t.get("cot").unwrap(), Some(&"words"));
+        assert_eq!(t.get("cut").unwrap(), Some(&"words"));
+        assert_eq!(t.get("cet").unwrap(), None);
+        assert_eq!(t.get("cit").unwrap(), None);
+    }
+
+    #[test]
+    fn class_multiple() {
+        let mut t = alpha();
+        t.add("[ab][xy]", "combo").unwrap();
+
+        assert_eq!(t.get("ax").unwrap(), Some(&"combo"));
+        assert_eq!(t.get("ay").unwrap(), Some(&"combo"));
+        assert_eq!(t.get("bx").unwrap(), Some(&"combo"));
+        assert_eq!(t.get("by").unwrap(), Some(&"combo"));
+        assert_eq!(t.get("cx").unwrap(), None);
+        assert_eq!(t.get("az").unwrap(), None);
+    }
+
+    #[test]
+    fn class_consecutive() {
+        let mut t = alpha();
+        t.add("[a][b][c]", "abc").unwrap();
+        assert_eq!(t.get("abc").unwrap(), Some(&"abc"));
+        assert_eq!(t.get("aaa").unwrap(), None);
+    }
+
+    #[test]
+    fn class_with_duplicates() {
+        let mut t = alpha();
+        // [aaa] should be treated as [a]
+        t.add("[aaa]", "triple").unwrap();
+        assert_eq!(t.get("a").unwrap(), Some(&"triple"));
+        assert_eq!(t.get("aa").unwrap(), None);
+    }
+
+    #[test]
+    fn class_preserves_order() {
+        let mut t = alpha();
+        // duplicates should be ignored, keeping first occurrence
+        t.add("[abab]", "val").unwrap();
+        assert_eq!(t.get("a").unwrap(), Some(&"val"));
+        assert_eq!(t.get("b").unwrap(), Some(&"val"));
+    }
+
+    // ========================================================================
+    // PLUS OPERATOR (+)
+    // ========================================================================
+
+    #[test]
+    fn plus_single_char() {
+        let mut t = alpha();
+        t.add("a+", "as").unwrap();
+
+        assert_eq!(t.get("a").unwrap(), Some(&"as"));
+        assert_eq!(t.get("aa").unwrap(), Some(&"as"));
+        assert_eq!(t.get("aaa").unwrap(), Some(&"as"));
+        assert_eq!(t.get("").unwrap(), None);
+    }
+
+    #[test]
+    fn plus_with_prefix() {
+        let mut t = alpha();
+        t.add("ba+", "b_then_as").unwrap();
+
+        assert_eq!(t.get("ba").unwrap(), Some(&"b_then_as"));
+        assert_eq!(t.get("baa").unwrap(), Some(&"b_then_as"));
+        assert_eq!(t.get("baaa").unwrap(), Some(&"b_then_as"));
+        assert_eq!(t.get("b").unwrap(), None);
+    }
+
+    #[test]
+    fn plus_with_suffix() {
+        let mut t = alpha();
+        t.add("a+b", "as_then_b").unwrap();
+
+        assert_eq!(t.get("ab").unwrap(), Some(&"as_then_b"));
+        assert_eq!(t.get("aab").unwrap(), Some(&"as_then_b"));
+        assert_eq!(t.get("aaab").unwrap(), Some(&"as_then_b"));
+        assert_eq!(t.get("a").unwrap(), None);
+        assert_eq!(t.get("b").unwrap(), None);
+    }
+
+    #[test]
+    fn plus_with_class() {
+        let mut t = alpha();
+        t.add("[ab]+", "ab_repeated").unwrap();
+
+        assert_eq!(t.get("a").unwrap(), Some(&"ab_repeated"));
+        assert_eq!(t.get("b").unwrap(), Some(&"ab_repeated"));
+        assert_eq!(t.get("ab").unwrap(), Some(&"ab_repeated"));
+        assert_eq!(t.get("ba").unwrap(), Some(&"ab_repeated"));
+        assert_eq!(t.get("aabb").unwrap(), Some(&"ab_repeated"));
+        assert_eq!(t.get("abab").unwrap(), Some(&"ab_repeated"));
+        assert_eq!(t.get("c").unwrap(), None);
+        assert_eq!(t.get("").unwrap(), None);
+    }
+
+    #[test]
+    fn plus_multiple() {
+        let mut t = alpha();
+        t.add("a+b+", "as_then_bs").unwrap();
+
+        assert_eq!(t.get("ab").unwrap(), Some(&"as_then_bs"));
+        assert_eq!(t.get("aab").unwrap(), Some(&"as_then_bs"));
+        assert_eq!(t.get("abb").unwrap(), Some(&"as_then_bs"));
+        assert_eq!(t.get("aabb").unwrap(), Some(&"as_then_bs"));
+        assert_eq!(t.get("aaabbb").unwrap(), Some(&"as_then_bs"));
+    }
+
+    #[test]
+    fn plus_in_middle() {
+        let mut t = alpha();
+        t.add("a+bc", "pattern").unwrap();
+
+        assert_eq!(t.get("abc").unwrap(), Some(&"pattern"));
+        assert_eq!(t.get("aabc").unwrap(), Some(&"pattern"));
+        assert_eq!(t.get("aaabc").unwrap(), Some(&"pattern"));
+        assert_eq!(t.get("bc").unwrap(), None);
+    }
+
+    #[test]
+    fn plus_long_match() {
+        let mut t = alpha();
+        t.add("a+", "many").unwrap();
+
+        let long = "a".repeat(10000);
+        assert_eq!(t.get(&long).unwrap(), Some(&"many"));
+    }
+
+    // ========================================================================
+    // AMBIGUITY / CONVERGENCE (paths converging to same node)
+    // ========================================================================
+
+    #[test]
+    fn ambiguity_plus_followed_by_class() {
+        let mut t: Table<&str> = Table::new("ab".to_string());
+        // [ab]+[ab] creates duplicate currents due to self-loops
+        let result = t.add("[ab]+[ab]", "value");
+        assert!(result.is_ok());
+
+        // Semantically equivalent to [ab]+
+        assert_eq!(t.get("a").unwrap(), Some(&"value"));
+        assert_eq!(t.get("b").unwrap(), Some(&"value"));
+        assert_eq!(t.get("ab").unwrap(), Some(&"value"));
+        assert_eq!(t.get("ba").unwrap(), Some(&"value"));
+    }
+
+    #[test]
+    fn ambiguity_plus_then_same_char() {
+        let mut t: Table<&str> = Table::new("a".to_string());
+        // a+a: the second 'a' is absorbed by the self-loop
+        // so it's semantically equivalent to a+
+        t.add("a+a", "value").unwrap();
+
+        assert_eq!(t.get("a").unwrap(), Some(&"value"));
+        assert_eq!(t.get("aa").unwrap(), Some(&"value"));
+        assert_eq!(t.get("aaa").unwrap(), Some(&"value"));
+    }
+
+    #[test]
+    fn ambiguity_class_plus_then_subset() {
+        let mut t: Table<&str> = Table::new("ab".to_string());
+        // a+[ab]:
+        // - 'a' after + loops back, so a+a = a+
+        // - 'b' after + creates new final state
+        // Result: matches "a", "aa", "ab", "aab", etc.
+        t.add("a+[ab]", "value").unwrap();
+
+        assert_eq!(t.get("a").unwrap(), Some(&"value")); // a+ part
+        assert_eq!(t.get("aa").unwrap(), Some(&"value")); // a+ looping
+        assert_eq!(t.get("ab").unwrap(), Some(&"value")); // a then b
+        assert_eq!(t.get("aaa").unwrap(), Some(&"value"));
+        assert_eq!(t.get("aab").unwrap(), Some(&"value"));
+    }
+
+    // ========================================================================
+    // ERROR HANDLING
+    // ========================================================================
+
+    #[test]
+    fn error_invalid_char_not_in_alphabet() {
+        let mut t = alpha();
+        let result = t.add("hello1", "with_digit");
+        assert!(matches!(result, Err(TableError::InvalidInput('1'))));
+    }
+
+    #[test]
+    fn error_invalid_char_in_class() {
+        let mut t = alpha();
+        let result = t.add("[abc1]", "invalid");
+        assert!(matches!(result, Err(TableError::InvalidInput('1'))));
+    }
+
+    #[test]
+    fn error_unclosed_bracket() {
+        let mut t = alpha();
+        let result = t.add("[abc", "unclosed");
+        assert_eq!(result, Err(TableError::UnclosedClass));
+    }
+
+    #[test]
+    fn error_unclosed_empty_bracket() {
+        let mut t = alpha();
+        assert_eq!(t.add("a[", "unclosed"), Err(TableError::UnclosedClass));
+    }
+
+    #[test]
+    fn error_empty_class() {
+        let mut t = alpha();
+        let result = t.add("[]", "empty");
+        assert_eq!(result, Err(TableError::EmptyClass));
+    }
+
+    #[test]
+    fn error_empty_class_with_plus() {
+        let mut t = alpha();
+        let result = t.add("[]+", "empty_plus");
+        assert_eq!(result, Err(TableError::EmptyClass));
+    }
+
+    #[test]
+    fn error_non_ascii_add() {
+        let mut t = alpha();
+        let result = t.add("héllo", "accented");
+        assert!(matches!(result, Err(TableError::InvalidString(_))));
+    }
+
+    #[test]
+    fn error_non_ascii_get() {
+        let t = alpha();
+        let result = t.get("héllo");
+        assert!(matches!(result, Err(TableError::InvalidString(_))));
+    }
+
+    #[test]
+    fn error_emoji() {
+        let mut t = alpha();
+        let result = t.add("hello😀", "emoji");
+        assert!(matches!(result, Err(TableError::InvalidString(_))));
+    }
+
+    #[test]
+    fn error_duplicate_value() {
+        let mut t = alpha();
+        t.add("hello", "first").unwrap();
+        let result = t.add("hello", "second");
+        assert!(matches!(
+            result,
+            Err(TableError::ValueAlreadyDefined { .. })
+        ));
+    }
+
+    #[test]
+    fn error_duplicate_via_class_overlap() {
+        let mut t = alpha();
+        t.add("a", "literal").unwrap();
+        let result = t.add("[abc]", "class");
+        assert!(matches!(
+            result,
+            Err(TableError::ValueAlreadyDefined { .. })
+        ));
+    }
+
+    #[test]
+    fn error_duplicate_via_plus_overlap() {
+        let mut t = alpha();
+        t.add("[ab]+", "pattern1").unwrap();
+        let result = t.add("a", "pattern2");
+        assert!(matches!(
+            result,
+            Err(TableError::ValueAlreadyDefined { .. })
+        ));
+    }
+
+    #[test]
+    fn error_get_char_not_in_alphabet() {
+        let mut t = alpha();
+        t.add("hello", "greeting").unwrap();
+        let result = t.get("hello!");
+        assert!(matches!(result, Err(TableError::InvalidInput('!'))));
+    }
+
+    // ========================================================================
+    // EDGE CASES
+    // ========================================================================
+
+    #[test]
+    fn edge_star_at_start_is_literal() {
+        let mut t: Table<&str> = Table::new("a*".to_string());
+        // * at the start is not an operator, just a literal
+        t.add("*a", "star").unwrap();
+        assert_eq!(t.get("*a").unwrap(), Some(&"star"));
+        assert_eq!(t.get("a").unwrap(), None);
+    }
+
+    #[test]
+    fn edge_plus_at_start_is_literal() {
+        let mut t: Table<&str> = Table::new("+a".to_string());
+        t.add("+a", "plus").unwrap();
+        assert_eq!(t.get("+a").unwrap(), Some(&"plus"));
+    }
+
+    #[test]
+    fn edge_bracket_in_alphabet() {
+        let mut t: Table<&str> = Table::new("a[]".to_string());
+        // When [ is in alphabet but used as operator, it's still parsed as operator
+        let result = t.add("[a]", "test");
+        assert!(result.is_ok());
+        assert_eq!(t.get("a").unwrap(), Some(&"test"));
+    }
+
+    #[test]
+    fn edge_nested_brackets() {
+        let mut t = alpha();
+        // [[ab]] - inner [ is looked up in alphabet, not found
+        let result = t.add("[[ab]]", "nested");
+        assert!(matches!(result, Err(TableError::InvalidInput('['))));
+    }
+
+    #[test]
+    fn edge_empty_alphabet() {
+        let mut t: Table<&str> = Table::new("".to_string());
+        t.add("", "empty").unwrap();
+        assert_eq!(t.get("").unwrap(), Some(&"empty"));
+
+        let result = t.add("a", "should_fail");
+        assert!(matches!(result, Err(TableError::InvalidInput('a'))));
+    }
+
+    #[test]
+    fn edge_single_char_alphabet() {
+        let mut t: Table<i32> = Table::new("a".to_string());
+        t.add("a+", 42).unwrap();
+
+        assert_eq!(t.get("a").unwrap(), Some(&42));
+        assert_eq!(t.get("aaa").unwrap(), Some(&42));
+
+        let result = t.get("b");
+        assert!(matches!(result, Err(TableError::InvalidInput('b'))));
+    }
+
+    #[test]
+    fn edge_very_long_pattern() {
+        let mut t = alpha();
+        let long_pattern = "a".repeat(1000);
+        t.add(&long_pattern, "long").unwrap();
+
+        assert_eq!(t.get(&long_pattern).unwrap(), Some(&"long"));
+        assert_eq!(t.get(&"a".repeat(999)).unwrap(), None);
+    }
+
+    #[test]
+    fn edge_special_chars_in_alphabet() {
+        let mut t: Table<&str> = Table::new("abc.+-_@#".to_string());
+        t.add("a.b", "dot").unwrap();
+        t.add("a-b", "dash").unwrap();
+        t.add("a@b", "at").unwrap();
+
+        assert_eq!(t.get("a.b").unwrap(), Some(&"dot"));
+        assert_eq!(t.get("a-b").unwrap(), Some(&"dash"));
+        assert_eq!(t.get("a@b").unwrap(), Some(&"at"));
+    }
+
+    // ========================================================================
+    // GENERIC TYPES
+    // ========================================================================
+
+    #[test]
+    fn generic_integer() {
+        let mut t: Table<i32> = Table::new("abc".to_string());
+        t.add("a", 1).unwrap();
+        t.add("b", 2).unwrap();
+        t.add("c", 3).unwrap();
+
+        assert_eq!(t.get("a").unwrap(), Some(&1));
+        assert_eq!(t.get("b").unwrap(), Some(&2));
+        assert_eq!(t.get("c").unwrap(), Some(&3));
+    }
+
+    #[test]
+    fn generic_struct() {
+        #[derive(Debug, Clone, PartialEq)]
+        struct Token {
+            kind: String,
+            priority: u8,
+        }
+
+        let mut t: Table<Token> = Table::new("+-*/".to_string());
+        t.add(
+            "+",
+            Token {
+                kind: "add".into(),
                 priority: 1,
             },
         )
@@ -745,926 +3153,4783 @@ mod tests {
         )
         .unwrap();
 
-        let plus = t.get("+").unwrap().unwrap();
-        assert_eq!(plus.kind, "add");
-        assert_eq!(plus.priority, 1);
+        let plus = t.get("+").unwrap().unwrap();
+        assert_eq!(plus.kind, "add");
+        assert_eq!(plus.priority, 1);
+
+        let mul = t.get("*").unwrap().unwrap();
+        assert_eq!(mul.kind, "mul");
+        assert_eq!(mul.priority, 2);
+    }
+
+    #[test]
+    fn generic_with_digits() {
+        let mut t = digits();
+        t.add("[0123456789]+", 42).unwrap();
+
+        assert_eq!(t.get("0").unwrap(), Some(&42));
+        assert_eq!(t.get("123").unwrap(), Some(&42));
+        assert_eq!(t.get("9876543210").unwrap(), Some(&42));
+    }
+
+    // ========================================================================
+    // COMPLEX PATTERNS
+    // ========================================================================
+
+    #[test]
+    fn complex_identifier_like() {
+        let mut t = alphanum();
+        // First char must be letter, rest can be letter or digit
+        t.add(
+            "[abcdefghijklmnopqrstuvwxyz][abcdefghijklmnopqrstuvwxyz0123456789]+",
+            "id",
+        )
+        .unwrap();
+
+        assert_eq!(t.get("ab").unwrap(), Some(&"id"));
+        assert_eq!(t.get("a1").unwrap(), Some(&"id"));
+        assert_eq!(t.get("var123").unwrap(), Some(&"id"));
+        assert_eq!(t.get("x9y8z7").unwrap(), Some(&"id"));
+
+        // Single letter doesn't match (needs at least 2 chars due to +)
+        assert_eq!(t.get("a").unwrap(), None);
+        // Starts with digit - no match
+        assert_eq!(t.get("1abc").unwrap(), None);
+    }
+
+    #[test]
+    fn complex_multiple_non_overlapping() {
+        let mut t = alpha();
+        t.add("get", "GET").unwrap();
+        t.add("put", "PUT").unwrap();
+        t.add("post", "POST").unwrap();
+        t.add("delete", "DELETE").unwrap();
+
+        assert_eq!(t.get("get").unwrap(), Some(&"GET"));
+        assert_eq!(t.get("put").unwrap(), Some(&"PUT"));
+        assert_eq!(t.get("post").unwrap(), Some(&"POST"));
+        assert_eq!(t.get("delete").unwrap(), Some(&"DELETE"));
+        assert_eq!(t.get("patch").unwrap(), None);
+    }
+
+    #[test]
+    fn complex_class_then_plus_then_literal() {
+        let mut t = alpha();
+        t.add("[abc]+x", "pattern").unwrap();
+
+        assert_eq!(t.get("ax").unwrap(), Some(&"pattern"));
+        assert_eq!(t.get("bx").unwrap(), Some(&"pattern"));
+        assert_eq!(t.get("abcx").unwrap(), Some(&"pattern"));
+        assert_eq!(t.get("aaabbbcccx").unwrap(), Some(&"pattern"));
+        assert_eq!(t.get("x").unwrap(), None);
+        assert_eq!(t.get("dx").unwrap(), None);
+    }
+
+    #[test]
+    fn complex_alternating_classes() {
+        let mut t: Table<&str> = Table::new("ab".to_string());
+        t.add("[ab][ab][ab]", "three").unwrap();
+
+        assert_eq!(t.get("aaa").unwrap(), Some(&"three"));
+        assert_eq!(t.get("bbb").unwrap(), Some(&"three"));
+        assert_eq!(t.get("aba").unwrap(), Some(&"three"));
+        assert_eq!(t.get("bab").unwrap(), Some(&"three"));
+        assert_eq!(t.get("ab").unwrap(), None);
+        assert_eq!(t.get("abab").unwrap(), None);
+    }
+
+    // ========================================================================
+    // NODE REUSE / STRUCTURE
+    // ========================================================================
+
+    #[test]
+    fn structure_shared_prefix_reuses_nodes() {
+        let mut t = alpha();
+        t.add("abc", "v1").unwrap();
+        let nodes_after_first = t.nodes.len();
+
+        t.add("abd", "v2").unwrap();
+        let nodes_after_second = t.nodes.len();
+
+        // "abd" should reuse nodes for "ab" and add only one for 'd'
+        assert_eq!(nodes_after_second, nodes_after_first + 1);
+    }
+
+    #[test]
+    fn structure_plus_creates_self_loop() {
+        let mut t = alpha();
+        t.add("a+", "loop").unwrap();
+
+        let a_pos = t.alphabet.find('a').unwrap();
+        let first_node = t.nodes[0].get_children(a_pos).unwrap();
+        let loop_target = t.nodes[*first_node].get_children(a_pos).unwrap();
+
+        // Node should point to itself
+        assert_eq!(first_node, loop_target);
+    }
+
+    // ========================================================================
+    // BASIC TOKENIZATION
+    // ========================================================================
+
+    #[test]
+    fn lexer_basic_expression() {
+        #[derive(Debug, Clone, PartialEq)]
+        enum Kind {
+            Number,
+            Add,
+            Sub,
+            Mul,
+            Div,
+        }
+
+        let mut t = Table::new("0123456789+-*/".to_string());
+        t.add("[0123456789]+", Kind::Number).unwrap();
+        t.add("+", Kind::Add).unwrap();
+        t.add("-", Kind::Sub).unwrap();
+        t.add("*", Kind::Mul).unwrap();
+        t.add("/", Kind::Div).unwrap();
+
+        let iter = t.lexer("1+2*3").unwrap();
+        let tokens: Vec<_> = iter.collect::<Result<_, _>>().unwrap();
+
+        assert_eq!(tokens.len(), 5);
+        assert_eq!(tokens[0], (&Kind::Number, "1"));
+        assert_eq!(tokens[1], (&Kind::Add, "+"));
+        assert_eq!(tokens[2], (&Kind::Number, "2"));
+        assert_eq!(tokens[3], (&Kind::Mul, "*"));
+        assert_eq!(tokens[4], (&Kind::Number, "3"));
+    }
+
+    #[test]
+    fn lexer_single_token() {
+        #[derive(Debug, Clone, PartialEq)]
+        enum Kind {
+            Word,
+        }
+
+        let mut t = Table::new("abcdefghijklmnopqrstuvwxyz".to_string());
+        t.add("[abcdefghijklmnopqrstuvwxyz]+", Kind::Word).unwrap();
+
+        let tokens: Vec<_> = t.lexer("hello").unwrap().collect::<Result<_, _>>().unwrap();
+
+        assert_eq!(tokens.len(), 1);
+        assert_eq!(tokens[0], (&Kind::Word, "hello"));
+    }
+
+    #[test]
+    fn lexer_empty_input() {
+        #[derive(Debug, Clone, PartialEq)]
+        enum Kind {
+            Number,
+        }
+
+        let mut t = Table::new("0123456789".to_string());
+        t.add("[0123456789]+", Kind::Number).unwrap();
+
+        let tokens: Vec<_> = t.lexer("").unwrap().collect::<Result<_, _>>().unwrap();
+
+        assert!(tokens.is_empty());
+    }
+
+    #[test]
+    fn lexer_manual_iteration() {
+        #[derive(Debug, Clone, PartialEq)]
+        enum Kind {
+            A,
+            B,
+        }
+
+        let mut t = Table::new("ab".to_string());
+        t.add("a", Kind::A).unwrap();
+        t.add("b", Kind::B).unwrap();
+
+        let mut iter = t.lexer("aba").unwrap();
+
+        let (kind, content) = iter.next().unwrap().unwrap();
+        assert_eq!(kind, &Kind::A);
+        assert_eq!(content, "a");
+
+        let (kind, content) = iter.next().unwrap().unwrap();
+        assert_eq!(kind, &Kind::B);
+        assert_eq!(content, "b");
+
+        let (kind, content) = iter.next().unwrap().unwrap();
+        assert_eq!(kind, &Kind::A);
+        assert_eq!(content, "a");
+
+        assert!(iter.next().is_none());
+    }
+
+    // ========================================================================
+    // LONGEST MATCH (MAXIMAL MUNCH)
+    // ========================================================================
+
+    #[test]
+    fn lexer_longest_match_numbers() {
+        #[derive(Debug, Clone, PartialEq)]
+        enum Kind {
+            Number,
+            Add,
+        }
+
+        let mut t = Table::new("0123456789+".to_string());
+        t.add("[0123456789]+", Kind::Number).unwrap();
+        t.add("+", Kind::Add).unwrap();
+
+        let tokens: Vec<_> = t
+            .lexer("123+456")
+            .unwrap()
+            .collect::<Result<_, _>>()
+            .unwrap();
+
+        assert_eq!(tokens.len(), 3);
+        assert_eq!(tokens[0], (&Kind::Number, "123"));
+        assert_eq!(tokens[1], (&Kind::Add, "+"));
+        assert_eq!(tokens[2], (&Kind::Number, "456"));
+    }
+
+    #[test]
+    fn lexer_longest_match_overlapping_literals() {
+        #[derive(Debug, Clone, PartialEq)]
+        enum Kind {
+            Eq,
+            EqEq,
+        }
+
+        let mut t = Table::new("=".to_string());
+        t.add("=", Kind::Eq).unwrap();
+        t.add("==", Kind::EqEq).unwrap();
+
+        // "==" should match as EqEq
+        let tokens: Vec<_> = t.lexer("==").unwrap().collect::<Result<_, _>>().unwrap();
+        assert_eq!(tokens.len(), 1);
+        assert_eq!(tokens[0], (&Kind::EqEq, "=="));
+
+        // "=" should match as Eq
+        let tokens: Vec<_> = t.lexer("=").unwrap().collect::<Result<_, _>>().unwrap();
+        assert_eq!(tokens.len(), 1);
+        assert_eq!(tokens[0], (&Kind::Eq, "="));
+
+        // "===" should be EqEq + Eq
+        let tokens: Vec<_> = t.lexer("===").unwrap().collect::<Result<_, _>>().unwrap();
+        assert_eq!(tokens.len(), 2);
+        assert_eq!(tokens[0], (&Kind::EqEq, "=="));
+        assert_eq!(tokens[1], (&Kind::Eq, "="));
+    }
+
+    #[test]
+    fn lexer_longest_match_backtrack() {
+        #[derive(Debug, Clone, PartialEq)]
+        enum Kind {
+            Ab,
+            Abc,
+            X,
+        }
+
+        let mut t = Table::new("abcx".to_string());
+        t.add("ab", Kind::Ab).unwrap();
+        t.add("abc", Kind::Abc).unwrap();
+        t.add("x", Kind::X).unwrap();
+
+        // "abx" - matches "ab" then "x" (not "abc" partial)
+        let tokens: Vec<_> = t.lexer("abx").unwrap().collect::<Result<_, _>>().unwrap();
+        assert_eq!(tokens.len(), 2);
+        assert_eq!(tokens[0], (&Kind::Ab, "ab"));
+        assert_eq!(tokens[1], (&Kind::X, "x"));
+    }
+
+    #[test]
+    fn lexer_longest_match_greedy() {
+        #[derive(Debug, Clone, PartialEq)]
+        enum Kind {
+            A,
+            Aa,
+            Aaa,
+        }
+
+        let mut t = Table::new("a".to_string());
+        t.add("a", Kind::A).unwrap();
+        t.add("aa", Kind::Aa).unwrap();
+        t.add("aaa", Kind::Aaa).unwrap();
+
+        let tokens: Vec<_> = t.lexer("aaa").unwrap().collect::<Result<_, _>>().unwrap();
+        assert_eq!(tokens.len(), 1);
+        assert_eq!(tokens[0], (&Kind::Aaa, "aaa"));
+
+        let tokens: Vec<_> = t.lexer("aaaa").unwrap().collect::<Result<_, _>>().unwrap();
+        assert_eq!(tokens.len(), 2);
+        assert_eq!(tokens[0], (&Kind::Aaa, "aaa"));
+        assert_eq!(tokens[1], (&Kind::A, "a"));
+
+        let tokens: Vec<_> = t.lexer("aaaaa").unwrap().collect::<Result<_, _>>().unwrap();
+        assert_eq!(tokens.len(), 2);
+        assert_eq!(tokens[0], (&Kind::Aaa, "aaa"));
+        assert_eq!(tokens[1], (&Kind::Aa, "aa"));
+    }
+
+    // ========================================================================
+    // ERROR HANDLING
+    // ========================================================================
+
+    #[test]
+    fn lexer_error_unknown_char_immediate() {
+        // Il lexer restituisce errore immediatamente quando incontra
+        // un carattere fuori dall'alfabeto, anche durante un match parziale
+        #[derive(Debug, Clone, PartialEq)]
+        enum Kind {
+            Number,
+        }
+
+        let mut t = Table::new("0123456789".to_string());
+        t.add("[0123456789]+", Kind::Number).unwrap();
+
+        let mut iter = t.lexer("12@34").unwrap();
+
+        // Errore immediato su '@', non restituisce "12" prima
+        let err = iter.next().unwrap().unwrap_err();
+        assert_eq!(
+            err,
+            LexerError::UnknownChar {
+                char: '@',
+                position: 2
+            }
+        );
+    }
+
+    #[test]
+    fn lexer_error_unknown_char_at_start() {
+        #[derive(Debug, Clone, PartialEq)]
+        enum Kind {
+            Number,
+        }
+
+        let mut t = Table::new("0123456789".to_string());
+        t.add("[0123456789]+", Kind::Number).unwrap();
+
+        let mut iter = t.lexer("@123").unwrap();
+
+        let err = iter.next().unwrap().unwrap_err();
+        assert_eq!(
+            err,
+            LexerError::UnknownChar {
+                char: '@',
+                position: 0
+            }
+        );
+    }
+
+    #[test]
+    fn lexer_error_unknown_char_after_valid_token() {
+        #[derive(Debug, Clone, PartialEq)]
+        enum Kind {
+            A,
+            B,
+        }
+
+        let mut t = Table::new("ab".to_string());
+        t.add("a", Kind::A).unwrap();
+        t.add("b", Kind::B).unwrap();
+
+        let mut iter = t.lexer("a@b").unwrap();
+
+        let err = iter.next().unwrap().unwrap_err();
+        assert_eq!(
+            err,
+            LexerError::UnknownChar {
+                char: '@',
+                position: 1
+            }
+        );
+    }
+
+    #[test]
+    fn lexer_error_unexpected_end_no_pattern() {
+        #[derive(Debug, Clone, PartialEq)]
+        enum Kind {
+            Abc,
+        }
+
+        let mut t = Table::new("abcdef".to_string());
+        t.add("abc", Kind::Abc).unwrap();
+
+        let mut iter = t.lexer("abcdef").unwrap();
+
+        // "abc" matches
+        let (kind, content) = iter.next().unwrap().unwrap();
+        assert_eq!(kind, &Kind::Abc);
+        assert_eq!(content, "abc");
+
+        // "def" is in alphabet but no pattern matches
+        let err = iter.next().unwrap().unwrap_err();
+        assert_eq!(err, LexerError::UnexpectedEnd { position: 3 });
+    }
+
+    #[test]
+    fn lexer_error_unexpected_end_at_start() {
+        #[derive(Debug, Clone, PartialEq)]
+        enum Kind {
+            Abc,
+        }
+
+        let mut t = Table::new("abcdef".to_string());
+        t.add("abc", Kind::Abc).unwrap();
+
+        let mut iter = t.lexer("def").unwrap();
+
+        // "def" starts with 'd' which has no transition from root
+        let err = iter.next().unwrap().unwrap_err();
+        assert_eq!(err, LexerError::UnexpectedEnd { position: 0 });
+    }
+
+    #[test]
+    fn lexer_error_invalid_string_non_ascii() {
+        #[derive(Debug, Clone, PartialEq)]
+        enum Kind {
+            Word,
+        }
+
+        let mut t = Table::new("abc".to_string());
+        t.add("[abc]+", Kind::Word).unwrap();
+
+        let result = t.lexer("héllo");
+        assert!(matches!(result, Err(LexerError::InvalidString(_))));
+    }
+
+    #[test]
+    fn lexer_error_invalid_string_emoji() {
+        #[derive(Debug, Clone, PartialEq)]
+        enum Kind {
+            Word,
+        }
+
+        let mut t = Table::new("abc".to_string());
+        t.add("[abc]+", Kind::Word).unwrap();
+
+        let result = t.lexer("abc😀");
+        assert!(matches!(result, Err(LexerError::InvalidString(_))));
+    }
+
+    // ========================================================================
+    // WHITESPACE HANDLING
+    // ========================================================================
+
+    #[test]
+    fn lexer_with_whitespace_in_alphabet() {
+        #[derive(Debug, Clone, PartialEq)]
+        enum Kind {
+            Number,
+            Space,
+        }
+
+        let mut t = Table::new("0123456789 ".to_string());
+        t.add("[0123456789]+", Kind::Number).unwrap();
+        t.add(" +", Kind::Space).unwrap();
+
+        let tokens: Vec<_> = t
+            .lexer("1 2  3")
+            .unwrap()
+            .collect::<Result<_, _>>()
+            .unwrap();
+
+        assert_eq!(tokens.len(), 5);
+        assert_eq!(tokens[0], (&Kind::Number, "1"));
+        assert_eq!(tokens[1], (&Kind::Space, " "));
+        assert_eq!(tokens[2], (&Kind::Number, "2"));
+        assert_eq!(tokens[3], (&Kind::Space, "  "));
+        assert_eq!(tokens[4], (&Kind::Number, "3"));
+    }
+
+    #[test]
+    fn lexer_whitespace_not_in_alphabet_immediate_error() {
+        // Errore immediato quando incontra spazio fuori alfabeto
+        #[derive(Debug, Clone, PartialEq)]
+        enum Kind {
+            Number,
+        }
+
+        let mut t = Table::new("0123456789".to_string());
+        t.add("[0123456789]+", Kind::Number).unwrap();
+
+        let mut iter = t.lexer("1 2").unwrap();
+
+        // Errore immediato, non restituisce "1" prima
+        let err = iter.next().unwrap().unwrap_err();
+        assert_eq!(
+            err,
+            LexerError::UnknownChar {
+                char: ' ',
+                position: 1
+            }
+        );
+    }
+
+    // ========================================================================
+    // COMPLEX PATTERNS
+    // ========================================================================
+
+    #[test]
+    fn lexer_identifier_and_numbers_separate_alphabets() {
+        // Usiamo alfabeti non sovrapposti per evitare ambiguità
+        #[derive(Debug, Clone, PartialEq)]
+        enum Kind {
+            Ident,
+            Number,
+            Eq,
+        }
+
+        let mut t = Table::new("abcxyz0123456789=".to_string());
+        t.add("[abcxyz]+", Kind::Ident).unwrap();
+        t.add("[0123456789]+", Kind::Number).unwrap();
+        t.add("=", Kind::Eq).unwrap();
+
+        let tokens: Vec<_> = t.lexer("x=42").unwrap().collect::<Result<_, _>>().unwrap();
+
+        assert_eq!(tokens.len(), 3);
+        assert_eq!(tokens[0], (&Kind::Ident, "x"));
+        assert_eq!(tokens[1], (&Kind::Eq, "="));
+        assert_eq!(tokens[2], (&Kind::Number, "42"));
+    }
+
+    #[test]
+    fn lexer_multiple_operators() {
+        #[derive(Debug, Clone, PartialEq)]
+        enum Kind {
+            Lt,
+            Le,
+            Gt,
+            Ge,
+            Eq,
+            EqEq,
+            Not,
+            Ne,
+        }
+
+        let mut t = Table::new("<=!>".to_string());
+        t.add("<", Kind::Lt).unwrap();
+        t.add("<=", Kind::Le).unwrap();
+        t.add(">", Kind::Gt).unwrap();
+        t.add(">=", Kind::Ge).unwrap();
+        t.add("=", Kind::Eq).unwrap();
+        t.add("==", Kind::EqEq).unwrap();
+        t.add("!", Kind::Not).unwrap();
+        t.add("!=", Kind::Ne).unwrap();
+
+        let tokens: Vec<_> = t
+            .lexer("<=>=!===!=")
+            .unwrap()
+            .collect::<Result<_, _>>()
+            .unwrap();
+
+        assert_eq!(tokens.len(), 5);
+        assert_eq!(tokens[0], (&Kind::Le, "<="));
+        assert_eq!(tokens[1], (&Kind::Ge, ">="));
+        assert_eq!(tokens[2], (&Kind::Ne, "!="));
+        assert_eq!(tokens[3], (&Kind::EqEq, "=="));
+        assert_eq!(tokens[4], (&Kind::Ne, "!="));
+    }
+
+    #[test]
+    fn lexer_hex_numbers() {
+        #[derive(Debug, Clone, PartialEq)]
+        enum Kind {
+            Hex,
+        }
+
+        let mut t = Table::new("0123456789abcdef".to_string());
+        t.add("[0123456789abcdef]+", Kind::Hex).unwrap();
+
+        let tokens: Vec<_> = t
+            .lexer("deadbeef")
+            .unwrap()
+            .collect::<Result<_, _>>()
+            .unwrap();
+
+        assert_eq!(tokens.len(), 1);
+        assert_eq!(tokens[0], (&Kind::Hex, "deadbeef"));
+    }
+
+    // ========================================================================
+    // EDGE CASES
+    // ========================================================================
+
+    #[test]
+    fn lexer_single_char_tokens() {
+        #[derive(Debug, Clone, PartialEq)]
+        enum Kind {
+            A,
+        }
+
+        let mut t = Table::new("a".to_string());
+        t.add("a", Kind::A).unwrap();
+
+        let tokens: Vec<_> = t.lexer("aaa").unwrap().collect::<Result<_, _>>().unwrap();
+
+        assert_eq!(tokens.len(), 3);
+        assert_eq!(tokens[0], (&Kind::A, "a"));
+        assert_eq!(tokens[1], (&Kind::A, "a"));
+        assert_eq!(tokens[2], (&Kind::A, "a"));
+    }
+
+    #[test]
+    fn lexer_very_long_token() {
+        #[derive(Debug, Clone, PartialEq)]
+        enum Kind {
+            A,
+        }
+
+        let mut t = Table::new("a".to_string());
+        t.add("a+", Kind::A).unwrap();
+
+        let long_input = "a".repeat(10000);
+        let tokens: Vec<_> = t
+            .lexer(&long_input)
+            .unwrap()
+            .collect::<Result<_, _>>()
+            .unwrap();
+
+        assert_eq!(tokens.len(), 1);
+        assert_eq!(tokens[0].0, &Kind::A);
+        assert_eq!(tokens[0].1.len(), 10000);
+    }
+
+    #[test]
+    fn lexer_alternating_tokens() {
+        #[derive(Debug, Clone, PartialEq)]
+        enum Kind {
+            A,
+            B,
+        }
+
+        let mut t = Table::new("ab".to_string());
+        t.add("a+", Kind::A).unwrap();
+        t.add("b+", Kind::B).unwrap();
+
+        let tokens: Vec<_> = t
+            .lexer("aaabbbaaab")
+            .unwrap()
+            .collect::<Result<_, _>>()
+            .unwrap();
+
+        assert_eq!(tokens.len(), 4);
+        assert_eq!(tokens[0], (&Kind::A, "aaa"));
+        assert_eq!(tokens[1], (&Kind::B, "bbb"));
+        assert_eq!(tokens[2], (&Kind::A, "aaa"));
+        assert_eq!(tokens[3], (&Kind::B, "b"));
+    }
+
+    #[test]
+    fn lexer_partial_match_at_end() {
+        #[derive(Debug, Clone, PartialEq)]
+        enum Kind {
+            Abc,
+        }
+
+        let mut t = Table::new("abc".to_string());
+        t.add("abc", Kind::Abc).unwrap();
+
+        // "abcab" - first "abc" matches, then "ab" is partial with no match
+        let mut iter = t.lexer("abcab").unwrap();
+
+        let (kind, content) = iter.next().unwrap().unwrap();
+        assert_eq!(kind, &Kind::Abc);
+        assert_eq!(content, "abc");
+
+        // "ab" has no complete match
+        let err = iter.next().unwrap().unwrap_err();
+        assert_eq!(err, LexerError::UnexpectedEnd { position: 3 });
+    }
+
+    #[test]
+    fn lexer_empty_table() {
+        #[derive(Debug, Clone, PartialEq)]
+        enum Kind {}
+
+        let t: Table<Kind> = Table::new("abc".to_string());
+
+        let mut iter = t.lexer("abc").unwrap();
+
+        // No patterns defined, should fail immediately
+        let err = iter.next().unwrap().unwrap_err();
+        assert_eq!(err, LexerError::UnexpectedEnd { position: 0 });
+    }
+
+    #[test]
+    fn lexer_collect_result_success() {
+        #[derive(Debug, Clone, PartialEq)]
+        enum Kind {
+            Num,
+            Op,
+        }
+
+        let mut t = Table::new("0123456789+".to_string());
+        t.add("[0123456789]+", Kind::Num).unwrap();
+        t.add("+", Kind::Op).unwrap();
+
+        let result: Result<Vec<_>, _> = t.lexer("1+2").unwrap().collect();
+        assert!(result.is_ok());
+        assert_eq!(result.unwrap().len(), 3);
+    }
+
+    #[test]
+    fn lexer_collect_result_failure() {
+        #[derive(Debug, Clone, PartialEq)]
+        enum Kind {
+            Num,
+            Op,
+        }
+
+        let mut t = Table::new("0123456789+".to_string());
+        t.add("[0123456789]+", Kind::Num).unwrap();
+        t.add("+", Kind::Op).unwrap();
+
+        let result: Result<Vec<_>, _> = t.lexer("1@2").unwrap().collect();
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn lexer_only_repetition() {
+        #[derive(Debug, Clone, PartialEq)]
+        enum Kind {
+            Stars,
+        }
+
+        let mut t = Table::new("*".to_string());
+        t.add("*+", Kind::Stars).unwrap();
+
+        let tokens: Vec<_> = t.lexer("****").unwrap().collect::<Result<_, _>>().unwrap();
+
+        assert_eq!(tokens.len(), 1);
+        assert_eq!(tokens[0], (&Kind::Stars, "****"));
+    }
+
+    #[test]
+    fn lexer_mixed_fixed_and_repetition() {
+        #[derive(Debug, Clone, PartialEq)]
+        enum Kind {
+            Prefix,
+            Suffix,
+        }
+
+        let mut t = Table::new("ab".to_string());
+        t.add("a+b", Kind::Prefix).unwrap(); // one or more 'a' followed by 'b'
+
+        let tokens: Vec<_> = t.lexer("aaab").unwrap().collect::<Result<_, _>>().unwrap();
+        assert_eq!(tokens.len(), 1);
+        assert_eq!(tokens[0], (&Kind::Prefix, "aaab"));
+
+        let tokens: Vec<_> = t.lexer("ab").unwrap().collect::<Result<_, _>>().unwrap();
+        assert_eq!(tokens.len(), 1);
+        assert_eq!(tokens[0], (&Kind::Prefix, "ab"));
+    }
+
+    // ========================================================================
+    // REAL-WORLD SCENARIOS
+    // ========================================================================
+
+    #[test]
+    fn lexer_arithmetic_expression() {
+        #[derive(Debug, Clone, PartialEq)]
+        enum Kind {
+            Num,
+            Add,
+            Sub,
+            Mul,
+            Div,
+            LParen,
+            RParen,
+        }
+
+        let mut t = Table::new("0123456789+-*/()".to_string());
+        t.add("[0123456789]+", Kind::Num).unwrap();
+        t.add("+", Kind::Add).unwrap();
+        t.add("-", Kind::Sub).unwrap();
+        t.add("*", Kind::Mul).unwrap();
+        t.add("/", Kind::Div).unwrap();
+        t.add("(", Kind::LParen).unwrap();
+        t.add(")", Kind::RParen).unwrap();
+
+        let tokens: Vec<_> = t
+            .lexer("(1+2)*3")
+            .unwrap()
+            .collect::<Result<_, _>>()
+            .unwrap();
+
+        assert_eq!(tokens.len(), 7);
+        assert_eq!(tokens[0], (&Kind::LParen, "("));
+        assert_eq!(tokens[1], (&Kind::Num, "1"));
+        assert_eq!(tokens[2], (&Kind::Add, "+"));
+        assert_eq!(tokens[3], (&Kind::Num, "2"));
+        assert_eq!(tokens[4], (&Kind::RParen, ")"));
+        assert_eq!(tokens[5], (&Kind::Mul, "*"));
+        assert_eq!(tokens[6], (&Kind::Num, "3"));
+    }
+
+    #[test]
+    fn lexer_complex_arithmetic() {
+        #[derive(Debug, Clone, PartialEq)]
+        enum Kind {
+            Num,
+            Add,
+            Sub,
+            Mul,
+            Div,
+            LParen,
+            RParen,
+        }
+
+        let mut t = Table::new("0123456789+-*/()".to_string());
+        t.add("[0123456789]+", Kind::Num).unwrap();
+        t.add("+", Kind::Add).unwrap();
+        t.add("-", Kind::Sub).unwrap();
+        t.add("*", Kind::Mul).unwrap();
+        t.add("/", Kind::Div).unwrap();
+        t.add("(", Kind::LParen).unwrap();
+        t.add(")", Kind::RParen).unwrap();
+
+        let tokens: Vec<_> = t
+            .lexer("((10+20)*(30-5))/2")
+            .unwrap()
+            .collect::<Result<_, _>>()
+            .unwrap();
+
+        assert_eq!(tokens.len(), 15);
+        assert_eq!(tokens[0], (&Kind::LParen, "("));
+        assert_eq!(tokens[1], (&Kind::LParen, "("));
+        assert_eq!(tokens[2], (&Kind::Num, "10"));
+        assert_eq!(tokens[3], (&Kind::Add, "+"));
+        assert_eq!(tokens[4], (&Kind::Num, "20"));
+        assert_eq!(tokens[5], (&Kind::RParen, ")"));
+        assert_eq!(tokens[6], (&Kind::Mul, "*"));
+        assert_eq!(tokens[7], (&Kind::LParen, "("));
+        assert_eq!(tokens[8], (&Kind::Num, "30"));
+        assert_eq!(tokens[9], (&Kind::Sub, "-"));
+        assert_eq!(tokens[10], (&Kind::Num, "5"));
+        assert_eq!(tokens[11], (&Kind::RParen, ")"));
+        assert_eq!(tokens[12], (&Kind::RParen, ")"));
+        assert_eq!(tokens[13], (&Kind::Div, "/"));
+        assert_eq!(tokens[14], (&Kind::Num, "2"));
+    }
+
+    #[test]
+    fn lexer_simple_tokens_no_overlap() {
+        // Semplice scenario senza ambiguità
+        #[derive(Debug, Clone, PartialEq)]
+        enum Kind {
+            LBrace,
+            RBrace,
+            Comma,
+            Colon,
+            Num,
+        }
+
+        let mut t = Table::new("{},:0123456789".to_string());
+        t.add("{", Kind::LBrace).unwrap();
+        t.add("}", Kind::RBrace).unwrap();
+        t.add(",", Kind::Comma).unwrap();
+        t.add(":", Kind::Colon).unwrap();
+        t.add("[0123456789]+", Kind::Num).unwrap();
+
+        let tokens: Vec<_> = t
+            .lexer("{1:2,3:4}")
+            .unwrap()
+            .collect::<Result<_, _>>()
+            .unwrap();
+
+        assert_eq!(tokens.len(), 9);
+        assert_eq!(tokens[0], (&Kind::LBrace, "{"));
+        assert_eq!(tokens[1], (&Kind::Num, "1"));
+        assert_eq!(tokens[2], (&Kind::Colon, ":"));
+        assert_eq!(tokens[3], (&Kind::Num, "2"));
+        assert_eq!(tokens[4], (&Kind::Comma, ","));
+        assert_eq!(tokens[5], (&Kind::Num, "3"));
+        assert_eq!(tokens[6], (&Kind::Colon, ":"));
+        assert_eq!(tokens[7], (&Kind::Num, "4"));
+        assert_eq!(tokens[8], (&Kind::RBrace, "}"));
+    }
+
+    #[test]
+    fn lexer_position_tracking() {
+        // Verifica che le posizioni negli errori siano corrette
+        #[derive(Debug, Clone, PartialEq)]
+        enum Kind {
+            A,
+        }
+
+        let mut t = Table::new("a".to_string());
+        t.add("a", Kind::A).unwrap();
+
+        // Errore a posizione 5
+        let mut iter = t.lexer("aaaaa@").unwrap();
+
+        for _ in 0..4 {
+            assert!(iter.next().unwrap().is_ok());
+        }
+
+        let err = iter.next().unwrap().unwrap_err();
+        assert_eq!(
+            err,
+            LexerError::UnknownChar {
+                char: '@',
+                position: 5
+            }
+        );
+    }
+
+    // ========================================================================
+    // ACCEPTED STRINGS
+    // ========================================================================
+
+    #[test]
+    fn accepted_of_length_classes() {
+        let mut t: Table<&str> = Table::new("ab".to_string());
+        t.add("[ab][ab]", "pair").unwrap();
+
+        assert_eq!(t.accepted_of_length(2), vec!["aa", "ab", "ba", "bb"]);
+        assert!(t.accepted_of_length(1).is_empty());
+        assert!(t.accepted_of_length(3).is_empty());
+    }
+
+    #[test]
+    fn accepted_of_length_plus() {
+        let mut t: Table<&str> = Table::new("ab".to_string());
+        t.add("a+b", "as_then_b").unwrap();
+
+        assert!(t.accepted_of_length(1).is_empty());
+        assert_eq!(t.accepted_of_length(2), vec!["ab"]);
+        assert_eq!(t.accepted_of_length(4), vec!["aaab"]);
+    }
+
+    #[test]
+    fn accepted_of_length_zero() {
+        let mut t = alpha();
+        assert!(t.accepted_of_length(0).is_empty());
+
+        t.add("", "empty").unwrap();
+        assert_eq!(t.accepted_of_length(0), vec![""]);
+    }
+
+    // ========================================================================
+    // OVERLAPS
+    // ========================================================================
+
+    #[test]
+    fn overlaps_prefix_pair() {
+        let mut t = alpha();
+        t.add("a", "short").unwrap();
+        t.add("ab", "long").unwrap();
+
+        assert_eq!(t.overlaps(), vec![("a".to_string(), "ab".to_string())]);
+    }
+
+    #[test]
+    fn overlaps_none_when_prefix_free() {
+        let mut t = alpha();
+        t.add("get", "GET").unwrap();
+        t.add("put", "PUT").unwrap();
+        t.add("post", "POST").unwrap();
+
+        assert!(t.overlaps().is_empty());
+    }
+
+    #[test]
+    fn overlaps_chain() {
+        let mut t = alpha();
+        t.add("a", "1").unwrap();
+        t.add("aa", "2").unwrap();
+        t.add("aaa", "3").unwrap();
+
+        let overlaps = t.overlaps();
+        assert_eq!(overlaps.len(), 3);
+        assert!(overlaps.contains(&("a".to_string(), "aa".to_string())));
+        assert!(overlaps.contains(&("a".to_string(), "aaa".to_string())));
+        assert!(overlaps.contains(&("aa".to_string(), "aaa".to_string())));
+    }
+
+    // ========================================================================
+    // SIZE HINT
+    // ========================================================================
+
+    #[test]
+    fn lexer_size_hint_remaining_bytes() {
+        let mut t = Table::new("0123456789+".to_string());
+        t.add("[0123456789]+", "num").unwrap();
+        t.add("+", "add").unwrap();
+
+        let mut iter = t.lexer("12+345").unwrap();
+        assert_eq!(iter.size_hint(), (0, Some(6)));
+
+        iter.next();
+        assert_eq!(iter.size_hint(), (0, Some(4)));
+
+        let tokens: Vec<_> = iter.collect::<Result<_, _>>().unwrap();
+        assert_eq!(tokens, vec![(&"add", "+"), (&"num", "345")]);
+    }
+
+    #[test]
+    fn lexer_size_hint_collect() {
+        let mut t = Table::new("a".to_string());
+        t.add("a", "a").unwrap();
+
+        let iter = t.lexer("aaaa").unwrap();
+        assert_eq!(iter.size_hint().1, Some(4));
+        let tokens: Vec<_> = iter.collect::<Result<_, _>>().unwrap();
+        assert_eq!(tokens.len(), 4);
+    }
+
+    #[test]
+    fn lexer_stops_after_error() {
+        let mut t = Table::new("0123456789".to_string());
+        t.add("[0123456789]+", "num").unwrap();
+
+        let mut iter = t.lexer("1@2").unwrap();
+        assert!(iter.next().unwrap().is_err());
+        assert_eq!(iter.size_hint(), (0, Some(0)));
+        assert!(iter.next().is_none());
+    }
+
+    // ========================================================================
+    // EXPORT
+    // ========================================================================
+
+    #[test]
+    fn to_dot_collapses_class_edges() {
+        let mut t = alpha();
+        // each class member gets its own node, the `+` loops back on all of them
+        t.add("[abc]+", "class").unwrap();
+
+        let dot = t.to_dot(true);
+        assert!(dot.contains("1 -> 1 [label=\"[a-c]\"];"));
+        assert_eq!(dot.matches("1 -> 1").count(), 1);
+
+        let dot = t.to_dot(false);
+        assert!(dot.contains("1 -> 1 [label=\"a\"];"));
+        assert_eq!(dot.matches("1 -> 1").count(), 3);
+    }
+
+    #[test]
+    fn to_dot_marks_terminals() {
+        let mut t = alpha();
+        t.add("ab", "v").unwrap();
+
+        let dot = t.to_dot(true);
+        assert!(dot.starts_with("digraph mtable {\n"));
+        assert!(dot.contains("0 [shape=circle];"));
+        assert!(dot.contains("2 [shape=doublecircle, label=\"2: \\\"v\\\"\"];"));
+    }
+
+    #[test]
+    fn debug_tree_collapses_class_edges() {
+        let mut t: Table<&str> = Table::new("abx".to_string());
+        t.add("x[ab]+", "pattern").unwrap();
+
+        assert_eq!(
+            t.debug_tree(true),
+            "0\n  x -> 1\n    a -> 2 = \"pattern\"\n      [ab] -> 2 = \"pattern\" (loop)\n    \
+             b -> 3 = \"pattern\"\n      [ab] -> 3 = \"pattern\" (loop)\n"
+        );
+        assert_eq!(t.debug_tree(false).matches("(loop)").count(), 4);
+    }
+
+    #[test]
+    fn class_label_ranges() {
+        assert_eq!(class_label(vec![b'a']), "a");
+        assert_eq!(class_label(vec![b'b', b'a']), "[ab]");
+        assert_eq!(class_label(vec![b'c', b'a', b'b', b'x']), "[a-cx]");
+    }
+
+    // ========================================================================
+    // TOKENIZABLE
+    // ========================================================================
+
+    fn arithmetic() -> Table<&'static str> {
+        let mut t = Table::new("0123456789+-*/()".to_string());
+        t.add("[0123456789]+", "num").unwrap();
+        t.add("+", "add").unwrap();
+        t.add("-", "sub").unwrap();
+        t.add("*", "mul").unwrap();
+        t.add("/", "div").unwrap();
+        t.add("(", "lparen").unwrap();
+        t.add(")", "rparen").unwrap();
+        t
+    }
+
+    #[test]
+    fn is_tokenizable_full_input() {
+        let t = arithmetic();
+        assert!(t.is_tokenizable("((10+20)*(30-5))/2"));
+        assert!(t.is_tokenizable(""));
+    }
+
+    #[test]
+    fn is_tokenizable_stray_char() {
+        let t = arithmetic();
+        assert!(!t.is_tokenizable("1+2@3"));
+        assert!(!t.is_tokenizable("1 + 2"));
+    }
+
+    #[test]
+    fn is_tokenizable_no_pattern() {
+        let mut t = Table::new("abcdef".to_string());
+        t.add("abc", "abc").unwrap();
+        assert!(t.is_tokenizable("abcabc"));
+        assert!(!t.is_tokenizable("abcdef"));
+    }
+
+    #[test]
+    fn is_tokenizable_non_ascii() {
+        let t = arithmetic();
+        assert!(!t.is_tokenizable("1+é"));
+    }
+
+    // ========================================================================
+    // DENSE MATRIX
+    // ========================================================================
+
+    #[test]
+    fn dense_matrix_walk() {
+        let mut t: Table<&str> = Table::new("abc".to_string());
+        t.add("ab", "ab").unwrap();
+        t.add("c", "c").unwrap();
+
+        let (matrix, width) = t.dense_matrix();
+        let values = t.terminal_values();
+        assert_eq!(width, 3);
+        assert_eq!(matrix.len(), values.len() * width);
+
+        let mut node = 0;
+        for ch in "ab".bytes() {
+            let pos = t.alphabet.find(ch as char).unwrap();
+            node = matrix[node * width + pos].unwrap();
+        }
+        assert_eq!(node, 2);
+        assert_eq!(values[node], Some(&"ab"));
+        assert_eq!(values[0], None);
+    }
+
+    #[test]
+    fn dense_matrix_self_loop() {
+        let mut t: Table<i32> = Table::new("a".to_string());
+        t.add("a+", 1).unwrap();
+
+        let (matrix, width) = t.dense_matrix();
+        assert_eq!(matrix, vec![Some(1), Some(1)]);
+        assert_eq!(width, 1);
+        assert_eq!(t.terminal_values(), vec![None, Some(&1)]);
+    }
+
+    // ========================================================================
+    // COMBINING VALUES
+    // ========================================================================
+
+    #[test]
+    fn combining_class_over_literal() {
+        let mut t: Table<i32> = Table::new("abc".to_string());
+        t.add("a", 10).unwrap();
+        t.add_combining("[abc]", 1, |existing, new| existing + new)
+            .unwrap();
+
+        assert_eq!(t.get("a").unwrap(), Some(&11));
+        assert_eq!(t.get("b").unwrap(), Some(&1));
+        assert_eq!(t.get("c").unwrap(), Some(&1));
+    }
+
+    #[test]
+    fn combining_literal_over_plus() {
+        let mut t: Table<i32> = Table::new("ab".to_string());
+        t.add("[ab]+", 5).unwrap();
+        t.add_combining("a", 2, |existing, new| existing + new)
+            .unwrap();
+
+        assert_eq!(t.get("a").unwrap(), Some(&7));
+        assert_eq!(t.get("b").unwrap(), Some(&5));
+    }
+
+    #[test]
+    fn combining_without_existing_value() {
+        let mut t: Table<i32> = Table::new("ab".to_string());
+        t.add_combining("ab", 3, |_, _| unreachable!()).unwrap();
+        assert_eq!(t.get("ab").unwrap(), Some(&3));
+    }
+
+    #[test]
+    fn combining_reports_pattern_errors() {
+        let mut t: Table<i32> = Table::new("ab".to_string());
+        let result = t.add_combining("[ab", 1, |e, n| e + n);
+        assert_eq!(result, Err(TableError::UnclosedClass));
+    }
+
+    // ========================================================================
+    // SEPARATORS
+    // ========================================================================
+
+    #[test]
+    fn separators_same_as_default_lexer() {
+        let mut t = Table::new("abcdefghijklmnopqrstuvwxyz0123456789 ,".to_string());
+        t.add("[abcdefghijklmnopqrstuvwxyz]+", "word").unwrap();
+        t.add("[0123456789]+", "num").unwrap();
+        t.add(" ", "space").unwrap();
+        t.add(",", "comma").unwrap();
+        let seps = HashSet::from([' ', ',']);
+
+        for input in ["hello world", "a,b,c", "abc 123,x", "", "word"] {
+            let expected: Result<Vec<_>, _> = t.lexer(input).unwrap().collect();
+            let actual: Result<Vec<_>, _> =
+                t.lexer_with_separators(input, &seps).unwrap().collect();
+            assert_eq!(actual, expected, "input {input:?}");
+        }
+    }
+
+    #[test]
+    fn separators_stop_longest_match() {
+        let mut t = Table::new("ab ".to_string());
+        t.add("a", "a").unwrap();
+        t.add("a b", "a_b").unwrap();
+        t.add(" ", "space").unwrap();
+        t.add("b", "b").unwrap();
+        let seps = HashSet::from([' ']);
+
+        let tokens: Vec<_> = t.lexer("a b").unwrap().collect::<Result<_, _>>().unwrap();
+        assert_eq!(tokens, vec![(&"a_b", "a b")]);
+
+        // the separator cannot extend the current token
+        let tokens: Vec<_> = t
+            .lexer_with_separators("a b", &seps)
+            .unwrap()
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert_eq!(tokens, vec![(&"a", "a"), (&"space", " "), (&"b", "b")]);
+    }
+
+    #[test]
+    fn separators_early_stop_long_input() {
+        let mut t = Table::new("a x".to_string());
+        t.add("a+", "run").unwrap();
+        // without separators every run follows this path before backtracking
+        t.add("a+ a+ a+ a+ a+ a+ a+ a+ x", "never").unwrap();
+        t.add(" ", "space").unwrap();
+        let seps = HashSet::from([' ']);
+
+        let input = "aaaa ".repeat(2000);
+        let count = t
+            .lexer_with_separators(&input, &seps)
+            .unwrap()
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap()
+            .len();
+        assert_eq!(count, 4000);
+    }
+
+    #[test]
+    fn separators_out_of_alphabet() {
+        let mut t = Table::new("0123456789".to_string());
+        t.add("[0123456789]+", "num").unwrap();
+        let seps = HashSet::from([' ']);
+
+        let mut iter = t.lexer_with_separators("12 34", &seps).unwrap();
+        assert_eq!(iter.next().unwrap(), Ok((&"num", "12")));
+        assert_eq!(
+            iter.next().unwrap(),
+            Err(LexerError::UnknownChar {
+                char: ' ',
+                position: 2
+            })
+        );
+    }
+
+    // ========================================================================
+    // DEFAULT
+    // ========================================================================
+
+    #[test]
+    fn default_empty_alphabet() {
+        let t = Table::<i32>::default();
+        assert!(t.alphabet.is_empty());
+        assert_eq!(t.get("").unwrap(), None);
+        assert!(matches!(t.get("a"), Err(TableError::InvalidInput('a'))));
+    }
+
+    #[test]
+    fn default_in_derived_struct() {
+        #[derive(Default)]
+        struct Wrapper {
+            table: Table<i32>,
+        }
+
+        let mut w = Wrapper::default();
+        w.table.add("", 1).unwrap();
+        assert_eq!(w.table.get("").unwrap(), Some(&1));
+    }
+
+    // ========================================================================
+    // HISTOGRAM
+    // ========================================================================
+
+    #[test]
+    fn histogram_counts_kinds() {
+        #[derive(Debug, Clone, PartialEq, Eq, Hash)]
+        enum Kind {
+            Number,
+            Plus,
+        }
+
+        let mut t = Table::new("0123456789+".to_string());
+        t.add("[0123456789]+", Kind::Number).unwrap();
+        t.add("+", Kind::Plus).unwrap();
+
+        let histogram = t.token_histogram("1+2+3").unwrap();
+        assert_eq!(histogram.len(), 2);
+        assert_eq!(histogram[&Kind::Number], 3);
+        assert_eq!(histogram[&Kind::Plus], 2);
+    }
+
+    #[test]
+    fn histogram_first_error() {
+        let mut t = Table::new("0123456789+".to_string());
+        t.add("[0123456789]+", "num").unwrap();
+
+        assert_eq!(
+            t.token_histogram("1+2"),
+            Err(LexerError::UnexpectedEnd { position: 1 })
+        );
+        assert!(matches!(
+            t.token_histogram("é"),
+            Err(LexerError::InvalidString(_))
+        ));
+    }
+
+    // ========================================================================
+    // STEP TOKEN
+    // ========================================================================
+
+    fn step_table() -> Table<&'static str> {
+        let mut t = Table::new("abcx=".to_string());
+        t.add("ab", "ab").unwrap();
+        t.add("abc", "abc").unwrap();
+        t.add("=", "eq").unwrap();
+        t
+    }
+
+    #[test]
+    fn step_token_complete() {
+        let t = step_table();
+        // `c` cannot extend "abc"
+        assert_eq!(
+            t.step_token("abcab"),
+            StepResult::Token {
+                value: &"abc",
+                len: 3
+            }
+        );
+        // `x` stops the walk after "ab"
+        assert_eq!(
+            t.step_token("abx"),
+            StepResult::Token {
+                value: &"ab",
+                len: 2
+            }
+        );
+        // no transition leaves the "=" node
+        assert_eq!(
+            t.step_token("="),
+            StepResult::Token {
+                value: &"eq",
+                len: 1
+            }
+        );
+    }
+
+    #[test]
+    fn step_token_need_more() {
+        let t = step_table();
+        assert_eq!(t.step_token("a"), StepResult::NeedMore);
+        // "ab" matches but "abc" could still follow
+        assert_eq!(t.step_token("ab"), StepResult::NeedMore);
+        assert_eq!(t.step_token(""), StepResult::NeedMore);
+    }
+
+    #[test]
+    fn step_token_no_match() {
+        let t = step_table();
+        assert_eq!(t.step_token("x"), StepResult::NoMatch { at: 0 });
+        assert_eq!(t.step_token("ax"), StepResult::NoMatch { at: 1 });
+
+        let empty: Table<&str> = Table::new("a".to_string());
+        assert_eq!(empty.step_token(""), StepResult::NoMatch { at: 0 });
+    }
+
+    #[test]
+    fn step_token_unknown() {
+        let t = step_table();
+        assert_eq!(t.step_token("a@"), StepResult::Unknown { ch: '@', at: 1 });
+        assert_eq!(t.step_token("é"), StepResult::Unknown { ch: 'é', at: 0 });
+    }
+
+    // ========================================================================
+    // NORMALIZED ALPHABET
+    // ========================================================================
+
+    #[test]
+    fn normalize_alphabet_canonical_form() {
+        let build = |alphabet: &str| {
+            let mut t: Table<i32> = Table::new(alphabet.to_string());
+            t.add("ab", 1).unwrap();
+            t.add("b+", 2).unwrap();
+            t.add("[ab]a", 3).unwrap();
+            t
+        };
+        let mut t1 = build("ba");
+        let mut t2 = build("ab");
+        assert_ne!(t1.dense_matrix(), t2.dense_matrix());
+
+        t1.normalize_alphabet();
+        t2.normalize_alphabet();
+
+        assert_eq!(t1.alphabet, "ab");
+        assert_eq!(t1.alphabet, t2.alphabet);
+        assert_eq!(t1.dense_matrix(), t2.dense_matrix());
+        assert_eq!(t1.terminal_values(), t2.terminal_values());
+
+        for t in [&t1, &t2] {
+            assert_eq!(t.get("ab").unwrap(), Some(&1));
+            assert_eq!(t.get("bbb").unwrap(), Some(&2));
+            assert_eq!(t.get("aa").unwrap(), Some(&3));
+            assert_eq!(t.get("ba").unwrap(), Some(&3));
+            assert_eq!(t.get("a").unwrap(), None);
+        }
+    }
+
+    #[test]
+    fn normalize_alphabet_removes_duplicates() {
+        let mut t: Table<i32> = Table::new("cabca".to_string());
+        t.add("abc", 1).unwrap();
+        t.normalize_alphabet();
+
+        assert_eq!(t.alphabet, "abc");
+        assert!(t.nodes.iter().all(|n| n.children.len() == 3));
+        assert_eq!(t.get("abc").unwrap(), Some(&1));
+        assert_eq!(t.get("cab").unwrap(), None);
+    }
+
+    // ========================================================================
+    // EXPLAIN
+    // ========================================================================
+
+    #[test]
+    fn explain_unknown_char() {
+        #[derive(Debug, Clone, PartialEq)]
+        enum Kind {
+            Number,
+            Plus,
+        }
+
+        let mut t = Table::new("0123456789+".to_string());
+        t.add("[0123456789]+", Kind::Number).unwrap();
+        t.add("+", Kind::Plus).unwrap();
+
+        let explanation = t.explain("1+@");
+        assert_eq!(
+            explanation,
+            "0..1 Number \"1\"\n\
+             error at 2: '@' is not in the alphabet, interrupting Plus \"+\" at 1..2\n"
+        );
+    }
+
+    #[test]
+    fn explain_no_pattern() {
+        let mut t = Table::new("abcdef".to_string());
+        t.add("abc", "abc").unwrap();
+
+        assert_eq!(
+            t.explain("abcdef"),
+            "0..3 \"abc\" \"abc\"\nerror at 3: no pattern matches \"def\"\n"
+        );
+        assert_eq!(t.explain(""), "");
+        assert_eq!(t.explain("é"), "Invalid string (non-ASCII): 'é'\n");
+    }
+
+    // ========================================================================
+    // PROGRAMMATIC PATTERNS
+    // ========================================================================
+
+    #[test]
+    fn add_pattern_matches_string_equivalent() {
+        let mut built = alpha();
+        built
+            .add_pattern(
+                &Pattern::literal("ab").class(&['x', 'y']).plus().build(),
+                "v",
+            )
+            .unwrap();
+        let mut parsed = alpha();
+        parsed.add("ab[xy]+", "v").unwrap();
+
+        for input in ["abx", "aby", "abxyyx", "ab", "abz", "xy"] {
+            assert_eq!(built.get(input), parsed.get(input), "input {input:?}");
+        }
+        assert_eq!(built.get("abyx").unwrap(), Some(&"v"));
+    }
+
+    #[test]
+    fn add_pattern_class_of_metacharacters() {
+        let mut t: Table<&str> = Table::new("[]+".to_string());
+        // no escaping needed for members computed at runtime
+        t.add_pattern(&Pattern::class(&['[', ']', '+']).plus().build(), "meta")
+            .unwrap();
+
+        assert_eq!(t.get("[+]").unwrap(), Some(&"meta"));
+        assert_eq!(t.get("+").unwrap(), Some(&"meta"));
+    }
+
+    #[test]
+    fn add_pattern_errors() {
+        let mut t = alpha();
+        assert_eq!(
+            t.add_pattern(&Pattern::literal("a1").build(), "digit"),
+            Err(TableError::InvalidInput('1'))
+        );
+        assert_eq!(
+            t.add_pattern(&Pattern::class(&[]).build(), "empty"),
+            Err(TableError::EmptyClass)
+        );
+        assert_eq!(
+            t.add_pattern(&Pattern::literal("é").build(), "accent"),
+            Err(TableError::InvalidInput('é'))
+        );
+    }
+
+    // ========================================================================
+    // ROOT SELF-LOOP
+    // ========================================================================
+
+    fn targets_root<T: Debug + Clone>(t: &Table<T>) -> bool {
+        t.nodes.iter().any(|node| node.children.contains(&Some(0)))
+    }
+
+    #[test]
+    fn root_never_self_loops_with_leading_plus() {
+        let mut t: Table<i32> = Table::new("+ab".to_string());
+        // a leading `+` is a literal, the second one repeats it
+        t.add("++", 1).unwrap();
+        t.add("+a+", 2).unwrap();
+        t.add("[+]+b", 3).unwrap();
+        assert!(matches!(
+            t.add("[+ab]+", 4),
+            Err(TableError::ValueAlreadyDefined { .. })
+        ));
+
+        assert!(!targets_root(&t));
+        assert_eq!(t.get("+").unwrap(), Some(&1));
+        assert_eq!(t.get("+++").unwrap(), Some(&1));
+        assert_eq!(t.get("+aaa").unwrap(), Some(&2));
+        assert_eq!(t.get("+++b").unwrap(), Some(&3));
+    }
+
+    #[test]
+    fn root_never_self_loops_with_builder() {
+        let mut t: Table<i32> = Table::new("+a".to_string());
+        t.add_pattern(&Pattern::builder().plus().literal("a").plus().build(), 1)
+            .unwrap();
+        t.add_pattern(&Pattern::class(&['+']).plus().build(), 2)
+            .unwrap();
+
+        assert!(!targets_root(&t));
+        assert_eq!(t.get("").unwrap(), None);
+    }
+
+    #[test]
+    fn root_never_self_loops_lexer_progresses() {
+        let mut t: Table<i32> = Table::new("+".to_string());
+        t.add("++", 1).unwrap();
+
+        let tokens: Vec<_> = t.lexer("+++").unwrap().collect::<Result<_, _>>().unwrap();
+        assert_eq!(tokens, vec![(&1, "+++")]);
+        assert!(!targets_root(&t));
+    }
+
+    // ========================================================================
+    // KEYS / VALUES
+    // ========================================================================
+
+    #[test]
+    fn keys_and_values() {
+        let mut t: Table<i32> = Table::new("abc".to_string());
+        t.add("ab", 2).unwrap();
+        t.add("c", 3).unwrap();
+        t.add("a", 1).unwrap();
+
+        assert_eq!(t.keys().collect::<Vec<_>>(), vec!["a", "c", "ab"]);
+        assert_eq!(t.values().collect::<Vec<_>>(), vec![&1, &3, &2]);
+        assert_eq!(
+            t.entries(),
+            vec![
+                ("a".to_string(), &1),
+                ("c".to_string(), &3),
+                ("ab".to_string(), &2)
+            ]
+        );
+    }
+
+    #[test]
+    fn keys_representative_for_classes_and_plus() {
+        let mut t: Table<i32> = Table::new("ab".to_string());
+        t.add("[ab]b+", 1).unwrap();
+
+        assert_eq!(t.keys().collect::<Vec<_>>(), vec!["ab", "bb"]);
+        assert_eq!(t.values().count(), 2);
+        assert_eq!(Table::<i32>::default().keys().count(), 0);
+    }
+
+    // ========================================================================
+    // INDEXED LEXER
+    // ========================================================================
+
+    #[test]
+    fn lexer_indexed_numbers_tokens() {
+        let mut t: Table<&str> = Table::new("0123456789+".to_string());
+        t.add("[0123456789]+", "num").unwrap();
+        t.add("+", "plus").unwrap();
+
+        let tokens: Vec<_> = t
+            .lexer_indexed("1+2")
+            .unwrap()
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert_eq!(
+            tokens,
+            vec![(0, &"num", "1"), (1, &"plus", "+"), (2, &"num", "2")]
+        );
+    }
+
+    #[test]
+    fn lexer_indexed_error_is_not_numbered() {
+        let mut t: Table<&str> = Table::new("0123456789+".to_string());
+        t.add("[0123456789]+", "num").unwrap();
+
+        let mut iter = t.lexer_indexed("12+").unwrap();
+        assert_eq!(iter.next(), Some(Ok((0, &"num", "12"))));
+        assert_eq!(
+            iter.next(),
+            Some(Err(LexerError::UnexpectedEnd { position: 2 }))
+        );
+        assert_eq!(iter.next(), None);
+        assert!(t.lexer_indexed("é").is_err());
+    }
+
+    // ========================================================================
+    // LOSSY LEXER
+    // ========================================================================
+
+    fn numbers() -> Table<&'static str> {
+        let mut t = Table::new("0123456789".to_string());
+        t.add("[0123456789]+", "num").unwrap();
+        t
+    }
+
+    #[test]
+    fn lossy_reports_each_unknown_char() {
+        let t = numbers();
+        let tokens: Vec<_> = t.lexer_lossy("12@@@34", false).unwrap().collect();
+        assert_eq!(
+            tokens,
+            vec![
+                Ok((&"num", "12")),
+                Err((
+                    LexerError::UnknownChar {
+                        char: '@',
+                        position: 2
+                    },
+                    "@"
+                )),
+                Err((
+                    LexerError::UnknownChar {
+                        char: '@',
+                        position: 3
+                    },
+                    "@"
+                )),
+                Err((
+                    LexerError::UnknownChar {
+                        char: '@',
+                        position: 4
+                    },
+                    "@"
+                )),
+                Ok((&"num", "34")),
+            ]
+        );
+    }
+
+    #[test]
+    fn lossy_coalesces_unknown_chars() {
+        let t = numbers();
+        let tokens: Vec<_> = t.lexer_lossy("12@@@34", true).unwrap().collect();
+        assert_eq!(
+            tokens,
+            vec![
+                Ok((&"num", "12")),
+                Err((
+                    LexerError::UnknownChar {
+                        char: '@',
+                        position: 2
+                    },
+                    "@@@"
+                )),
+                Ok((&"num", "34")),
+            ]
+        );
+
+        let tokens: Vec<_> = t.lexer_lossy("@#1", true).unwrap().collect();
+        assert_eq!(
+            tokens,
+            vec![
+                Err((
+                    LexerError::UnknownChar {
+                        char: '@',
+                        position: 0
+                    },
+                    "@#"
+                )),
+                Ok((&"num", "1")),
+            ]
+        );
+    }
+
+    #[test]
+    fn lossy_unexpected_end_at_end_of_input() {
+        let mut t: Table<&str> = Table::new("ab".to_string());
+        t.add("ab", "ab").unwrap();
+
+        let tokens: Vec<_> = t.lexer_lossy("abba", true).unwrap().collect();
+        assert_eq!(
+            tokens,
+            vec![
+                Ok((&"ab", "ab")),
+                Err((LexerError::UnexpectedEnd { position: 2 }, "ba")),
+            ]
+        );
+        assert!(t.lexer_lossy("é", false).is_err());
+    }
+
+    #[test]
+    fn lossy_skips_unmatched_region() {
+        let mut t: Table<&str> = Table::new("abcxyz".to_string());
+        t.add("abc", "abc").unwrap();
+
+        let tokens: Vec<_> = t.lexer_lossy("xyzabc", false).unwrap().collect();
+        assert_eq!(
+            tokens,
+            vec![
+                Err((LexerError::UnexpectedEnd { position: 0 }, "xyz")),
+                Ok((&"abc", "abc")),
+            ]
+        );
+
+        // a partial match is skipped too, then unknown chars are reported
+        let tokens: Vec<_> = t.lexer_lossy("abx@abc", true).unwrap().collect();
+        assert_eq!(
+            tokens,
+            vec![
+                Err((LexerError::UnexpectedEnd { position: 0 }, "abx")),
+                Err((
+                    LexerError::UnknownChar {
+                        char: '@',
+                        position: 3
+                    },
+                    "@"
+                )),
+                Ok((&"abc", "abc")),
+            ]
+        );
+    }
+
+    #[test]
+    fn lossy_max_errors_stops_after_budget() {
+        let t = numbers();
+        let mut iter = t.lexer_lossy_max_errors("1@@2@@@3@", 3).unwrap();
+        let items: Vec<_> = iter.by_ref().collect();
+        assert_eq!(items.iter().filter(|item| item.is_err()).count(), 3);
+        assert_eq!(
+            items,
+            vec![
+                Ok((&"num", "1")),
+                Err((
+                    LexerError::UnknownChar {
+                        char: '@',
+                        position: 1
+                    },
+                    "@"
+                )),
+                Err((
+                    LexerError::UnknownChar {
+                        char: '@',
+                        position: 2
+                    },
+                    "@"
+                )),
+                Ok((&"num", "2")),
+                Err((
+                    LexerError::UnknownChar {
+                        char: '@',
+                        position: 4
+                    },
+                    "@"
+                )),
+            ]
+        );
+        assert_eq!(iter.errors_emitted(), 3);
+        assert_eq!(iter.next(), None);
+    }
+
+    #[test]
+    fn lossy_max_errors_budget_not_reached() {
+        let t = numbers();
+        let mut iter = t.lexer_lossy_max_errors("1@2", 5).unwrap();
+        assert_eq!(iter.by_ref().count(), 3);
+        assert_eq!(iter.errors_emitted(), 1);
+
+        let mut iter = t.lexer_lossy_max_errors("@@", 0).unwrap();
+        assert_eq!(iter.next(), None);
+        assert_eq!(iter.errors_emitted(), 0);
+    }
+
+    // ========================================================================
+    // DISTINGUISHING SUFFIX
+    // ========================================================================
+
+    #[test]
+    fn distinguishing_suffix_literals() {
+        let t = alpha();
+        assert_eq!(
+            t.distinguishing_suffix("cat", "car"),
+            Some(("t".to_string(), "r".to_string()))
+        );
+        assert_eq!(
+            t.distinguishing_suffix("ca", "cat"),
+            Some(("".to_string(), "t".to_string()))
+        );
+        assert_eq!(t.distinguishing_suffix("cat", "cat"), None);
+    }
+
+    #[test]
+    fn distinguishing_suffix_keeps_atoms_whole() {
+        let t = alpha();
+        assert_eq!(
+            t.distinguishing_suffix("x[ab]c", "x[ab]d"),
+            Some(("c".to_string(), "d".to_string()))
+        );
+        assert_eq!(
+            t.distinguishing_suffix("x[ab]", "x[ac]"),
+            Some(("[ab]".to_string(), "[ac]".to_string()))
+        );
+        assert_eq!(
+            t.distinguishing_suffix("ab+", "abc"),
+            Some(("b+".to_string(), "bc".to_string()))
+        );
+    }
+
+    // ========================================================================
+    // FALLBACK
+    // ========================================================================
+
+    #[test]
+    fn fallback_single_char_tokens() {
+        let mut t: Table<&str> = Table::new("abc0123456789=".to_string());
+        t.add("[abc]+", "ident").unwrap();
+        t.add("==", "eqeq").unwrap();
+        t.add_fallback("other");
+
+        let tokens: Vec<_> = t.lexer("ab12c").unwrap().collect::<Result<_, _>>().unwrap();
+        assert_eq!(
+            tokens,
+            vec![
+                (&"ident", "ab"),
+                (&"other", "1"),
+                (&"other", "2"),
+                (&"ident", "c"),
+            ]
+        );
+    }
+
+    #[test]
+    fn fallback_only_at_token_start() {
+        let mut t: Table<&str> = Table::new("ab=".to_string());
+        t.add("==", "eqeq").unwrap();
+        t.add_fallback("other");
+
+        // "=a" starts a match for "==" which then fails: no fallback
+        let mut iter = t.lexer("=a").unwrap();
+        assert_eq!(
+            iter.next(),
+            Some(Err(LexerError::UnexpectedEnd { position: 0 }))
+        );
+
+        // unknown chars are still errors
+        let mut iter = t.lexer("a@").unwrap();
+        assert_eq!(iter.next(), Some(Ok((&"other", "a"))));
+        assert_eq!(
+            iter.next(),
+            Some(Err(LexerError::UnknownChar {
+                char: '@',
+                position: 1
+            }))
+        );
+        assert_eq!(t.get("a").unwrap(), None);
+    }
+
+    // ========================================================================
+    // RESERVED WORDS
+    // ========================================================================
+
+    #[test]
+    fn reserve_keyword_over_identifier() {
+        let mut t = alpha();
+        t.add("[abcdefghijklmnopqrstuvwxyz]+", "ident").unwrap();
+        t.reserve("for", "for").unwrap();
+
+        assert_eq!(t.get("for").unwrap(), Some(&"for"));
+        assert_eq!(t.get("form").unwrap(), Some(&"ident"));
+        assert_eq!(t.get("fo").unwrap(), Some(&"ident"));
+        assert_eq!(t.get("f").unwrap(), Some(&"ident"));
+        assert_eq!(t.get("xfor").unwrap(), Some(&"ident"));
+        assert_eq!(t.get("forfor").unwrap(), Some(&"ident"));
+
+        let mut t2: Table<&str> = Table::new("abcdefghijklmnopqrstuvwxyz ".to_string());
+        t2.add("[abcdefghijklmnopqrstuvwxyz]+", "ident").unwrap();
+        t2.add(" +", "ws").unwrap();
+        t2.reserve("for", "for").unwrap();
+        let tokens: Vec<_> = t2
+            .lexer("for form fo")
+            .unwrap()
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert_eq!(
+            tokens,
+            vec![
+                (&"for", "for"),
+                (&"ws", " "),
+                (&"ident", "form"),
+                (&"ws", " "),
+                (&"ident", "fo"),
+            ]
+        );
+    }
+
+    #[test]
+    fn reserve_several_keywords_sharing_prefix() {
+        let mut t = alpha();
+        t.add("[abcdefghijklmnopqrstuvwxyz]+", "ident").unwrap();
+        t.reserve("if", "if").unwrap();
+        t.reserve("in", "in").unwrap();
+        t.reserve("int", "int").unwrap();
+
+        assert_eq!(t.get("if").unwrap(), Some(&"if"));
+        assert_eq!(t.get("in").unwrap(), Some(&"in"));
+        assert_eq!(t.get("int").unwrap(), Some(&"int"));
+        assert_eq!(t.get("i").unwrap(), Some(&"ident"));
+        assert_eq!(t.get("inta").unwrap(), Some(&"ident"));
+        assert_eq!(t.get("ifs").unwrap(), Some(&"ident"));
+    }
+
+    #[test]
+    fn reserve_new_and_conflicting_words() {
+        let mut t = alpha();
+        t.add("do", "do").unwrap();
+        t.reserve("done", "done").unwrap();
+        assert_eq!(t.get("done").unwrap(), Some(&"done"));
+        assert_eq!(t.get("do").unwrap(), Some(&"do"));
+
+        // a value set on the word's own path is not overridden
+        assert_eq!(
+            t.reserve("do", "again"),
+            Err(TableError::ValueAlreadyDefined {
+                current: "do",
+                requested: "again"
+            })
+        );
+        assert_eq!(t.reserve("d1", "x"), Err(TableError::InvalidInput('1')));
+    }
+
+    // ========================================================================
+    // OWNED LEXER
+    // ========================================================================
+
+    fn owned_tokens(input: &str) -> OwnedLexer<&'static str> {
+        let mut t = Table::new("0123456789+".to_string());
+        t.add("[0123456789]+", "num").unwrap();
+        t.add("+", "plus").unwrap();
+        t.into_lexer(input.to_string()).unwrap()
+    }
+
+    #[test]
+    fn owned_lexer_outlives_table_and_input() {
+        let tokens: Vec<_> = owned_tokens("12+3").collect::<Result<_, _>>().unwrap();
+        assert_eq!(
+            tokens,
+            vec![
+                ("num", "12".to_string()),
+                ("plus", "+".to_string()),
+                ("num", "3".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn owned_lexer_errors() {
+        let mut iter = owned_tokens("1+-");
+        assert_eq!(iter.next(), Some(Ok(("num", "1".to_string()))));
+        assert_eq!(
+            iter.next(),
+            Some(Err(LexerError::UnknownChar {
+                char: '-',
+                position: 2
+            }))
+        );
+        assert_eq!(iter.next(), None);
+
+        let t: Table<i32> = digits();
+        assert!(matches!(
+            t.into_lexer("é".to_string()),
+            Err(LexerError::InvalidString(_))
+        ));
+    }
+
+    // ========================================================================
+    // OPTIONAL PATTERNS (value on the root)
+    // ========================================================================
+
+    #[test]
+    fn optional_pattern_get() {
+        let mut t = alpha();
+        t.add("", "opt").unwrap();
+        t.add("abc", "opt").unwrap();
+
+        assert_eq!(t.get("").unwrap(), Some(&"opt"));
+        assert_eq!(t.get("abc").unwrap(), Some(&"opt"));
+        assert_eq!(t.get("ab").unwrap(), None);
+    }
+
+    #[test]
+    fn optional_pattern_empty_alternative() {
+        let mut t = alpha();
+        t.add("abc|", "opt").unwrap();
+        assert_eq!(t.get("").unwrap(), Some(&"opt"));
+        assert_eq!(t.get("abc").unwrap(), Some(&"opt"));
+        assert_eq!(t.get("ab").unwrap(), None);
+
+        // the empty match is never a token
+        let tokens: Vec<_> = t
+            .lexer("abcabc")
+            .unwrap()
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert_eq!(tokens, vec![(&"opt", "abc"), (&"opt", "abc")]);
+
+        let mut t = alpha();
+        t.add("|abc", "opt").unwrap();
+        assert_eq!(t.get("").unwrap(), Some(&"opt"));
+    }
+
+    #[test]
+    fn optional_pattern_lexer_never_matches_empty() {
+        let mut t = alpha();
+        t.add("", "opt").unwrap();
+        t.add("abc", "opt").unwrap();
+
+        let tokens: Vec<_> = t
+            .lexer("abcabc")
+            .unwrap()
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert_eq!(tokens, vec![(&"opt", "abc"), (&"opt", "abc")]);
+
+        // no zero-length token at "x": the lexer fails and stops
+        let tokens: Vec<_> = t.lexer("abcx").unwrap().collect();
+        assert_eq!(
+            tokens,
+            vec![
+                Ok((&"opt", "abc")),
+                Err(LexerError::UnexpectedEnd { position: 3 })
+            ]
+        );
+        assert_eq!(t.lexer("").unwrap().next(), None);
+        assert_eq!(t.step_token("x"), StepResult::NoMatch { at: 0 });
+    }
+
+    // ========================================================================
+    // STRUCTURE COUNTS
+    // ========================================================================
+
+    #[test]
+    fn transition_count_and_terminal_indices() {
+        let mut t = alpha();
+        assert_eq!(t.transition_count(), 0);
+        assert!(t.terminal_indices().is_empty());
+
+        t.add("abc", "v1").unwrap();
+        t.add("abd", "v2").unwrap();
+        // a, b, then c and d
+        assert_eq!(t.transition_count(), 4);
+        assert_eq!(t.terminal_indices(), vec![3, 4]);
+
+        t.add("", "empty").unwrap();
+        assert_eq!(t.terminal_indices(), vec![0, 3, 4]);
+    }
+
+    #[test]
+    fn transition_count_includes_self_loops() {
+        let mut t = alpha();
+        t.add("[ab]+", "v").unwrap();
+        // root -> a, root -> b, and two self-loops on each node
+        assert_eq!(t.transition_count(), 6);
+        assert_eq!(t.terminal_indices(), vec![1, 2]);
+    }
+
+    // ========================================================================
+    // GRAMMAR EXPORT
+    // ========================================================================
+
+    fn from_grammar_lines(alphabet: &str, grammar: &str) -> Table<String> {
+        let mut t = Table::new(alphabet.to_string());
+        for line in grammar.lines() {
+            let (name, pattern) = line.split_once(" = ").unwrap();
+            t.add(pattern, name.to_string()).unwrap();
+        }
+        t
+    }
+
+    #[test]
+    fn to_grammar_literals_and_repetition() {
+        let mut t: Table<&str> = Table::new("ab+".to_string());
+        t.add("ab", "AB").unwrap();
+        t.add("a+", "AS").unwrap();
+        t.add("+", "PLUS").unwrap();
+
+        assert_eq!(
+            t.to_grammar(|v| v.to_string()),
+            "AS = a+\nAB = a+b\nPLUS = [+]\n"
+        );
+    }
+
+    #[test]
+    fn to_grammar_round_trip() {
+        let alphabet = "0123456789+-[abc";
+        let grammar = "NUM = [0123456789]+\nPLUS = +\nINC = +[+]\nOPEN = [[]\nID = a[abc]+\nNEG = -[0123456789]\n";
+        let t = from_grammar_lines(alphabet, grammar);
+
+        let exported = t.to_grammar(|v| v.clone());
+        assert_eq!(
+            exported,
+            "NUM = [0123456789]+\nPLUS = [+]\nINC = [+][+]\nNEG = -[0123456789]\nOPEN = [[]\nID = a[abc]+\n"
+        );
+        let reloaded = from_grammar_lines(alphabet, &exported);
+
+        for input in ["12+3", "a++[", "abca-1", "-9+[ab", "cab", "1-", "[[++"] {
+            let expected: Result<Vec<_>, _> = t.lexer(input).unwrap().collect();
+            let actual: Result<Vec<_>, _> = reloaded.lexer(input).unwrap().collect();
+            assert_eq!(actual, expected, "input {input:?}");
+        }
+        assert_eq!(reloaded.to_grammar(|v| v.clone()), exported);
+    }
+
+    #[test]
+    fn to_grammar_round_trip_special_members() {
+        let cases = [
+            // `\A` over an alphabet with `]` or with `[` before `:`
+            ("a]", "ANY = \\A\n", "ANY = []a]\n"),
+            ("[:", "ANY = \\A\n", "ANY = [:[]\n"),
+            ("]-^", "ANY = \\A\n", "ANY = []-^]\n"),
+            // a literal `\A` and a literal `(?<`
+            ("\\A", "KEY = [\\]A\n", "KEY = [\\]A\n"),
+            ("(?<a", "P = [(][?]<a\n", "P = [(][?]<a\n"),
+        ];
+        for (alphabet, grammar, expected) in cases {
+            let t = from_grammar_lines(alphabet, grammar);
+            let exported = t.to_grammar(|v| v.clone());
+            assert_eq!(exported, expected, "alphabet {alphabet:?}");
+            let reloaded = from_grammar_lines(alphabet, &exported);
+            assert_eq!(reloaded.debug_tree(false), t.debug_tree(false));
+            assert_eq!(reloaded.to_grammar(|v| v.clone()), exported);
+        }
+    }
+
+    #[test]
+    fn to_grammar_empty_and_root_value() {
+        let mut t = alpha();
+        assert_eq!(t.to_grammar(|v| v.to_string()), "");
+        t.add("", "EMPTY").unwrap();
+        assert_eq!(t.to_grammar(|v| v.to_string()), "EMPTY = \n");
+    }
+
+    // ========================================================================
+    // ASSERT MATCHES
+    // ========================================================================
+
+    #[test]
+    fn assert_matches_all_pass() {
+        let mut t = alpha();
+        t.add("ab+", "abs").unwrap();
+        assert_eq!(
+            t.assert_matches(&[("ab", Some(&"abs")), ("abbb", Some(&"abs")), ("a", None)]),
+            Ok(())
+        );
+    }
+
+    #[test]
+    fn assert_matches_lists_every_failure() {
+        let mut t = alpha();
+        t.add("ab+", "abs").unwrap();
+        let result = t.assert_matches(&[
+            ("ab", Some(&"abs")),
+            ("a", Some(&"abs")),
+            ("abb", None),
+            ("a1", None),
+        ]);
+        assert_eq!(
+            result,
+            Err("\"a\": expected Some(\"abs\"), got None\n\
+                 \"abb\": expected None, got Some(\"abs\")\n\
+                 \"a1\": expected None, got error: Invalid input character: '1'"
+                .to_string())
+        );
+    }
+
+    // ========================================================================
+    // METACHARACTER CONFLICTS
+    // ========================================================================
+
+    #[test]
+    fn metachar_conflicts_reported() {
+        let t: Table<i32> = Table::new("ab+[*?".to_string());
+        assert_eq!(t.metachar_conflicts(), vec!['+', '[', '*', '?']);
+
+        let t: Table<i32> = Table::new("]x".to_string());
+        assert_eq!(t.metachar_conflicts(), vec![']']);
+        assert!(alpha().metachar_conflicts().is_empty());
+
+        // `\A` makes the backslash an operator
+        let t: Table<i32> = Table::new("A\\".to_string());
+        assert_eq!(t.metachar_conflicts(), vec!['\\']);
+        let t: Table<i32> = Table::new_with_literals("A\\".to_string(), &['\\']);
+        assert!(t.metachar_conflicts().is_empty());
+    }
+
+    // ========================================================================
+    // LITERAL CHARACTERS
+    // ========================================================================
+
+    #[test]
+    fn literal_plus_is_not_repetition() {
+        let mut t: Table<&str> = Table::new_with_literals("ab+".to_string(), &['+']);
+        t.add("a+", "a plus").unwrap();
+
+        assert_eq!(t.get("a+").unwrap(), Some(&"a plus"));
+        assert_eq!(t.get("a").unwrap(), None);
+        assert_eq!(t.get("aa").unwrap(), None);
+        assert!(t.metachar_conflicts().is_empty());
+    }
+
+    #[test]
+    fn literal_bracket_and_class() {
+        let mut t: Table<&str> = Table::new_with_literals("ab[]".to_string(), &['[', ']']);
+        t.add("[a]", "brackets").unwrap();
+        t.add("b+", "bs").unwrap();
+
+        assert_eq!(t.get("[a]").unwrap(), Some(&"brackets"));
+        assert_eq!(t.get("a").unwrap(), None);
+        assert_eq!(t.get("bbb").unwrap(), Some(&"bs"));
+        assert_eq!(
+            t.distinguishing_suffix("[a]", "[b]"),
+            Some(("a]".to_string(), "b]".to_string()))
+        );
+    }
+
+    // ========================================================================
+    // LITERAL LOOKUP
+    // ========================================================================
+
+    #[test]
+    fn literal_only_flag() {
+        let mut t = alpha();
+        assert!(t.has_only_literals);
+        t.add("for", "for").unwrap();
+        t.add("[a]", "a").unwrap();
+        assert!(t.has_only_literals);
+        t.add("[xy]", "xy").unwrap();
+        assert!(!t.has_only_literals);
+
+        let mut t = alpha();
+        t.add("a+", "as").unwrap();
+        assert!(!t.has_only_literals);
+
+        let mut t = alpha();
+        let snapshot = t.snapshot();
+        t.add("(ab)+", "abs").unwrap();
+        assert!(!t.has_only_literals);
+        t.restore(snapshot);
+        assert!(t.has_only_literals);
+    }
+
+    #[test]
+    fn literal_lookup_results() {
+        let mut t = alpha();
+        t.add("for", "for").unwrap();
+        t.add("fort", "fort").unwrap();
+        t.add("", "empty").unwrap();
+
+        assert_eq!(t.get("for").unwrap(), Some(&"for"));
+        assert_eq!(t.get("fort").unwrap(), Some(&"fort"));
+        assert_eq!(t.get("fo").unwrap(), None);
+        assert_eq!(t.get("forts").unwrap(), None);
+        assert_eq!(t.get("").unwrap(), Some(&"empty"));
+        assert_eq!(t.get("fo1"), Err(TableError::InvalidInput('1')));
+        assert_eq!(t.get("x1").unwrap(), None);
+        assert!(matches!(t.get("fé"), Err(TableError::InvalidString(_))));
+        assert!(matches!(t.get("xé"), Err(TableError::InvalidString(_))));
+        // the same answers without the literal walk
+        let mut mixed = alpha();
+        mixed.add("for", "for").unwrap();
+        mixed.add("fort", "fort").unwrap();
+        mixed.add("", "empty").unwrap();
+        mixed.add("z[xy]", "zxy").unwrap();
+        for key in ["for", "fort", "fo", "forts", "", "fo1", "x1", "fé", "xé"] {
+            assert_eq!(mixed.get(key), t.get(key));
+        }
+
+        t.normalize_alphabet();
+        assert_eq!(t.get("fort").unwrap(), Some(&"fort"));
+    }
+
+    // ========================================================================
+    // COUNT WITH PREFIX
+    // ========================================================================
+
+    #[test]
+    fn count_with_prefix_words() {
+        let mut t = alpha();
+        t.add("cat", "cat").unwrap();
+        t.add("car", "car").unwrap();
+        t.add("card", "card").unwrap();
+
+        assert_eq!(t.count_with_prefix("car").unwrap(), 2);
+        assert_eq!(t.count_with_prefix("ca").unwrap(), 3);
+        assert_eq!(t.count_with_prefix("").unwrap(), 3);
+        assert_eq!(t.count_with_prefix("card").unwrap(), 1);
+        assert_eq!(t.count_with_prefix("dog").unwrap(), 0);
+        assert_eq!(
+            t.count_with_prefix("c4"),
+            Err(TableError::InvalidInput('4'))
+        );
+    }
+
+    #[test]
+    fn count_with_prefix_self_loops() {
+        let mut t = alpha();
+        t.add("a+", "as").unwrap();
+        t.add("a+b", "asb").unwrap();
+
+        assert_eq!(t.count_with_prefix("a").unwrap(), 2);
+        assert_eq!(t.count_with_prefix("aaa").unwrap(), 2);
+        assert_eq!(t.count_with_prefix("ab").unwrap(), 1);
+    }
+
+    // ========================================================================
+    // CAPTURES
+    // ========================================================================
+
+    #[test]
+    fn lexer_capturing_sign() {
+        let mut t: Table<&str> = Table::new("+-0123456789 ".to_string());
+        t.add("(?<sign>[+-])[0123456789]+", "signed").unwrap();
+        t.add("[0123456789]+", "unsigned").unwrap();
+        t.add(" ", "space").unwrap();
+
+        let tokens: Vec<_> = t
+            .lexer_capturing("-42 7 +1")
+            .unwrap()
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert_eq!(
+            tokens[0],
+            CapturedToken {
+                value: &"signed",
+                text: "-42",
+                captures: vec![("sign", '-')],
+            }
+        );
+        assert_eq!(tokens[2].text, "7");
+        assert!(tokens[2].captures.is_empty());
+        assert_eq!(tokens[4].captures, vec![("sign", '+')]);
+    }
+
+    #[test]
+    fn capture_not_reported_for_other_pattern() {
+        let mut t: Table<&str> = Table::new("+-0123456789".to_string());
+        t.add("(?<sign>[+-])[0-9]+", "signed").unwrap();
+        t.add("+", "plus").unwrap();
+
+        let tokens: Vec<_> = t
+            .lexer_capturing("+")
+            .unwrap()
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert_eq!(tokens[0].value, &"plus");
+        assert!(tokens[0].captures.is_empty());
+        let tokens: Vec<_> = t
+            .lexer_capturing("+1")
+            .unwrap()
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert_eq!(tokens[0].captures, vec![("sign", '+')]);
+    }
+
+    #[test]
+    fn capture_names_on_shared_node() {
+        let mut t: Table<&str> = Table::new("xyz".to_string());
+        t.add("(?<first>x)y", "xy").unwrap();
+        t.add("(?<second>x)z", "xz").unwrap();
+
+        let tokens: Vec<_> = t
+            .lexer_capturing("xyxz")
+            .unwrap()
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert_eq!(tokens[0].captures, vec![("first", 'x')]);
+        assert_eq!(tokens[1].captures, vec![("second", 'x')]);
+    }
+
+    #[test]
+    fn capture_does_not_change_matching() {
+        let mut t: Table<&str> = Table::new("ab".to_string());
+        t.add("a(?<x>[ab])+", "v").unwrap();
+
+        assert_eq!(t.get("ab").unwrap(), Some(&"v"));
+        assert_eq!(t.get("abab").unwrap(), Some(&"v"));
+        let token = t.lexer_capturing("aba").unwrap().next().unwrap().unwrap();
+        assert_eq!(token.captures, vec![("x", 'b'), ("x", 'a')]);
+        assert_eq!(t.add("(?<x>a", "w"), Err(TableError::UnclosedGroup));
+    }
+
+    #[test]
+    fn lexer_capturing_with_fallback() {
+        let mut t: Table<&str> = Table::new("ab1".to_string());
+        t.add("(?<x>a)b", "ab").unwrap();
+        t.add_fallback("other");
+
+        let tokens: Vec<_> = t
+            .lexer_capturing("ab1")
+            .unwrap()
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert_eq!(tokens.len(), 2);
+        assert_eq!(tokens[0].captures, vec![("x", 'a')]);
+        assert_eq!((tokens[1].value, tokens[1].text), (&"other", "1"));
+        assert!(tokens[1].captures.is_empty());
+    }
+
+    // ========================================================================
+    // DISJOINT TABLES
+    // ========================================================================
+
+    #[test]
+    fn is_disjoint_keywords() {
+        let mut http = alpha();
+        http.add("get", "GET").unwrap();
+        http.add("post", "POST").unwrap();
+        let mut sql = alpha();
+        sql.add("select", "SELECT").unwrap();
+        sql.add("ge", "GE").unwrap();
+
+        assert_eq!(http.is_disjoint(&sql), Ok(true));
+
+        sql.add("get", "GET").unwrap();
+        assert_eq!(http.is_disjoint(&sql), Ok(false));
+        assert_eq!(sql.is_disjoint(&http), Ok(false));
+    }
+
+    #[test]
+    fn is_disjoint_with_repetitions() {
+        let mut idents = alpha();
+        idents.add("[abc]+", "ident").unwrap();
+        let mut words = alpha();
+        words.add("xyz", "xyz").unwrap();
+        assert_eq!(idents.is_disjoint(&words), Ok(true));
+
+        words.add("cabbac", "cabbac").unwrap();
+        assert_eq!(idents.is_disjoint(&words), Ok(false));
+    }
+
+    #[test]
+    fn is_disjoint_alphabets() {
+        let mut t = Table::new("ba".to_string());
+        t.add("ab", "ab").unwrap();
+        let mut u = Table::new("ab".to_string());
+        u.add("ab", "ab").unwrap();
+        assert_eq!(t.is_disjoint(&u), Ok(false));
+
+        assert_eq!(
+            t.is_disjoint(&Table::new("abc".to_string())),
+            Err(TableError::AlphabetMismatch)
+        );
+    }
+
+    // ========================================================================
+    // MAPPED LEXER
+    // ========================================================================
+
+    #[test]
+    fn lexer_map_projects_kinds() {
+        #[derive(Debug, Clone, PartialEq)]
+        enum Kind {
+            Number,
+            Plus,
+        }
+
+        let mut t = Table::new("0123456789+".to_string());
+        t.add("[0123456789]+", Kind::Number).unwrap();
+        t.add("+", Kind::Plus).unwrap();
+
+        let tags: Vec<(u8, &str)> = t
+            .lexer_map("12+3", |kind| match kind {
+                Kind::Number => 1,
+                Kind::Plus => 2,
+            })
+            .unwrap()
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert_eq!(tags, vec![(1, "12"), (2, "+"), (1, "3")]);
+
+        let mut iter = t.lexer_map("1-", |_| 0).unwrap();
+        assert_eq!(
+            iter.next(),
+            Some(Err(LexerError::UnknownChar {
+                char: '-',
+                position: 1
+            }))
+        );
+    }
+
+    // ========================================================================
+    // OPERATOR SOUPS (backtracking to the last valid token)
+    // ========================================================================
+
+    fn comparison_ops() -> Table<&'static str> {
+        let mut t = Table::new("<=>!".to_string());
+        t.add("<", "Lt").unwrap();
+        t.add("<=", "Le").unwrap();
+        t.add("<<", "Shl").unwrap();
+        t.add("<<<=", "ShlShlEq").unwrap();
+        t.add("=", "Eq").unwrap();
+        t.add("==", "EqEq").unwrap();
+        t.add("!==", "StrictNe").unwrap();
+        t.add(">", "Gt").unwrap();
+        t
+    }
+
+    fn kinds(t: &Table<&'static str>, input: &str) -> Vec<&'static str> {
+        t.lexer(input)
+            .unwrap()
+            .map(|token| *token.unwrap().0)
+            .collect()
+    }
+
+    #[test]
+    fn soup_longest_valid_token_wins() {
+        let t = comparison_ops();
+        assert_eq!(kinds(&t, "<<="), vec!["Shl", "Eq"]);
+        assert_eq!(kinds(&t, "<=<"), vec!["Le", "Lt"]);
+        assert_eq!(kinds(&t, "<<<="), vec!["ShlShlEq"]);
+        assert_eq!(kinds(&t, "===<"), vec!["EqEq", "Eq", "Lt"]);
+    }
+
+    #[test]
+    fn soup_backtracks_over_failed_partial_paths() {
+        let t = comparison_ops();
+        // "<<<" is only a prefix of "<<<=": back to "<<" and restart
+        assert_eq!(kinds(&t, "<<<>"), vec!["Shl", "Lt", "Gt"]);
+        assert_eq!(kinds(&t, "<<<<="), vec!["Shl", "Shl", "Eq"]);
+        assert_eq!(kinds(&t, "<<<"), vec!["Shl", "Lt"]);
+    }
+
+    #[test]
+    fn soup_partial_path_without_valid_prefix_fails() {
+        let t = comparison_ops();
+        // "!=" is only a prefix of "!==" and "!" is not a token
+        let tokens: Vec<_> = t.lexer("!=<").unwrap().collect();
+        assert_eq!(tokens, vec![Err(LexerError::UnexpectedEnd { position: 0 })]);
+        let tokens: Vec<_> = t.lexer("<!=").unwrap().collect();
+        assert_eq!(
+            tokens,
+            vec![
+                Ok((&"Lt", "<")),
+                Err(LexerError::UnexpectedEnd { position: 1 })
+            ]
+        );
+    }
+
+    // ========================================================================
+    // LOOKUP
+    // ========================================================================
+
+    #[test]
+    fn rebuild_lookup_after_extending_alphabet() {
+        let mut t: Table<&str> = Table::new("ab".to_string());
+        t.add("ab", "ab").unwrap();
+
+        // extend the alphabet by hand, as an alphabet mutation would
+        t.alphabet.push('z');
+        for node in &mut t.nodes {
+            node.children.push(None);
+        }
+        assert_eq!(t.lookup[b'z' as usize], None);
+
+        t.rebuild_lookup();
+        assert_eq!(t.lookup[b'z' as usize], Some(2));
+        t.add("az", "az").unwrap();
+        assert_eq!(t.get("az").unwrap(), Some(&"az"));
+        assert_eq!(t.get("ab").unwrap(), Some(&"ab"));
+        let token = t.lexer_capturing("az").unwrap().next().unwrap().unwrap();
+        assert_eq!(token.text, "az");
+    }
+
+    // ========================================================================
+    // END OF INPUT TOKENS
+    // ========================================================================
+
+    #[test]
+    fn eof_only_token_at_tail() {
+        let mut t: Table<&str> = Table::new("foEND".to_string());
+        t.add("[fo]+", "word").unwrap();
+        t.add("[EN]", "letter").unwrap();
+        t.add_eof_only("END", "end").unwrap();
+
+        let tokens: Vec<_> = t
+            .lexer("fooEND")
+            .unwrap()
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert_eq!(tokens, vec![(&"word", "foo"), (&"end", "END")]);
+        assert_eq!(t.get("END").unwrap(), Some(&"end"));
+    }
+
+    #[test]
+    fn eof_only_token_embedded_does_not_match() {
+        let mut t: Table<&str> = Table::new("foEND".to_string());
+        t.add("[fo]+", "word").unwrap();
+        t.add("[EN]", "letter").unwrap();
+        t.add_eof_only("END", "end").unwrap();
+
+        // "END" followed by more input falls back to the shorter "E"
+        let tokens: Vec<_> = t.lexer("ENDfo").unwrap().collect();
+        assert_eq!(
+            tokens,
+            vec![
+                Ok((&"letter", "E")),
+                Ok((&"letter", "N")),
+                Err(LexerError::UnexpectedEnd { position: 2 }),
+            ]
+        );
+    }
+
+    #[test]
+    fn eof_only_embedded_before_unknown_char() {
+        let mut t: Table<&str> = Table::new("ENDf".to_string());
+        t.add_eof_only("END", "end").unwrap();
+        t.add("f", "f").unwrap();
+
+        // the input goes on after the unknown char: "END" is not at its end
+        let events: Vec<_> = t.lexer_events("END@f").unwrap().collect();
+        assert_eq!(
+            events,
+            vec![
+                LexerEvent::Error {
+                    error: LexerError::UnexpectedEnd { position: 0 },
+                    span: 0..3
+                },
+                LexerEvent::Error {
+                    error: LexerError::UnknownChar {
+                        char: '@',
+                        position: 3
+                    },
+                    span: 3..4
+                },
+                LexerEvent::Token {
+                    kind: &"f",
+                    text: "f",
+                    span: 4..5
+                },
+            ]
+        );
+        assert_eq!(
+            t.explain("END@f"),
+            "error at 3: '@' is not in the alphabet\n"
+        );
+    }
+
+    #[test]
+    fn eof_only_step_token() {
+        let mut t: Table<&str> = Table::new("foEND".to_string());
+        t.add("[EN]", "letter").unwrap();
+        t.add_eof_only("END", "end").unwrap();
+
+        // more input after "END" falls back to "E"
+        assert_eq!(
+            t.step_token("ENDfo"),
+            StepResult::Token {
+                value: &"letter",
+                len: 1
+            }
+        );
+        // the input may not end here
+        assert_eq!(t.step_token("END"), StepResult::NeedMore);
+    }
+
+    // ========================================================================
+    // POSIX CLASSES
+    // ========================================================================
+
+    #[test]
+    fn posix_digit_runs() {
+        let mut t: Table<&str> = Table::new("abc0123456789 ".to_string());
+        t.add("[[:digit:]]+", "num").unwrap();
+        t.add("[[:alpha:]]+", "word").unwrap();
+        t.add("[[:space:]]", "space").unwrap();
+
+        let tokens: Vec<_> = t
+            .lexer("ab 12c 7")
+            .unwrap()
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert_eq!(
+            tokens,
+            vec![
+                (&"word", "ab"),
+                (&"space", " "),
+                (&"num", "12"),
+                (&"word", "c"),
+                (&"space", " "),
+                (&"num", "7"),
+            ]
+        );
+        assert_eq!(t.get("1a").unwrap(), None);
+    }
+
+    #[test]
+    fn posix_class_intersects_alphabet() {
+        let mut t: Table<&str> = Table::new("ab12_".to_string());
+        t.add("[[:alnum:]_]+", "ident").unwrap();
+        assert_eq!(t.get("a_1b2").unwrap(), Some(&"ident"));
+
+        // `_` is the only punctuation in the alphabet
+        assert_eq!(
+            t.add("[[:punct:]]", "p"),
+            Err(TableError::ValueAlreadyDefined {
+                current: "ident",
+                requested: "p"
+            })
+        );
+        assert_eq!(t.add("[[:space:]]", "s"), Err(TableError::EmptyClass));
+        assert_eq!(
+            t.add("[[:upper:]]", "u"),
+            Err(TableError::UnknownPosixClass("upper".to_string()))
+        );
+    }
+
+    // ========================================================================
+    // GET ENTRY
+    // ========================================================================
+
+    #[test]
+    fn get_entry_returns_key_and_value() {
+        let mut t = alpha();
+        t.add("hello", "greeting").unwrap();
+
+        assert_eq!(t.get_entry("hello").unwrap(), Some(("hello", &"greeting")));
+        assert_eq!(t.get_entry("hell").unwrap(), None);
+        assert_eq!(t.get_entry("hell0"), Err(TableError::InvalidInput('0')));
+    }
+
+    // ========================================================================
+    // MATCHER
+    // ========================================================================
+
+    #[test]
+    fn matcher_accepts_patterns() {
+        let m = Table::matcher(&["get", "put", "[0123456789]+"]).unwrap();
+
+        assert!(m.is_match("get"));
+        assert!(m.is_match("put"));
+        assert!(m.is_match("2024"));
+        assert!(!m.is_match("ge"));
+        assert!(!m.is_match("post"));
+        assert!(!m.is_match(""));
+        assert!(matches!(
+            Table::matcher(&["[ab"]),
+            Err(TableError::UnclosedClass)
+        ));
+    }
+
+    #[test]
+    fn matcher_accepts_overlapping_patterns() {
+        let m = Table::matcher(&["a", "a"]).unwrap();
+        assert!(m.is_match("a"));
+
+        let m = Table::matcher(&["[ab]", "a", "b+"]).unwrap();
+        assert!(m.is_match("a"));
+        assert!(m.is_match("bbb"));
+        assert!(!m.is_match("ab"));
+    }
+
+    #[test]
+    fn try_from_alphabet() {
+        let mut t = Table::try_from("ab").unwrap();
+        assert!(!t.is_match("a"));
+        t.add("a+", ()).unwrap();
+        assert!(t.is_match("aaa"));
+        assert!(!t.is_match("b"));
+
+        assert!(matches!(
+            Table::try_from("àb"),
+            Err(TableError::InvalidString(_))
+        ));
+    }
+
+    // ========================================================================
+    // COMMON PREFIX
+    // ========================================================================
+
+    #[test]
+    fn common_prefix_len_of_keys() {
+        let mut t = alpha();
+        assert_eq!(t.common_prefix_len(), 0);
+        t.add("test", "test").unwrap();
+        t.add("testing", "testing").unwrap();
+        t.add("tester", "tester").unwrap();
+        assert_eq!(t.common_prefix_len(), 4);
+
+        t.add("xyz", "xyz").unwrap();
+        assert_eq!(t.common_prefix_len(), 0);
+    }
+
+    #[test]
+    fn common_prefix_len_stops_at_class_and_loop() {
+        let mut t = alpha();
+        t.add("ab[cd]e", "ab[cd]e").unwrap();
+        assert_eq!(t.common_prefix_len(), 2);
+
+        let mut t = alpha();
+        t.add("xa+b", "xa+b").unwrap();
+        assert_eq!(t.common_prefix_len(), 2);
+    }
+
+    // ========================================================================
+    // BOUNDED INPUT
+    // ========================================================================
+
+    #[test]
+    fn lexer_bounded_rejects_large_input() {
+        let mut t = digits();
+        t.add("[0123456789]+", 1).unwrap();
+
+        assert!(matches!(
+            t.lexer_bounded("123456", 5),
+            Err(LexerError::InputTooLarge { len: 6, max: 5 })
+        ));
+        let tokens: Vec<_> = t
+            .lexer_bounded("12345", 5)
+            .unwrap()
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert_eq!(tokens, vec![(&1, "12345")]);
+    }
+
+    // ========================================================================
+    // INTERNED LEXER
+    // ========================================================================
+
+    #[test]
+    fn lexer_interned_shares_text() {
+        let mut t: Table<&str> = Table::new("for ".to_string());
+        t.add("for", "for").unwrap();
+        t.add(" ", "space").unwrap();
+
+        let tokens: Vec<_> = t
+            .lexer_interned("for for for")
+            .unwrap()
+            .collect::<Result<_, _>>()
+            .unwrap();
+        let fors: Vec<_> = tokens.iter().filter(|(kind, _)| *kind == "for").collect();
+        assert_eq!(fors.len(), 3);
+        assert_eq!(&*fors[0].1, "for");
+        assert!(Arc::ptr_eq(&fors[0].1, &fors[1].1));
+        assert!(Arc::ptr_eq(&fors[0].1, &fors[2].1));
+        assert!(!Arc::ptr_eq(&fors[0].1, &tokens[1].1));
+    }
+
+    // ========================================================================
+    // REACHABILITY
+    // ========================================================================
+
+    #[test]
+    fn reachable_node_count_all_reachable() {
+        let mut t = alpha();
+        assert_eq!(t.reachable_node_count(), 1);
+        t.add("abc", "abc").unwrap();
+        t.add("a+", "as").unwrap();
+        assert_eq!(t.node_count(), 4);
+        assert_eq!(t.reachable_node_count(), t.node_count());
+    }
+
+    #[test]
+    fn reachable_node_count_detects_orphans() {
+        let mut t = alpha();
+        t.add("abc", "abc").unwrap();
+        t.add("xy", "xy").unwrap();
+
+        // drop the edge to "x", as removing "xy" would
+        let x = t.alphabet.find('x').unwrap();
+        t.nodes[0].children[x] = None;
+        assert_eq!(t.node_count(), 6);
+        assert_eq!(t.reachable_node_count(), 4);
+    }
+
+    // ========================================================================
+    // FLEXIBLE SPACES
+    // ========================================================================
+
+    #[test]
+    fn add_flexible_spacing_variants() {
+        let mut t: Table<&str> = Table::new("goto ".to_string());
+        t.add_flexible("go to", "goto").unwrap();
+
+        for input in ["go to", "goto", "go  to", "go   to"] {
+            assert_eq!(t.get(input).unwrap(), Some(&"goto"), "input {input:?}");
+        }
+        assert_eq!(t.get("go t").unwrap(), None);
+        assert_eq!(t.get(" goto").unwrap(), None);
+    }
+
+    #[test]
+    fn add_flexible_multiple_runs_and_lexer() {
+        let mut t: Table<&str> = Table::new("abc ".to_string());
+        t.add_flexible("a  b c", "abc").unwrap();
+        t.add(" ", "space").unwrap();
+
+        assert_eq!(t.get("abc").unwrap(), Some(&"abc"));
+        assert_eq!(t.get("a b  c").unwrap(), Some(&"abc"));
+        let tokens: Vec<_> = t
+            .lexer("a bc abc")
+            .unwrap()
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert_eq!(
+            tokens,
+            vec![(&"abc", "a bc"), (&"space", " "), (&"abc", "abc")]
+        );
+    }
+
+    #[test]
+    fn add_flexible_requires_space_in_alphabet() {
+        let mut t = alpha();
+        assert_eq!(
+            t.add_flexible("go to", "goto"),
+            Err(TableError::InvalidInput(' '))
+        );
+        // without spaces it is a plain `add`
+        t.add_flexible("goto", "goto").unwrap();
+        assert_eq!(t.get("goto").unwrap(), Some(&"goto"));
+    }
+
+    // ========================================================================
+    // SHORTEST KEY
+    // ========================================================================
+
+    #[test]
+    fn shortest_key_tie_follows_alphabet_order() {
+        let mut t: Table<i32> = Table::new("abcxy".to_string());
+        t.add("abc", 1).unwrap();
+        t.add("xy", 2).unwrap();
+        t.add("ab", 3).unwrap();
+        assert_eq!(t.shortest_key(), Some("ab".to_string()));
+
+        let mut t: Table<i32> = Table::new("xyabc".to_string());
+        t.add("abc", 1).unwrap();
+        t.add("xy", 2).unwrap();
+        t.add("ab", 3).unwrap();
+        assert_eq!(t.shortest_key(), Some("xy".to_string()));
+    }
+
+    #[test]
+    fn shortest_key_edge_cases() {
+        assert_eq!(Table::<i32>::default().shortest_key(), None);
+
+        let mut t: Table<i32> = Table::new("ab".to_string());
+        t.add("[ab]b+", 1).unwrap();
+        assert_eq!(t.shortest_key(), Some("ab".to_string()));
+        t.add("", 2).unwrap();
+        assert_eq!(t.shortest_key(), Some(String::new()));
+    }
+
+    // ========================================================================
+    // READER LEXER
+    // ========================================================================
+
+    fn expression_table() -> Table<&'static str> {
+        let mut t = Table::new("0123456789+*".to_string());
+        t.add("[0123456789]+", "num").unwrap();
+        t.add("+", "plus").unwrap();
+        t.add("*", "mul").unwrap();
+        t
+    }
+
+    // Hands out at most `chunk` bytes per read.
+    struct Trickle<'a> {
+        data: &'a [u8],
+        chunk: usize,
+    }
+
+    impl Read for Trickle<'_> {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            let n = self.chunk.min(buf.len()).min(self.data.len());
+            buf[..n].copy_from_slice(&self.data[..n]);
+            self.data = &self.data[n..];
+            Ok(n)
+        }
+    }
+
+    #[test]
+    fn lexer_reader_large_expression() {
+        let t = expression_table();
+        let input = (0..3000)
+            .map(|i| format!("{}", i * 7919))
+            .collect::<Vec<_>>()
+            .join("+");
+        let expected: Vec<_> = t
+            .lexer(&input)
+            .unwrap()
+            .map(|token| token.map(|(value, text)| (*value, text.to_string())))
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert!(input.len() > 2 * READ_CHUNK);
+
+        let actual: Vec<_> = t
+            .lexer_reader(std::io::Cursor::new(input.as_bytes()))
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn lexer_reader_tokens_across_reads() {
+        let t = expression_table();
+        let reader = Trickle {
+            data: b"12345*678+9",
+            chunk: 2,
+        };
+        let tokens: Vec<_> = t.lexer_reader(reader).collect::<Result<_, _>>().unwrap();
+        assert_eq!(
+            tokens,
+            vec![
+                ("num", "12345".to_string()),
+                ("mul", "*".to_string()),
+                ("num", "678".to_string()),
+                ("plus", "+".to_string()),
+                ("num", "9".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn lexer_reader_errors_use_stream_positions() {
+        let t = expression_table();
+        let reader = Trickle {
+            data: "12+3é".as_bytes(),
+            chunk: 1,
+        };
+        let tokens: Vec<_> = t.lexer_reader(reader).collect();
+        // the unknown byte interrupts the pending `3`, as in `lexer`
+        assert_eq!(tokens.len(), 3);
+        assert_eq!(
+            tokens[2],
+            Err(LexerError::UnknownChar {
+                char: 0xc3 as char,
+                position: 4
+            })
+        );
+
+        let mut iter = t.lexer_reader(std::io::Cursor::new(&b"1+"[..]));
+        assert_eq!(iter.next(), Some(Ok(("num", "1".to_string()))));
+        assert_eq!(iter.next(), Some(Ok(("plus", "+".to_string()))));
+        assert_eq!(iter.next(), None);
+        assert_eq!(t.lexer_reader(std::io::empty()).next(), None);
+    }
+
+    // ========================================================================
+    // VERIFY AGAINST
+    // ========================================================================
+
+    fn words_and_numbers() -> Table<&'static str> {
+        let mut t = alphanum();
+        t.add("[abcdefghijklmnopqrstuvwxyz]+", "w").unwrap();
+        t.add("[0123456789]+", "n").unwrap();
+        t
+    }
+
+    #[test]
+    fn verify_against_expected_tokens() {
+        let t = words_and_numbers();
+        assert_eq!(
+            t.verify_against("ab12c", &[("w", "ab"), ("n", "12"), ("w", "c")]),
+            Ok(())
+        );
+        assert_eq!(t.verify_against("", &[]), Ok(()));
+    }
+
+    #[test]
+    fn verify_against_reports_first_divergence() {
+        let t = words_and_numbers();
+        assert_eq!(
+            t.verify_against("ab12c", &[("w", "ab"), ("n", "1"), ("w", "c")]),
+            Err("token 1: expected (\"n\", \"1\"), got (\"n\", \"12\")".to_string())
+        );
+        assert_eq!(
+            t.verify_against("ab12", &[("w", "ab"), ("n", "12"), ("w", "c")]),
+            Err("expected 3 tokens, got 2".to_string())
+        );
+        assert_eq!(
+            t.verify_against("ab12c", &[("w", "ab")]),
+            Err("expected 1 tokens, got 3".to_string())
+        );
+        assert_eq!(
+            t.verify_against("ab-", &[("w", "ab"), ("w", "c")]),
+            Err(
+                "token 0: expected (\"w\", \"ab\"), got error: Unknown char '-' at position 2"
+                    .to_string()
+            )
+        );
+    }
+
+    // ========================================================================
+    // GENERATED PATTERNS
+    // ========================================================================
+
+    #[test]
+    fn add_generated_skips_exact_duplicates() {
+        let mut t = alpha();
+        let count = t
+            .add_generated(|| {
+                ["select", "from", "where", "select"]
+                    .into_iter()
+                    .map(|kw| (kw.to_string(), "keyword"))
+            })
+            .unwrap();
+        assert_eq!(count, 3);
+        assert_eq!(t.get("select").unwrap(), Some(&"keyword"));
+        assert_eq!(t.get("where").unwrap(), Some(&"keyword"));
+    }
+
+    #[test]
+    fn add_generated_reports_conflicts() {
+        let mut t = alpha();
+        let result = t.add_generated(|| vec![("on".to_string(), "a"), ("on".to_string(), "b")]);
+        assert_eq!(
+            result,
+            Err(TableError::ValueAlreadyDefined {
+                current: "a",
+                requested: "b"
+            })
+        );
+        assert_eq!(t.add_generated(Vec::new), Ok(0));
+    }
+
+    // ========================================================================
+    // IDEMPOTENT ADDS
+    // ========================================================================
+
+    #[test]
+    fn idempotent_same_value_succeeds() {
+        let mut t = Table::new_idempotent("abcdefghijklmnopqrstuvwxyz".to_string());
+        t.add("hello", "greeting").unwrap();
+        t.add("hello", "greeting").unwrap();
+        t.add("[ab]+", "ab").unwrap();
+        t.add("a+", "ab").unwrap();
+        t.reserve("hello", "greeting").unwrap();
+        assert_eq!(t.get("hello").unwrap(), Some(&"greeting"));
+        assert_eq!(t.get("aaa").unwrap(), Some(&"ab"));
+    }
+
+    #[test]
+    fn idempotent_changed_value_errors() {
+        let mut t = Table::new_idempotent("abcdefghijklmnopqrstuvwxyz".to_string());
+        t.add("hello", "first").unwrap();
+        assert_eq!(
+            t.add("hello", "second"),
+            Err(TableError::ValueAlreadyDefined {
+                current: "first",
+                requested: "second"
+            })
+        );
+        // the default table keeps rejecting every duplicate
+        let mut t = alpha();
+        t.add("hello", "first").unwrap();
+        assert!(t.add("hello", "first").is_err());
+    }
+
+    // ========================================================================
+    // ANY OF
+    // ========================================================================
 
-        let mul = t.get("*").unwrap().unwrap();
-        assert_eq!(mul.kind, "mul");
-        assert_eq!(mul.priority, 2);
+    #[test]
+    fn add_any_of_matches_each_option() {
+        let mut t = alpha();
+        t.add_any_of(&["get", "post", "put"], "method").unwrap();
+        assert_eq!(t.get("get").unwrap(), Some(&"method"));
+        assert_eq!(t.get("post").unwrap(), Some(&"method"));
+        assert_eq!(t.get("put").unwrap(), Some(&"method"));
+        assert_eq!(t.get("pu").unwrap(), None);
+        assert_eq!(t.get("patch").unwrap(), None);
+        // "post" and "put" share the `p` node
+        assert_eq!(t.node_count(), 10);
     }
 
     #[test]
-    fn generic_with_digits() {
-        let mut t = digits();
-        t.add("[0123456789]+", 42).unwrap();
+    fn add_any_of_options_are_exact() {
+        let mut t: Table<i32> = Table::new("a+[]".to_string());
+        t.add_any_of(&["a+", "[a]"], 1).unwrap();
+        assert_eq!(t.get("a+").unwrap(), Some(&1));
+        assert_eq!(t.get("aa").unwrap(), None);
+        assert_eq!(t.get("[a]").unwrap(), Some(&1));
 
-        assert_eq!(t.get("0").unwrap(), Some(&42));
-        assert_eq!(t.get("123").unwrap(), Some(&42));
-        assert_eq!(t.get("9876543210").unwrap(), Some(&42));
+        assert_eq!(
+            t.add_any_of(&["a", "ab"], 2),
+            Err(TableError::InvalidInput('b'))
+        );
     }
 
     // ========================================================================
-    // COMPLEX PATTERNS
+    // SNAPSHOT
     // ========================================================================
 
     #[test]
-    fn complex_identifier_like() {
-        let mut t = alphanum();
-        // First char must be letter, rest can be letter or digit
-        t.add(
-            "[abcdefghijklmnopqrstuvwxyz][abcdefghijklmnopqrstuvwxyz0123456789]+",
-            "id",
-        )
-        .unwrap();
+    fn restore_undoes_a_failed_batch() {
+        let mut t = alpha();
+        t.add("ab", "ab").unwrap();
+        t.add("cd+", "cds").unwrap();
+        let before = (t.debug_tree(false), t.node_count(), t.transition_count());
 
-        assert_eq!(t.get("ab").unwrap(), Some(&"id"));
-        assert_eq!(t.get("a1").unwrap(), Some(&"id"));
-        assert_eq!(t.get("var123").unwrap(), Some(&"id"));
-        assert_eq!(t.get("x9y8z7").unwrap(), Some(&"id"));
+        let snapshot = t.snapshot();
+        t.add("a+", "as").unwrap();
+        t.add("abc", "abc").unwrap();
+        t.add_fallback("other");
+        assert!(t.add("ab", "other").is_err());
+        t.restore(snapshot);
 
-        // Single letter doesn't match (needs at least 2 chars due to +)
+        assert_eq!(
+            (t.debug_tree(false), t.node_count(), t.transition_count()),
+            before
+        );
         assert_eq!(t.get("a").unwrap(), None);
-        // Starts with digit - no match
-        assert_eq!(t.get("1abc").unwrap(), None);
+        assert_eq!(t.get("aa").unwrap(), None);
+        assert_eq!(t.get("cddd").unwrap(), Some(&"cds"));
+        assert!(t.lexer("x").unwrap().next().unwrap().is_err());
+        // the restored table keeps working
+        t.add("a", "a").unwrap();
+        assert_eq!(t.get("a").unwrap(), Some(&"a"));
     }
 
     #[test]
-    fn complex_multiple_non_overlapping() {
+    fn restore_undoes_replaced_values_and_loops() {
         let mut t = alpha();
-        t.add("get", "GET").unwrap();
-        t.add("put", "PUT").unwrap();
-        t.add("post", "POST").unwrap();
-        t.add("delete", "DELETE").unwrap();
-
-        assert_eq!(t.get("get").unwrap(), Some(&"GET"));
-        assert_eq!(t.get("put").unwrap(), Some(&"PUT"));
-        assert_eq!(t.get("post").unwrap(), Some(&"POST"));
-        assert_eq!(t.get("delete").unwrap(), Some(&"DELETE"));
-        assert_eq!(t.get("patch").unwrap(), None);
+        t.add("ab", "ab").unwrap();
+        t.add_fallback("other");
+        let before = t.debug_tree(false);
+
+        let snapshot = t.snapshot();
+        t.add_combining("ab", "x", |_, new| new).unwrap();
+        t.add_skip("ab+").unwrap();
+        t.add_fallback("replaced");
+        t.restore(snapshot);
+
+        assert_eq!(t.debug_tree(false), before);
+        assert_eq!(t.get("ab").unwrap(), Some(&"ab"));
+        assert_eq!(t.get("abb").unwrap(), None);
+        assert_eq!(
+            t.lexer("z").unwrap().next().unwrap().unwrap(),
+            (&"other", "z")
+        );
     }
 
     #[test]
-    fn complex_class_then_plus_then_literal() {
+    fn restore_after_prune_and_nested_snapshots() {
         let mut t = alpha();
-        t.add("[abc]+x", "pattern").unwrap();
+        t.add("ab", "ab").unwrap();
+        assert!(t.add("cd", "ab").is_ok());
+        let outer = t.snapshot();
+        t.retain_keys(|key| key != "cd");
+        t.prune_empty();
+        let inner = t.snapshot();
+        t.add("a", "a").unwrap();
+        t.restore(inner);
+        assert_eq!(t.get("a").unwrap(), None);
+        t.add("b", "b").unwrap();
+        t.restore(outer);
 
-        assert_eq!(t.get("ax").unwrap(), Some(&"pattern"));
-        assert_eq!(t.get("bx").unwrap(), Some(&"pattern"));
-        assert_eq!(t.get("abcx").unwrap(), Some(&"pattern"));
-        assert_eq!(t.get("aaabbbcccx").unwrap(), Some(&"pattern"));
-        assert_eq!(t.get("x").unwrap(), None);
-        assert_eq!(t.get("dx").unwrap(), None);
+        assert_eq!(t.get("cd").unwrap(), Some(&"ab"));
+        assert_eq!(t.get("b").unwrap(), None);
+        assert_eq!(t.node_count(), 5);
     }
 
     #[test]
-    fn complex_alternating_classes() {
-        let mut t: Table<&str> = Table::new("ab".to_string());
-        t.add("[ab][ab][ab]", "three").unwrap();
+    fn restore_undoes_normalize_alphabet() {
+        let mut t: Table<&str> = Table::new("ba".to_string());
+        t.add("ab", "ab").unwrap();
+        let snapshot = t.snapshot();
+        t.normalize_alphabet();
+        assert_eq!(t.alphabet, "ab");
+        t.restore(snapshot);
+
+        assert_eq!(t.alphabet, "ba");
+        assert_eq!(t.get("ab").unwrap(), Some(&"ab"));
+        assert_eq!(t.get("ba").unwrap(), None);
+    }
 
-        assert_eq!(t.get("aaa").unwrap(), Some(&"three"));
-        assert_eq!(t.get("bbb").unwrap(), Some(&"three"));
-        assert_eq!(t.get("aba").unwrap(), Some(&"three"));
-        assert_eq!(t.get("bab").unwrap(), Some(&"three"));
-        assert_eq!(t.get("ab").unwrap(), None);
-        assert_eq!(t.get("abab").unwrap(), None);
+    #[test]
+    fn journal_only_while_a_snapshot_lives() {
+        let mut t = alpha();
+        t.add("ab", "ab").unwrap();
+        let snapshot = t.snapshot();
+        t.add("a", "a").unwrap();
+        assert_eq!(t.journal.len(), 1);
+        drop(snapshot);
+        t.add("abc", "abc").unwrap();
+        assert!(t.journal.is_empty());
     }
 
     // ========================================================================
-    // NODE REUSE / STRUCTURE
+    // COVERAGE
     // ========================================================================
 
     #[test]
-    fn structure_shared_prefix_reuses_nodes() {
-        let mut t = alpha();
-        t.add("abc", "v1").unwrap();
-        let nodes_after_first = t.nodes.len();
+    fn coverage_of_tokenizable_input() {
+        let t = words_and_numbers();
+        assert_eq!(t.coverage("ab12cd"), Ok(6));
+        assert_eq!(t.coverage(""), Ok(0));
+    }
 
-        t.add("abd", "v2").unwrap();
-        let nodes_after_second = t.nodes.len();
+    #[test]
+    fn coverage_stops_at_first_error() {
+        let t = words_and_numbers();
+        assert_eq!(t.coverage("ab12-cd"), Ok(4));
+        assert_eq!(t.coverage("-"), Ok(0));
 
-        // "abd" should reuse nodes for "ab" and add only one for 'd'
-        assert_eq!(nodes_after_second, nodes_after_first + 1);
+        let mut t = alpha();
+        t.add("ab", "ab").unwrap();
+        // no pattern matches at `a`
+        assert_eq!(t.coverage("abac"), Ok(2));
+        assert!(matches!(t.coverage("é"), Err(LexerError::InvalidString(_))));
     }
 
+    // ========================================================================
+    // ADD OR REPLACE
+    // ========================================================================
+
     #[test]
-    fn structure_plus_creates_self_loop() {
+    fn add_or_replace_sets_every_terminal() {
         let mut t = alpha();
-        t.add("a+", "loop").unwrap();
+        t.add("x[abc]", "old").unwrap();
+        t.add_or_replace("x[abc]", "new").unwrap();
+        assert_eq!(t.get("xa").unwrap(), Some(&"new"));
+        assert_eq!(t.get("xb").unwrap(), Some(&"new"));
+        assert_eq!(t.get("xc").unwrap(), Some(&"new"));
+
+        // terminals without a value take it too
+        t.add_or_replace("x[cd]", "new").unwrap();
+        assert_eq!(t.get("xd").unwrap(), Some(&"new"));
+    }
 
-        let a_pos = t.alphabet.find('a').unwrap();
-        let first_node = t.nodes[0].get_children(a_pos).unwrap();
-        let loop_target = t.nodes[*first_node].get_children(a_pos).unwrap();
+    #[test]
+    fn add_or_replace_reports_divergent_terminals() {
+        let mut t = alpha();
+        t.add("a", "first").unwrap();
+        t.add("c", "second").unwrap();
+        let nodes = t.node_count();
+        let tree = t.debug_tree(false);
+        let result = t.add_or_replace("[abc]", "all");
+        assert_eq!(
+            result,
+            Err(TableError::ConflictingValues(vec![
+                ("a".to_string(), "first"),
+                ("c".to_string(), "second"),
+            ]))
+        );
+        assert_eq!(
+            result.unwrap_err().to_string(),
+            "Conflicting values: \"a\"=\"first\" \"c\"=\"second\""
+        );
+        // nothing was replaced, and the node for "b" was not kept
+        assert_eq!(t.get("a").unwrap(), Some(&"first"));
+        assert_eq!(t.get("b").unwrap(), None);
+        assert_eq!(t.node_count(), nodes);
+        assert_eq!(t.debug_tree(false), tree);
 
-        // Node should point to itself
-        assert_eq!(first_node, loop_target);
+        assert_eq!(
+            t.add_or_replace("b1", "bad"),
+            Err(TableError::InvalidInput('1'))
+        );
+        assert_eq!(t.node_count(), nodes);
     }
 
     // ========================================================================
-    // BASIC TOKENIZATION
+    // TRANSITION FUNCTION
     // ========================================================================
 
     #[test]
-    fn lexer_basic_expression() {
-        #[derive(Debug, Clone, PartialEq)]
-        enum Kind {
-            Number,
-            Add,
-            Sub,
-            Mul,
-            Div,
-        }
-
-        let mut t = Table::new("0123456789+-*/".to_string());
-        t.add("[0123456789]+", Kind::Number).unwrap();
-        t.add("+", Kind::Add).unwrap();
-        t.add("-", Kind::Sub).unwrap();
-        t.add("*", Kind::Mul).unwrap();
-        t.add("/", Kind::Div).unwrap();
+    fn transition_fn_walks_to_terminal() {
+        let mut t = alpha();
+        t.add("ab", "ab").unwrap();
+        let step = t.transition_fn();
+        let end = "ab".chars().try_fold(0, &step).unwrap();
+        assert_eq!(t.terminal_indices(), vec![end]);
+
+        assert_eq!(step(0, 'b'), None);
+        assert_eq!(step(0, '1'), None);
+        assert_eq!(step(0, 'é'), None);
+        assert_eq!(step(99, 'a'), None);
+    }
 
-        let iter = t.lexer("1+2*3").unwrap();
-        let tokens: Vec<_> = iter.collect::<Result<_, _>>().unwrap();
+    // ========================================================================
+    // LEXER EVENTS
+    // ========================================================================
 
-        assert_eq!(tokens.len(), 5);
-        assert_eq!(tokens[0], (&Kind::Number, "1"));
-        assert_eq!(tokens[1], (&Kind::Add, "+"));
-        assert_eq!(tokens[2], (&Kind::Number, "2"));
-        assert_eq!(tokens[3], (&Kind::Mul, "*"));
-        assert_eq!(tokens[4], (&Kind::Number, "3"));
+    #[test]
+    fn lexer_events_tokens_and_errors() {
+        let mut t = digits();
+        t.add("[0123456789]+", 1).unwrap();
+        let events: Vec<_> = t.lexer_events("1@23").unwrap().collect();
+        assert_eq!(
+            events,
+            vec![
+                LexerEvent::Token {
+                    kind: &1,
+                    text: "1",
+                    span: 0..1
+                },
+                LexerEvent::Error {
+                    error: LexerError::UnknownChar {
+                        char: '@',
+                        position: 1
+                    },
+                    span: 1..2
+                },
+                LexerEvent::Token {
+                    kind: &1,
+                    text: "23",
+                    span: 2..4
+                },
+            ]
+        );
+        assert!(t.lexer_events("é").is_err());
     }
 
+    // ========================================================================
+    // TOKENIZABLE PREFIX
+    // ========================================================================
+
     #[test]
-    fn lexer_single_token() {
-        #[derive(Debug, Clone, PartialEq)]
-        enum Kind {
-            Word,
-        }
+    fn longest_tokenizable_prefix_before_stray_char() {
+        let mut t: Table<&str> = Table::new("0123456789+".to_string());
+        t.add("[0123456789]+", "num").unwrap();
+        t.add("+", "plus").unwrap();
+        assert_eq!(t.longest_tokenizable_prefix("1+2@3"), "1+2");
+        assert_eq!(t.longest_tokenizable_prefix("1+2"), "1+2");
+        assert_eq!(t.longest_tokenizable_prefix("12é3"), "12");
+        assert_eq!(t.longest_tokenizable_prefix("@1"), "");
+    }
 
-        let mut t = Table::new("abcdefghijklmnopqrstuvwxyz".to_string());
-        t.add("[abcdefghijklmnopqrstuvwxyz]+", Kind::Word).unwrap();
+    #[test]
+    fn longest_tokenizable_prefix_backs_off() {
+        let mut t: Table<&str> = Table::new("ab".to_string());
+        t.add("a", "a").unwrap();
+        t.add("abb", "abb").unwrap();
+        // "ab" has no token for the `b`, so the prefix stops after "a"
+        assert_eq!(t.longest_tokenizable_prefix("abab"), "a");
+        assert_eq!(t.longest_tokenizable_prefix("abba"), "abba");
+    }
 
-        let tokens: Vec<_> = t.lexer("hello").unwrap().collect::<Result<_, _>>().unwrap();
+    // ========================================================================
+    // EXTEND
+    // ========================================================================
 
-        assert_eq!(tokens.len(), 1);
-        assert_eq!(tokens[0], (&Kind::Word, "hello"));
+    #[test]
+    fn extend_with_borrowed_patterns() {
+        let mut t = alpha();
+        t.extend([("get", "GET"), ("put", "PUT"), ("[xy]+", "XY")]);
+        assert_eq!(t.get("get").unwrap(), Some(&"GET"));
+        assert_eq!(t.get("put").unwrap(), Some(&"PUT"));
+        assert_eq!(t.get("xyx").unwrap(), Some(&"XY"));
     }
 
     #[test]
-    fn lexer_empty_input() {
-        #[derive(Debug, Clone, PartialEq)]
-        enum Kind {
-            Number,
-        }
-
-        let mut t = Table::new("0123456789".to_string());
-        t.add("[0123456789]+", Kind::Number).unwrap();
+    #[should_panic(expected = "cannot add pattern \"get\"")]
+    fn extend_panics_on_duplicate() {
+        let mut t = alpha();
+        t.extend([("get", "GET"), ("get", "OTHER")]);
+    }
 
-        let tokens: Vec<_> = t.lexer("").unwrap().collect::<Result<_, _>>().unwrap();
+    // ========================================================================
+    // SELF LOOPS
+    // ========================================================================
 
-        assert!(tokens.is_empty());
+    #[test]
+    fn self_loops_report_repetitions() {
+        let mut t = alpha();
+        t.add("a+", "as").unwrap();
+        t.add("x[bc]+", "bcs").unwrap();
+        t.add("xy", "xy").unwrap();
+        let x = (t.transition_fn())(0, 'x').unwrap();
+        let b = (t.transition_fn())(x, 'b').unwrap();
+        let c = (t.transition_fn())(x, 'c').unwrap();
+        let a = (t.transition_fn())(0, 'a').unwrap();
+        let mut expected = vec![(a, 'a'), (b, 'b'), (b, 'c'), (c, 'b'), (c, 'c')];
+        expected.sort();
+        assert_eq!(t.self_loops(), expected);
+
+        assert!(alpha().self_loops().is_empty());
     }
 
+    // ========================================================================
+    // ANY CHAR
+    // ========================================================================
+
     #[test]
-    fn lexer_manual_iteration() {
-        #[derive(Debug, Clone, PartialEq)]
-        enum Kind {
-            A,
-            B,
+    fn any_escape_matches_every_alphabet_char() {
+        let mut t: Table<i32> = Table::new("abAXyz".to_string());
+        t.add("a\\Ab", 1).unwrap();
+        for ch in "abAXyz".chars() {
+            assert_eq!(t.get(&format!("a{ch}b")).unwrap(), Some(&1));
         }
+        assert_eq!(t.get("ab").unwrap(), None);
+        assert_eq!(t.get("aXXb").unwrap(), None);
+    }
 
-        let mut t = Table::new("ab".to_string());
-        t.add("a", Kind::A).unwrap();
-        t.add("b", Kind::B).unwrap();
+    #[test]
+    fn any_escape_is_not_a_literal_a() {
+        let mut t: Table<i32> = Table::new("Aab\\".to_string());
+        t.add("\\A+", 1).unwrap();
+        // `A` is one of the strings `\A+` already matches
+        assert!(matches!(
+            t.add("A", 2),
+            Err(TableError::ValueAlreadyDefined { .. })
+        ));
 
-        let mut iter = t.lexer("aba").unwrap();
+        let mut t: Table<i32> = Table::new("Aab\\".to_string());
+        t.add("bA", 1).unwrap();
+        assert_eq!(t.get("bA").unwrap(), Some(&1));
+        assert_eq!(t.get("ba").unwrap(), None);
+        // a backslash not followed by `A` is a literal
+        t.add("\\b", 3).unwrap();
+        assert_eq!(t.get("\\b").unwrap(), Some(&3));
+    }
 
-        let (kind, content) = iter.next().unwrap().unwrap();
-        assert_eq!(kind, &Kind::A);
-        assert_eq!(content, "a");
+    // ========================================================================
+    // NODE FANOUT
+    // ========================================================================
 
-        let (kind, content) = iter.next().unwrap().unwrap();
-        assert_eq!(kind, &Kind::B);
-        assert_eq!(content, "b");
+    #[test]
+    fn node_fanout_per_node() {
+        let mut t = alpha();
+        t.add("get", "get").unwrap();
+        t.add("go", "go").unwrap();
+        t.add("[xyz]", "xyz").unwrap();
+        t.add("a+", "as").unwrap();
+        let fanout = t.node_fanout();
+        // g, x, y, z, a
+        assert_eq!(fanout[0], 5);
+        assert_eq!(fanout.len(), t.node_count());
+        assert_eq!(fanout.iter().sum::<usize>(), t.transition_count());
+        let g = (t.transition_fn())(0, 'g').unwrap();
+        assert_eq!(fanout[g], 2);
+    }
 
-        let (kind, content) = iter.next().unwrap().unwrap();
-        assert_eq!(kind, &Kind::A);
-        assert_eq!(content, "a");
+    // ========================================================================
+    // FIRST WINS
+    // ========================================================================
 
-        assert!(iter.next().is_none());
+    #[test]
+    fn first_wins_keeps_earliest_value() {
+        let mut t = Table::new_first_wins("abcdefghijklmnopqrstuvwxyz".to_string());
+        t.add("[ab]x", "first").unwrap();
+        t.add("ax", "second").unwrap();
+        assert_eq!(t.get("ax").unwrap(), Some(&"first"));
+
+        t.add("if", "keyword").unwrap();
+        t.add("[abcdefghijklmnopqrstuvwxyz]+", "ident").unwrap();
+        let tokens: Vec<_> = t.lexer("if").unwrap().collect::<Result<_, _>>().unwrap();
+        assert_eq!(tokens, vec![(&"keyword", "if")]);
+        assert_eq!(t.get("ix").unwrap(), Some(&"ident"));
+        assert_eq!(t.get("bx").unwrap(), Some(&"first"));
     }
 
     // ========================================================================
-    // LONGEST MATCH (MAXIMAL MUNCH)
+    // PRUNE EMPTY
     // ========================================================================
 
     #[test]
-    fn lexer_longest_match_numbers() {
-        #[derive(Debug, Clone, PartialEq)]
-        enum Kind {
-            Number,
-            Add,
-        }
+    fn prune_empty_removes_dead_ends() {
+        let mut t = alpha();
+        t.add("ab", "ab").unwrap();
+        t.add("c+", "cs").unwrap();
+        let before = t.debug_tree(false);
+        // the nodes for "xyz" are created before `1` fails
+        assert!(t.add("xyz1", "bad").is_err());
+        assert!(t.add("ab[xy]1", "bad").is_err());
+        assert_eq!(t.prune_empty(), 5);
+        assert_eq!(t.debug_tree(false), before);
+        assert_eq!(t.node_count(), 4);
+
+        assert_eq!(t.get("ab").unwrap(), Some(&"ab"));
+        assert_eq!(t.get("ccc").unwrap(), Some(&"cs"));
+        assert_eq!(t.get("x").unwrap(), None);
+        assert_eq!(t.prune_empty(), 0);
+        t.add("xy", "xy").unwrap();
+        assert_eq!(t.get("xy").unwrap(), Some(&"xy"));
+    }
 
-        let mut t = Table::new("0123456789+".to_string());
-        t.add("[0123456789]+", Kind::Number).unwrap();
-        t.add("+", Kind::Add).unwrap();
+    // ========================================================================
+    // SKIP PATTERNS
+    // ========================================================================
+
+    fn with_skips() -> Table<&'static str> {
+        let mut t: Table<&str> = Table::new("abcxyz0123456789 #\n".to_string());
+        t.add("[abcxyz]+", "word").unwrap();
+        t.add("[0123456789]+", "num").unwrap();
+        t.add_skip(" +").unwrap();
+        t.add_skip("#[abcxyz0123456789 ]+\n").unwrap();
+        t
+    }
 
+    #[test]
+    fn skip_patterns_are_consumed() {
+        let t = with_skips();
         let tokens: Vec<_> = t
-            .lexer("123+456")
+            .lexer("  abc 12 #cab 1\nxyz  ")
             .unwrap()
             .collect::<Result<_, _>>()
             .unwrap();
+        assert_eq!(
+            tokens,
+            vec![(&"word", "abc"), (&"num", "12"), (&"word", "xyz")]
+        );
+        assert_eq!(t.lexer("   ").unwrap().count(), 0);
+        assert_eq!(t.get(" ").unwrap(), None);
 
-        assert_eq!(tokens.len(), 3);
-        assert_eq!(tokens[0], (&Kind::Number, "123"));
-        assert_eq!(tokens[1], (&Kind::Add, "+"));
-        assert_eq!(tokens[2], (&Kind::Number, "456"));
+        let owned: Vec<_> = with_skips()
+            .into_lexer("a 1".to_string())
+            .unwrap()
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert_eq!(
+            owned,
+            vec![("word", "a".to_string()), ("num", "1".to_string())]
+        );
+    }
+
+    #[test]
+    fn skip_patterns_step_token() {
+        let t = with_skips();
+        assert_eq!(t.step_token("  a"), StepResult::Skip { len: 2 });
+        assert_eq!(
+            t.step_token("ab "),
+            StepResult::Token {
+                value: &"word",
+                len: 2
+            }
+        );
+        // more spaces could follow
+        assert_eq!(t.step_token("  "), StepResult::NeedMore);
     }
 
     #[test]
-    fn lexer_longest_match_overlapping_literals() {
-        #[derive(Debug, Clone, PartialEq)]
-        enum Kind {
-            Eq,
-            EqEq,
-        }
+    fn skip_patterns_keep_offsets() {
+        let t = with_skips();
+        let events: Vec<_> = t.lexer_events("a  1").unwrap().collect();
+        assert_eq!(
+            events,
+            vec![
+                LexerEvent::Token {
+                    kind: &"word",
+                    text: "a",
+                    span: 0..1
+                },
+                LexerEvent::Token {
+                    kind: &"num",
+                    text: "1",
+                    span: 3..4
+                },
+            ]
+        );
+        assert_eq!(
+            t.explain("a  1"),
+            "0..1 \"word\" \"a\"\n3..4 \"num\" \"1\"\n"
+        );
+        let read: Vec<_> = t
+            .lexer_reader(std::io::Cursor::new(&b" a #c\n"[..]))
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert_eq!(read, vec![("word", "a".to_string())]);
+    }
 
-        let mut t = Table::new("=".to_string());
-        t.add("=", Kind::Eq).unwrap();
-        t.add("==", Kind::EqEq).unwrap();
+    // ========================================================================
+    // TOKEN START
+    // ========================================================================
 
-        // "==" should match as EqEq
-        let tokens: Vec<_> = t.lexer("==").unwrap().collect::<Result<_, _>>().unwrap();
-        assert_eq!(tokens.len(), 1);
-        assert_eq!(tokens[0], (&Kind::EqEq, "=="));
+    #[test]
+    fn can_start_token_checks_root() {
+        let mut t = alpha();
+        t.add("ab", "ab").unwrap();
+        t.add("c+", "cs").unwrap();
+        assert!(t.can_start_token('a'));
+        assert!(t.can_start_token('c'));
+        assert!(!t.can_start_token('b'));
+        assert!(!t.can_start_token('1'));
+        assert!(!t.can_start_token('é'));
+    }
 
-        // "=" should match as Eq
-        let tokens: Vec<_> = t.lexer("=").unwrap().collect::<Result<_, _>>().unwrap();
-        assert_eq!(tokens.len(), 1);
-        assert_eq!(tokens[0], (&Kind::Eq, "="));
+    // ========================================================================
+    // NODE BUDGET
+    // ========================================================================
 
-        // "===" should be EqEq + Eq
-        let tokens: Vec<_> = t.lexer("===").unwrap().collect::<Result<_, _>>().unwrap();
-        assert_eq!(tokens.len(), 2);
-        assert_eq!(tokens[0], (&Kind::EqEq, "=="));
-        assert_eq!(tokens[1], (&Kind::Eq, "="));
+    #[test]
+    fn node_budget_allows_small_patterns() {
+        let mut t = Table::new_with_node_budget("abcdefghijklmnopqrstuvwxyz".to_string(), 6);
+        t.add("abc", "abc").unwrap();
+        t.add("ab[xy]", "abxy").unwrap();
+        assert_eq!(t.node_count(), 6);
+        // reusing the existing nodes is always possible
+        t.add("ab", "ab").unwrap();
     }
 
     #[test]
-    fn lexer_longest_match_backtrack() {
-        #[derive(Debug, Clone, PartialEq)]
-        enum Kind {
-            Ab,
-            Abc,
-            X,
-        }
-
-        let mut t = Table::new("abcx".to_string());
-        t.add("ab", Kind::Ab).unwrap();
-        t.add("abc", Kind::Abc).unwrap();
-        t.add("x", Kind::X).unwrap();
+    fn node_budget_exceeded_leaves_table_unchanged() {
+        let mut t = Table::new_with_node_budget("abcdefghijklmnopqrstuvwxyz".to_string(), 6);
+        t.add("ab", "ab").unwrap();
+        let before = (t.debug_tree(false), t.node_count(), t.transition_count());
 
-        // "abx" - matches "ab" then "x" (not "abc" partial)
-        let tokens: Vec<_> = t.lexer("abx").unwrap().collect::<Result<_, _>>().unwrap();
-        assert_eq!(tokens.len(), 2);
-        assert_eq!(tokens[0], (&Kind::Ab, "ab"));
-        assert_eq!(tokens[1], (&Kind::X, "x"));
+        assert_eq!(
+            t.add("a[bcd][efg]+", "big"),
+            Err(TableError::NodeBudgetExceeded { budget: 6 })
+        );
+        assert_eq!(
+            (t.debug_tree(false), t.node_count(), t.transition_count()),
+            before
+        );
+        assert_eq!(t.get("abe").unwrap(), None);
+        // a failing pattern is undone as well
+        assert!(t.add("xy1", "bad").is_err());
+        assert_eq!(t.node_count(), 3);
     }
 
     #[test]
-    fn lexer_longest_match_greedy() {
-        #[derive(Debug, Clone, PartialEq)]
-        enum Kind {
-            A,
-            Aa,
-            Aaa,
-        }
-
-        let mut t = Table::new("a".to_string());
-        t.add("a", Kind::A).unwrap();
-        t.add("aa", Kind::Aa).unwrap();
-        t.add("aaa", Kind::Aaa).unwrap();
-
-        let tokens: Vec<_> = t.lexer("aaa").unwrap().collect::<Result<_, _>>().unwrap();
-        assert_eq!(tokens.len(), 1);
-        assert_eq!(tokens[0], (&Kind::Aaa, "aaa"));
-
-        let tokens: Vec<_> = t.lexer("aaaa").unwrap().collect::<Result<_, _>>().unwrap();
-        assert_eq!(tokens.len(), 2);
-        assert_eq!(tokens[0], (&Kind::Aaa, "aaa"));
-        assert_eq!(tokens[1], (&Kind::A, "a"));
+    fn node_budget_stops_before_building() {
+        // 26^10 nodes: the walk must stop at the budget, not after
+        let mut t = Table::new_with_node_budget("abcdefghijklmnopqrstuvwxyz".to_string(), 100);
+        assert_eq!(
+            t.add("[a-z]{10}", "word"),
+            Err(TableError::NodeBudgetExceeded { budget: 100 })
+        );
+        assert_eq!(t.node_count(), 1);
 
-        let tokens: Vec<_> = t.lexer("aaaaa").unwrap().collect::<Result<_, _>>().unwrap();
-        assert_eq!(tokens.len(), 2);
-        assert_eq!(tokens[0], (&Kind::Aaa, "aaa"));
-        assert_eq!(tokens[1], (&Kind::Aa, "aa"));
+        let mut t = Table::new_with_node_budget("abcdefghijklmnopqrstuvwxyz".to_string(), 3);
+        t.add("[ab]", "ab").unwrap();
+        assert_eq!(
+            t.reserve("ab", "kw"),
+            Err(TableError::NodeBudgetExceeded { budget: 3 })
+        );
+        assert_eq!(t.node_count(), 3);
+        assert_eq!(t.get("a").unwrap(), Some(&"ab"));
     }
 
     // ========================================================================
-    // ERROR HANDLING
+    // TOKEN POSITIONS
     // ========================================================================
 
     #[test]
-    fn lexer_error_unknown_char_immediate() {
-        // Il lexer restituisce errore immediatamente quando incontra
-        // un carattere fuori dall'alfabeto, anche durante un match parziale
-        #[derive(Debug, Clone, PartialEq)]
-        enum Kind {
-            Number,
-        }
-
-        let mut t = Table::new("0123456789".to_string());
-        t.add("[0123456789]+", Kind::Number).unwrap();
+    fn last_token_positions_of_class_match() {
+        let mut t: Table<&str> = Table::new("abc ".to_string());
+        t.add("[abc]+", "word").unwrap();
+        t.add(" ", "space").unwrap();
+        let mut iter = t.lexer("cab a").unwrap();
+        assert!(iter.last_token_positions().is_empty());
+        assert_eq!(iter.next(), Some(Ok((&"word", "cab"))));
+        assert_eq!(iter.last_token_positions(), vec![2, 0, 1]);
+        iter.next();
+        assert_eq!(iter.last_token_positions(), vec![3]);
+    }
 
-        let mut iter = t.lexer("12@34").unwrap();
+    // ========================================================================
+    // ALPHABET UNION
+    // ========================================================================
 
-        // Errore immediato su '@', non restituisce "12" prima
-        let err = iter.next().unwrap().unwrap_err();
+    #[test]
+    fn union_alphabet_lexes_mixed_expression() {
+        let mut nums = digits();
+        nums.add("[0123456789]+", 1).unwrap();
+        let mut ops: Table<i32> = Table::new_with_literals("+-*".to_string(), &['*']);
+        ops.add("[+-]", 2).unwrap();
+        ops.add("*", 3).unwrap();
+        ops.add("**", 4).unwrap();
+
+        let t = nums.union_alphabet(ops).unwrap();
+        let tokens: Vec<_> = t
+            .lexer("12+3**40-5")
+            .unwrap()
+            .collect::<Result<_, _>>()
+            .unwrap();
         assert_eq!(
-            err,
-            LexerError::UnknownChar {
-                char: '@',
-                position: 2
-            }
+            tokens,
+            vec![
+                (&1, "12"),
+                (&2, "+"),
+                (&1, "3"),
+                (&4, "**"),
+                (&1, "40"),
+                (&2, "-"),
+                (&1, "5")
+            ]
         );
     }
 
     #[test]
-    fn lexer_error_unknown_char_at_start() {
-        #[derive(Debug, Clone, PartialEq)]
-        enum Kind {
-            Number,
-        }
+    fn union_alphabet_shared_keys() {
+        let mut a: Table<i32> = Table::new("ab".to_string());
+        a.add("a+", 1).unwrap();
+        let mut b: Table<i32> = Table::new("abc".to_string());
+        b.add("ab", 2).unwrap();
+        b.add("c", 3).unwrap();
+        let t = a.union_alphabet(b).unwrap();
+        assert_eq!(t.get("aaa").unwrap(), Some(&1));
+        assert_eq!(t.get("ab").unwrap(), Some(&2));
+        assert_eq!(t.get("aab").unwrap(), None);
+        assert_eq!(t.get("c").unwrap(), Some(&3));
 
-        let mut t = Table::new("0123456789".to_string());
-        t.add("[0123456789]+", Kind::Number).unwrap();
+        let mut a: Table<i32> = Table::new("ab".to_string());
+        a.add("a[ab]", 1).unwrap();
+        let mut c: Table<i32> = Table::new("ab".to_string());
+        c.add("ab", 2).unwrap();
+        assert_eq!(
+            a.union_alphabet(c).map(|_| ()),
+            Err(TableError::ValueAlreadyDefined {
+                current: 1,
+                requested: 2
+            })
+        );
+    }
 
-        let mut iter = t.lexer("@123").unwrap();
+    // ========================================================================
+    // GAPS
+    // ========================================================================
 
-        let err = iter.next().unwrap().unwrap_err();
+    #[test]
+    fn lexer_with_gaps_counts_skipped_bytes() {
+        let mut t: Table<&str> = Table::new("0123456789+ ".to_string());
+        t.add("[0123456789]+", "num").unwrap();
+        t.add("+", "plus").unwrap();
+        t.add_skip(" +").unwrap();
+        let tokens: Vec<_> = t
+            .lexer_with_gaps("1   +   2")
+            .unwrap()
+            .collect::<Result<_, _>>()
+            .unwrap();
         assert_eq!(
-            err,
-            LexerError::UnknownChar {
-                char: '@',
-                position: 0
-            }
+            tokens,
+            vec![(&"num", "1", 0), (&"plus", "+", 3), (&"num", "2", 3)]
         );
+        let leading: Vec<_> = t.lexer_with_gaps("  7 ").unwrap().collect();
+        assert_eq!(leading, vec![Ok((&"num", "7", 2))]);
     }
 
+    // ========================================================================
+    // ACCEPTOR
+    // ========================================================================
+
     #[test]
-    fn lexer_error_unknown_char_after_valid_token() {
-        #[derive(Debug, Clone, PartialEq)]
-        enum Kind {
-            A,
-            B,
-        }
+    fn into_acceptor_keeps_membership() {
+        let mut t = digits();
+        t.add("12", 1).unwrap();
+        t.add("3[45]+", 2).unwrap();
+        let keys: Vec<String> = t.keys().collect();
+        let samples = ["12", "1", "34", "3455", "3", "", "123"];
+        let expected: Vec<bool> = samples.iter().map(|s| t.is_match(s)).collect();
+
+        let acceptor = t.into_acceptor();
+        assert_eq!(acceptor.keys().collect::<Vec<_>>(), keys);
+        let actual: Vec<bool> = samples.iter().map(|s| acceptor.is_match(s)).collect();
+        assert_eq!(actual, expected);
+        assert_eq!(acceptor.get("3545").unwrap(), Some(&()));
+        assert_eq!(acceptor.get("35x"), Err(TableError::InvalidInput('x')));
+    }
 
-        let mut t = Table::new("ab".to_string());
-        t.add("a", Kind::A).unwrap();
-        t.add("b", Kind::B).unwrap();
+    // ========================================================================
+    // BYTE KEYS
+    // ========================================================================
 
-        let mut iter = t.lexer("a@b").unwrap();
+    #[test]
+    fn get_bytes_matches_get() {
+        let mut t = alpha();
+        t.add("ab+", "abs").unwrap();
+        for key in ["ab", "abbb", "a", "", "ba"] {
+            assert_eq!(t.get_bytes(key.as_bytes()), t.get(key));
+        }
+        assert_eq!(t.get_bytes(b"ab1"), Err(TableError::InvalidInput('1')));
+    }
 
-        let err = iter.next().unwrap().unwrap_err();
+    #[test]
+    fn get_bytes_high_byte_is_invalid() {
+        let mut t = alpha();
+        t.add("ab", "ab").unwrap();
         assert_eq!(
-            err,
-            LexerError::UnknownChar {
-                char: '@',
-                position: 1
-            }
+            t.get_bytes(&[b'a', 0xe9]),
+            Err(TableError::InvalidInput('\u{e9}'))
         );
     }
 
-    #[test]
-    fn lexer_error_unexpected_end_no_pattern() {
-        #[derive(Debug, Clone, PartialEq)]
-        enum Kind {
-            Abc,
-        }
+    // ========================================================================
+    // CONVERGENCES
+    // ========================================================================
 
-        let mut t = Table::new("abcdef".to_string());
-        t.add("abc", Kind::Abc).unwrap();
+    #[test]
+    fn convergences_after_repetition() {
+        let mut t = alpha();
+        t.add("a+[ab]", "v").unwrap();
+        let a = (t.transition_fn())(0, 'a').unwrap();
+        // reached from the root and from its own loop
+        assert_eq!(t.convergences(), vec![a]);
 
-        let mut iter = t.lexer("abcdef").unwrap();
+        let mut t = alpha();
+        t.add("ab", "ab").unwrap();
+        t.add("cd", "cd").unwrap();
+        assert!(t.convergences().is_empty());
+    }
 
-        // "abc" matches
-        let (kind, content) = iter.next().unwrap().unwrap();
-        assert_eq!(kind, &Kind::Abc);
-        assert_eq!(content, "abc");
+    // ========================================================================
+    // CLASS ORDER
+    // ========================================================================
 
-        // "def" is in alphabet but no pattern matches
-        let err = iter.next().unwrap().unwrap_err();
-        assert_eq!(err, LexerError::UnexpectedEnd { position: 3 });
+    #[test]
+    fn class_order_does_not_change_layout() {
+        let mut a = alpha();
+        a.add("x[cab]+y", "v").unwrap();
+        let mut b = alpha();
+        b.add("x[abc]+y", "v").unwrap();
+        assert_eq!(format!("{:?}", a.nodes), format!("{:?}", b.nodes));
+
+        let mut d = alpha();
+        d.add("[ba]", "v").unwrap();
+        let b_node = (d.transition_fn())(0, 'b').unwrap();
+        let a_node = (d.transition_fn())(0, 'a').unwrap();
+        assert!(a_node < b_node);
+
+        // the order of the alphabet, not of the chars
+        let mut d: Table<&str> = Table::new("ba".to_string());
+        d.add("[ab]", "v").unwrap();
+        let b_node = (d.transition_fn())(0, 'b').unwrap();
+        let a_node = (d.transition_fn())(0, 'a').unwrap();
+        assert!(b_node < a_node);
     }
 
-    #[test]
-    fn lexer_error_unexpected_end_at_start() {
-        #[derive(Debug, Clone, PartialEq)]
-        enum Kind {
-            Abc,
-        }
+    // ========================================================================
+    // MANY INPUTS
+    // ========================================================================
 
-        let mut t = Table::new("abcdef".to_string());
-        t.add("abc", Kind::Abc).unwrap();
+    #[test]
+    fn tokenize_many_per_input_results() {
+        let mut t: Table<&str> = Table::new("0123456789+".to_string());
+        t.add("[0123456789]+", "num").unwrap();
+        t.add("+", "plus").unwrap();
+        let results = t.tokenize_many(&["1+2", "3-4", "56"]);
+        assert_eq!(
+            results,
+            vec![
+                Ok(vec![(&"num", "1"), (&"plus", "+"), (&"num", "2")]),
+                Err(LexerError::UnknownChar {
+                    char: '-',
+                    position: 1
+                }),
+                Ok(vec![(&"num", "56")]),
+            ]
+        );
+        assert!(t.tokenize_many(&[]).is_empty());
+    }
 
-        let mut iter = t.lexer("def").unwrap();
+    // ========================================================================
+    // INTERNAL VALUES
+    // ========================================================================
 
-        // "def" starts with 'd' which has no transition from root
-        let err = iter.next().unwrap().unwrap_err();
-        assert_eq!(err, LexerError::UnexpectedEnd { position: 0 });
+    #[test]
+    fn verify_no_internal_values_on_clean_pattern() {
+        let mut t = alpha();
+        t.add("a+b", "ab").unwrap();
+        t.add("b", "b").unwrap();
+        assert_eq!(t.verify_no_internal_values("a+b"), Ok(()));
+        let tokens: Vec<_> = t.lexer("aabb").unwrap().collect::<Result<_, _>>().unwrap();
+        assert_eq!(tokens, vec![(&"ab", "aab"), (&"b", "b")]);
+        assert!(t.lexer("a").unwrap().next().unwrap().is_err());
     }
 
     #[test]
-    fn lexer_error_invalid_string_non_ascii() {
-        #[derive(Debug, Clone, PartialEq)]
-        enum Kind {
-            Word,
-        }
+    fn verify_no_internal_values_reports_stray_values() {
+        let mut t = alpha();
+        t.add("a+b", "ab").unwrap();
+        // "aa" ends on the looping node of `a+`, so a bare "a" becomes a token
+        t.add("aa", "aa").unwrap();
+        assert_eq!(
+            t.verify_no_internal_values("a+b"),
+            Err(TableError::InternalValues(vec![("a".to_string(), "aa")]))
+        );
+        assert_eq!(t.lexer("a").unwrap().next(), Some(Ok((&"aa", "a"))));
+        assert_eq!(t.verify_no_internal_values("aa"), Ok(()));
+    }
 
-        let mut t = Table::new("abc".to_string());
-        t.add("[abc]+", Kind::Word).unwrap();
+    // ========================================================================
+    // CHAR FREQUENCY
+    // ========================================================================
 
-        let result = t.lexer("héllo");
-        assert!(matches!(result, Err(LexerError::InvalidString(_))));
+    #[test]
+    fn char_frequency_counts_transitions() {
+        let mut t = alpha();
+        t.add("ab", "ab").unwrap();
+        t.add("ba", "ba").unwrap();
+        t.add("za+", "za").unwrap();
+        let frequency = t.char_frequency();
+        // "ab", "ba", "za" and the loop of `a+`
+        assert_eq!(frequency[&'a'], 4);
+        assert_eq!(frequency[&'b'], 2);
+        assert_eq!(frequency[&'z'], 1);
+        assert!(!frequency.contains_key(&'c'));
+        assert_eq!(frequency.values().sum::<usize>(), t.transition_count());
     }
 
-    #[test]
-    fn lexer_error_invalid_string_emoji() {
-        #[derive(Debug, Clone, PartialEq)]
-        enum Kind {
-            Word,
-        }
+    // ========================================================================
+    // MINIMUM LENGTH
+    // ========================================================================
 
-        let mut t = Table::new("abc".to_string());
-        t.add("[abc]+", Kind::Word).unwrap();
+    #[test]
+    fn lexer_min_len_rejects_short_matches() {
+        let mut t: Table<&str> = Table::new("ab ".to_string());
+        t.add("[ab]+", "word").unwrap();
+        t.add(" ", "space").unwrap();
 
-        let result = t.lexer("abc😀");
-        assert!(matches!(result, Err(LexerError::InvalidString(_))));
+        let tokens: Vec<_> = t
+            .lexer_min_len("ab", 2)
+            .unwrap()
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert_eq!(tokens, vec![(&"word", "ab")]);
+        let mut iter = t.lexer_min_len("a", 2).unwrap();
+        assert_eq!(
+            iter.next(),
+            Some(Err(LexerError::UnexpectedEnd { position: 0 }))
+        );
+        // the single-char space is rejected too
+        let tokens: Vec<_> = t.lexer_min_len("ab ba", 2).unwrap().collect();
+        assert_eq!(
+            tokens,
+            vec![
+                Ok((&"word", "ab")),
+                Err(LexerError::UnexpectedEnd { position: 2 })
+            ]
+        );
     }
 
     // ========================================================================
-    // WHITESPACE HANDLING
+    // MATCHED BYTES
     // ========================================================================
 
     #[test]
-    fn lexer_with_whitespace_in_alphabet() {
-        #[derive(Debug, Clone, PartialEq)]
-        enum Kind {
-            Number,
-            Space,
-        }
+    fn matched_byte_count_excludes_errors() {
+        let mut t = digits();
+        t.add("[0123456789]+", 1).unwrap();
+        assert_eq!(t.matched_byte_count("12@34"), 4);
+        assert_eq!(t.matched_byte_count("1234"), 4);
+        assert_eq!(t.matched_byte_count("@@"), 0);
+        assert_eq!(t.matched_byte_count("1é"), 0);
+    }
 
-        let mut t = Table::new("0123456789 ".to_string());
-        t.add("[0123456789]+", Kind::Number).unwrap();
-        t.add(" +", Kind::Space).unwrap();
+    // ========================================================================
+    // TERMINAL HANDLES
+    // ========================================================================
 
-        let tokens: Vec<_> = t
-            .lexer("1 2  3")
-            .unwrap()
-            .collect::<Result<_, _>>()
-            .unwrap();
+    #[test]
+    fn terminal_handles_read_and_update() {
+        let mut t: Table<&str> = Table::new("+-*=".to_string());
+        let add = t.add_returning("+=", "assign").unwrap();
+        let sub = t.add_returning("[-*]=", "assign").unwrap();
+        t.add("=", "eq").unwrap();
+        assert_eq!(t.value_of(&add), Some(&"assign"));
+        assert_eq!(t.value_of(&sub), Some(&"assign"));
+
+        t.set_value_of(&sub, "compound").unwrap();
+        assert_eq!(t.get("-=").unwrap(), Some(&"compound"));
+        assert_eq!(t.get("*=").unwrap(), Some(&"compound"));
+        assert_eq!(t.get("+=").unwrap(), Some(&"assign"));
+        assert_eq!(t.value_of(&sub), Some(&"compound"));
 
-        assert_eq!(tokens.len(), 5);
-        assert_eq!(tokens[0], (&Kind::Number, "1"));
-        assert_eq!(tokens[1], (&Kind::Space, " "));
-        assert_eq!(tokens[2], (&Kind::Number, "2"));
-        assert_eq!(tokens[3], (&Kind::Space, "  "));
-        assert_eq!(tokens[4], (&Kind::Number, "3"));
+        assert!(matches!(
+            t.add_returning("+=", "other"),
+            Err(TableError::ValueAlreadyDefined { .. })
+        ));
     }
 
     #[test]
-    fn lexer_whitespace_not_in_alphabet_immediate_error() {
-        // Errore immediato quando incontra spazio fuori alfabeto
-        #[derive(Debug, Clone, PartialEq)]
-        enum Kind {
-            Number,
-        }
+    fn terminal_handles_go_stale() {
+        let mut t: Table<&str> = Table::new("+-*=".to_string());
+        let add = t.add_returning("+=", "assign").unwrap();
+        t.add("-=", "sub").unwrap();
+        t.retain_keys(|key| key != "-=");
+        assert_eq!(t.prune_empty(), 2);
+        assert_eq!(t.value_of(&add), None);
+        assert_eq!(t.set_value_of(&add, "x"), Err(TableError::StaleHandle));
+        assert_eq!(t.get("+=").unwrap(), Some(&"assign"));
+
+        // nothing removed, nothing renumbered
+        let add = t.add_returning("*=", "assign").unwrap();
+        assert_eq!(t.prune_empty(), 0);
+        assert_eq!(t.value_of(&add), Some(&"assign"));
+
+        let snapshot = t.snapshot();
+        t.set_value_of(&add, "x").unwrap();
+        t.restore(snapshot);
+        assert_eq!(t.value_of(&add), None);
+        assert_eq!(t.set_value_of(&add, "x"), Err(TableError::StaleHandle));
+        assert_eq!(t.get("*=").unwrap(), Some(&"assign"));
+
+        // a failed add keeps the handles
+        let add = t.add_returning("-=", "sub").unwrap();
+        assert!(t.add_or_replace("[+-]=", "y").is_err());
+        assert_eq!(t.value_of(&add), Some(&"sub"));
+    }
 
-        let mut t = Table::new("0123456789".to_string());
-        t.add("[0123456789]+", Kind::Number).unwrap();
+    // ========================================================================
+    // ZERO OR MORE
+    // ========================================================================
 
-        let mut iter = t.lexer("1 2").unwrap();
+    #[test]
+    fn star_then_literal() {
+        let mut t = alpha();
+        t.add("a*b", "ab").unwrap();
+        assert_eq!(t.get("b").unwrap(), Some(&"ab"));
+        assert_eq!(t.get("ab").unwrap(), Some(&"ab"));
+        assert_eq!(t.get("aaab").unwrap(), Some(&"ab"));
+        assert_eq!(t.get("a").unwrap(), None);
+        assert_eq!(t.get("ba").unwrap(), None);
+    }
 
-        // Errore immediato, non restituisce "1" prima
-        let err = iter.next().unwrap().unwrap_err();
+    #[test]
+    fn star_class_and_alone() {
+        let mut t: Table<&str> = Table::new("abc".to_string());
+        t.add("c[ab]*", "cab").unwrap();
+        assert_eq!(t.get("c").unwrap(), Some(&"cab"));
+        assert_eq!(t.get("cabba").unwrap(), Some(&"cab"));
+        assert_eq!(t.get("cc").unwrap(), None);
+
+        let mut t: Table<&str> = Table::new("a".to_string());
+        t.add("a*", "as").unwrap();
+        assert_eq!(t.get("").unwrap(), Some(&"as"));
+        assert_eq!(t.get("aaaa").unwrap(), Some(&"as"));
+    }
+
+    #[test]
+    fn star_empty_match_does_not_loop_lexer() {
+        let mut t: Table<&str> = Table::new("ab".to_string());
+        t.add("a*", "as").unwrap();
+        t.add("b", "b").unwrap();
+        let tokens: Vec<_> = t.lexer("aabaa").unwrap().collect();
         assert_eq!(
-            err,
-            LexerError::UnknownChar {
-                char: ' ',
-                position: 1
-            }
+            tokens,
+            vec![Ok((&"as", "aa")), Ok((&"b", "b")), Ok((&"as", "aa"))]
         );
+        // the empty match is never a token
+        let mut t: Table<&str> = Table::new("ab".to_string());
+        t.add("a*", "as").unwrap();
+        let tokens: Vec<_> = t.lexer("b").unwrap().collect();
+        assert_eq!(tokens, vec![Err(LexerError::UnexpectedEnd { position: 0 })]);
     }
 
     // ========================================================================
-    // COMPLEX PATTERNS
+    // RETAIN KEYS
     // ========================================================================
 
     #[test]
-    fn lexer_identifier_and_numbers_separate_alphabets() {
-        // Usiamo alfabeti non sovrapposti per evitare ambiguità
-        #[derive(Debug, Clone, PartialEq)]
-        enum Kind {
-            Ident,
-            Number,
-            Eq,
-        }
-
-        let mut t = Table::new("abcxyz0123456789=".to_string());
-        t.add("[abcxyz]+", Kind::Ident).unwrap();
-        t.add("[0123456789]+", Kind::Number).unwrap();
-        t.add("=", Kind::Eq).unwrap();
-
-        let tokens: Vec<_> = t.lexer("x=42").unwrap().collect::<Result<_, _>>().unwrap();
+    fn retain_keys_longer_than_two() {
+        let mut t = alpha();
+        t.add("a", "a").unwrap();
+        t.add("ab", "ab").unwrap();
+        t.add("abc", "abc").unwrap();
+        t.add("xyzw", "xyzw").unwrap();
+        t.retain_keys(|key| key.len() > 2);
 
-        assert_eq!(tokens.len(), 3);
-        assert_eq!(tokens[0], (&Kind::Ident, "x"));
-        assert_eq!(tokens[1], (&Kind::Eq, "="));
-        assert_eq!(tokens[2], (&Kind::Number, "42"));
+        assert_eq!(t.get("a").unwrap(), None);
+        assert_eq!(t.get("ab").unwrap(), None);
+        assert_eq!(t.get("abc").unwrap(), Some(&"abc"));
+        assert_eq!(t.get("xyzw").unwrap(), Some(&"xyzw"));
+        assert_eq!(t.keys().collect::<Vec<_>>(), vec!["abc", "xyzw"]);
     }
 
-    #[test]
-    fn lexer_multiple_operators() {
-        #[derive(Debug, Clone, PartialEq)]
-        enum Kind {
-            Lt,
-            Le,
-            Gt,
-            Ge,
-            Eq,
-            EqEq,
-            Not,
-            Ne,
-        }
+    // ========================================================================
+    // OPTIONAL
+    // ========================================================================
 
-        let mut t = Table::new("<=!>".to_string());
-        t.add("<", Kind::Lt).unwrap();
-        t.add("<=", Kind::Le).unwrap();
-        t.add(">", Kind::Gt).unwrap();
-        t.add(">=", Kind::Ge).unwrap();
-        t.add("=", Kind::Eq).unwrap();
-        t.add("==", Kind::EqEq).unwrap();
-        t.add("!", Kind::Not).unwrap();
-        t.add("!=", Kind::Ne).unwrap();
+    #[test]
+    fn optional_alone_and_before_literal() {
+        let mut t: Table<&str> = Table::new("ab".to_string());
+        t.add("a?", "maybe a").unwrap();
+        assert_eq!(t.get("").unwrap(), Some(&"maybe a"));
+        assert_eq!(t.get("a").unwrap(), Some(&"maybe a"));
+        assert_eq!(t.get("aa").unwrap(), None);
 
-        let tokens: Vec<_> = t
-            .lexer("<=>=!===!=")
-            .unwrap()
-            .collect::<Result<_, _>>()
-            .unwrap();
+        let mut t: Table<&str> = Table::new("ab".to_string());
+        t.add("a?b", "ab").unwrap();
+        assert_eq!(t.get("b").unwrap(), Some(&"ab"));
+        assert_eq!(t.get("ab").unwrap(), Some(&"ab"));
+        assert_eq!(t.get("aab").unwrap(), None);
+    }
 
-        assert_eq!(tokens.len(), 5);
-        assert_eq!(tokens[0], (&Kind::Le, "<="));
-        assert_eq!(tokens[1], (&Kind::Ge, ">="));
-        assert_eq!(tokens[2], (&Kind::Ne, "!="));
-        assert_eq!(tokens[3], (&Kind::EqEq, "=="));
-        assert_eq!(tokens[4], (&Kind::Ne, "!="));
+    #[test]
+    fn optional_sign_number() {
+        let mut t: Table<&str> = Table::new("-0123456789 ".to_string());
+        t.add("-?[0123456789]+", "int").unwrap();
+        t.add(" ", "space").unwrap();
+        let tokens: Vec<_> = t.lexer("-12 7").unwrap().collect::<Result<_, _>>().unwrap();
+        assert_eq!(
+            tokens,
+            vec![(&"int", "-12"), (&"space", " "), (&"int", "7")]
+        );
+        assert_eq!(t.get("-").unwrap(), None);
+        assert_eq!(t.get("--1").unwrap(), None);
     }
 
     #[test]
-    fn lexer_hex_numbers() {
-        #[derive(Debug, Clone, PartialEq)]
-        enum Kind {
-            Hex,
-        }
+    fn optional_at_start_is_literal() {
+        let mut t: Table<&str> = Table::new("?a".to_string());
+        t.add("?a", "q").unwrap();
+        assert_eq!(t.get("?a").unwrap(), Some(&"q"));
+        assert_eq!(t.get("a").unwrap(), None);
+    }
 
-        let mut t = Table::new("0123456789abcdef".to_string());
-        t.add("[0123456789abcdef]+", Kind::Hex).unwrap();
+    #[test]
+    fn optional_long_chain_stays_linear() {
+        // without deduplication each `a?` doubled the nodes walked next
+        let mut t: Table<&str> = Table::new("ab".to_string());
+        t.add(&format!("{}b", "a?".repeat(200)), "chain").unwrap();
+        assert_eq!(t.node_count(), 402);
+        assert_eq!(t.get("b").unwrap(), Some(&"chain"));
+        assert_eq!(
+            t.get(&format!("{}b", "a".repeat(200))).unwrap(),
+            Some(&"chain")
+        );
+        assert_eq!(t.get(&format!("{}b", "a".repeat(201))).unwrap(), None);
+    }
 
-        let tokens: Vec<_> = t
-            .lexer("deadbeef")
-            .unwrap()
-            .collect::<Result<_, _>>()
-            .unwrap();
+    // ========================================================================
+    // DUMP KEYS
+    // ========================================================================
 
-        assert_eq!(tokens.len(), 1);
-        assert_eq!(tokens[0], (&Kind::Hex, "deadbeef"));
+    #[test]
+    fn dump_keys_sorted_lines() {
+        let mut t = alpha();
+        t.add("zeta", "z").unwrap();
+        t.add("alpha", "a").unwrap();
+        t.add("mu+", "m").unwrap();
+        assert_eq!(t.dump_keys(), "alpha\nmu\nzeta");
+        assert_eq!(alpha().dump_keys(), "");
     }
 
     // ========================================================================
-    // EDGE CASES
+    // CLASS RANGES
     // ========================================================================
 
     #[test]
-    fn lexer_single_char_tokens() {
-        #[derive(Debug, Clone, PartialEq)]
-        enum Kind {
-            A,
-        }
-
-        let mut t = Table::new("a".to_string());
-        t.add("a", Kind::A).unwrap();
+    fn range_letters() {
+        let mut t: Table<&str> = Table::new("abcdx".to_string());
+        t.add("[a-c]+", "abc").unwrap();
+        assert_eq!(t.get("cab").unwrap(), Some(&"abc"));
+        assert_eq!(t.get("d").unwrap(), None);
+        assert_eq!(t.get("ax").unwrap(), None);
+    }
 
-        let tokens: Vec<_> = t.lexer("aaa").unwrap().collect::<Result<_, _>>().unwrap();
+    #[test]
+    fn range_digits() {
+        let mut t: Table<&str> = Table::new("0123456789+-".to_string());
+        t.add("[0-9]+", "num").unwrap();
+        t.add("[-+]", "sign").unwrap();
+        assert_eq!(t.get("2026").unwrap(), Some(&"num"));
+        assert_eq!(t.get("+").unwrap(), Some(&"sign"));
+    }
 
-        assert_eq!(tokens.len(), 3);
-        assert_eq!(tokens[0], (&Kind::A, "a"));
-        assert_eq!(tokens[1], (&Kind::A, "a"));
-        assert_eq!(tokens[2], (&Kind::A, "a"));
+    #[test]
+    fn range_invalid() {
+        let mut t: Table<&str> = Table::new("abc".to_string());
+        assert_eq!(t.add("[c-a]", "rev"), Err(TableError::InvalidRange));
+        assert_eq!(t.add("[a-z]", "out"), Err(TableError::InvalidRange));
+        assert_eq!(t.add("[a-c]", "ok"), Ok(()));
     }
 
     #[test]
-    fn lexer_very_long_token() {
-        #[derive(Debug, Clone, PartialEq)]
-        enum Kind {
-            A,
-        }
+    fn range_lone_hyphen() {
+        let mut t: Table<&str> = Table::new("-a".to_string());
+        t.add("[-]", "dash").unwrap();
+        t.add("[a-]a", "pair").unwrap();
+        assert_eq!(t.get("-").unwrap(), Some(&"dash"));
+        assert_eq!(t.get("-a").unwrap(), Some(&"pair"));
+        assert_eq!(t.get("aa").unwrap(), Some(&"pair"));
+    }
 
-        let mut t = Table::new("a".to_string());
-        t.add("a+", Kind::A).unwrap();
+    // ========================================================================
+    // REPETITION MODE
+    // ========================================================================
 
-        let long_input = "a".repeat(10000);
-        let tokens: Vec<_> = t
-            .lexer(&long_input)
-            .unwrap()
-            .collect::<Result<_, _>>()
-            .unwrap();
+    #[test]
+    fn repetition_absorb_accepts_single() {
+        let mut t: Table<&str> =
+            Table::new_with_repetition_mode("a".to_string(), RepetitionMode::Absorb);
+        t.add("a+a", "value").unwrap();
+        assert_eq!(t.get("a").unwrap(), Some(&"value"));
+        assert_eq!(t.get("aa").unwrap(), Some(&"value"));
+    }
 
-        assert_eq!(tokens.len(), 1);
-        assert_eq!(tokens[0].0, &Kind::A);
-        assert_eq!(tokens[0].1.len(), 10000);
+    #[test]
+    fn repetition_strict_requires_trailing_char() {
+        let mut t: Table<&str> =
+            Table::new_with_repetition_mode("a".to_string(), RepetitionMode::Strict);
+        t.add("a+a", "value").unwrap();
+        assert_eq!(t.get("a").unwrap(), None);
+        assert_eq!(t.get("aa").unwrap(), Some(&"value"));
+        assert_eq!(t.get("aaaa").unwrap(), Some(&"value"));
     }
 
     #[test]
-    fn lexer_alternating_tokens() {
-        #[derive(Debug, Clone, PartialEq)]
-        enum Kind {
-            A,
-            B,
-        }
+    fn repetition_strict_chained_and_classes() {
+        let mut t: Table<&str> =
+            Table::new_with_repetition_mode("ab".to_string(), RepetitionMode::Strict);
+        t.add("[ab]+[ba]", "x").unwrap();
+        assert_eq!(t.get("a").unwrap(), None);
+        assert_eq!(t.get("ab").unwrap(), Some(&"x"));
 
-        let mut t = Table::new("ab".to_string());
-        t.add("a+", Kind::A).unwrap();
-        t.add("b+", Kind::B).unwrap();
+        let mut t: Table<&str> =
+            Table::new_with_repetition_mode("a".to_string(), RepetitionMode::Strict);
+        t.add("a*aa", "y").unwrap();
+        assert_eq!(t.get("a").unwrap(), None);
+        assert_eq!(t.get("aa").unwrap(), Some(&"y"));
+        assert_eq!(t.get("aaa").unwrap(), Some(&"y"));
+    }
 
-        let tokens: Vec<_> = t
-            .lexer("aaabbbaaab")
-            .unwrap()
-            .collect::<Result<_, _>>()
-            .unwrap();
+    // ========================================================================
+    // NEGATED CLASSES
+    // ========================================================================
 
-        assert_eq!(tokens.len(), 4);
-        assert_eq!(tokens[0], (&Kind::A, "aaa"));
-        assert_eq!(tokens[1], (&Kind::B, "bbb"));
-        assert_eq!(tokens[2], (&Kind::A, "aaa"));
-        assert_eq!(tokens[3], (&Kind::B, "b"));
+    #[test]
+    fn negated_single_char() {
+        let mut t: Table<&str> = Table::new("abc".to_string());
+        t.add("[^a]", "not a").unwrap();
+        assert_eq!(t.get("b").unwrap(), Some(&"not a"));
+        assert_eq!(t.get("c").unwrap(), Some(&"not a"));
+        assert_eq!(t.get("a").unwrap(), None);
     }
 
     #[test]
-    fn lexer_partial_match_at_end() {
-        #[derive(Debug, Clone, PartialEq)]
-        enum Kind {
-            Abc,
-        }
-
-        let mut t = Table::new("abc".to_string());
-        t.add("abc", Kind::Abc).unwrap();
+    fn negated_repeated_class_lexes() {
+        let mut t: Table<&str> = Table::new("abcd".to_string());
+        t.add("[^ab]+", "other").unwrap();
+        t.add("[ab]", "ab").unwrap();
+        let tokens: Vec<_> = t.lexer("cdadc").unwrap().collect::<Result<_, _>>().unwrap();
+        assert_eq!(
+            tokens,
+            vec![(&"other", "cd"), (&"ab", "a"), (&"other", "dc")]
+        );
+    }
 
-        // "abcab" - first "abc" matches, then "ab" is partial with no match
-        let mut iter = t.lexer("abcab").unwrap();
+    #[test]
+    fn negated_everything_is_invalid_range() {
+        let mut t: Table<&str> = Table::new("ab".to_string());
+        assert_eq!(t.add("[^ab]", "none"), Err(TableError::InvalidRange));
+        assert_eq!(t.add("[^[:alpha:]]", "none"), Err(TableError::InvalidRange));
+    }
 
-        let (kind, content) = iter.next().unwrap().unwrap();
-        assert_eq!(kind, &Kind::Abc);
-        assert_eq!(content, "abc");
+    #[test]
+    fn caret_not_first_is_literal() {
+        let mut t: Table<&str> = Table::new("a^".to_string());
+        t.add("[a^]", "member").unwrap();
+        assert_eq!(t.get("^").unwrap(), Some(&"member"));
+        assert_eq!(t.get("a").unwrap(), Some(&"member"));
 
-        // "ab" has no complete match
-        let err = iter.next().unwrap().unwrap_err();
-        assert_eq!(err, LexerError::UnexpectedEnd { position: 3 });
+        let mut t: Table<&str> = Table::new("ab".to_string());
+        assert_eq!(t.add("[b^]", "x"), Err(TableError::InvalidInput('^')));
     }
 
+    // ========================================================================
+    // ALPHABET DIFF
+    // ========================================================================
+
     #[test]
-    fn lexer_empty_table() {
-        #[derive(Debug, Clone, PartialEq)]
-        enum Kind {}
+    fn alphabet_diff_both_sides() {
+        let a: Table<&str> = Table::new("abc".to_string());
+        let b: Table<&str> = Table::new("bcd".to_string());
+        assert_eq!(alphabet_diff(&a, &b), (vec!['a'], vec!['d']));
+        assert_eq!(alphabet_diff(&a, &a), (vec![], vec![]));
+    }
 
-        let t: Table<Kind> = Table::new("abc".to_string());
+    #[test]
+    fn alphabet_diff_explains_mismatch() {
+        let a: Table<&str> = Table::new("abab".to_string());
+        let b: Table<&str> = Table::new("b".to_string());
+        assert_eq!(a.is_disjoint(&b), Err(TableError::AlphabetMismatch));
+        assert_eq!(alphabet_diff(&a, &b), (vec!['a'], vec![]));
+    }
 
-        let mut iter = t.lexer("abc").unwrap();
+    // ========================================================================
+    // BOUNDED REPETITION
+    // ========================================================================
 
-        // No patterns defined, should fail immediately
-        let err = iter.next().unwrap().unwrap_err();
-        assert_eq!(err, LexerError::UnexpectedEnd { position: 0 });
+    #[test]
+    fn bounded_exact() {
+        let mut t: Table<&str> = Table::new("a".to_string());
+        t.add("a{3}", "three").unwrap();
+        assert_eq!(t.get("aa").unwrap(), None);
+        assert_eq!(t.get("aaa").unwrap(), Some(&"three"));
+        assert_eq!(t.get("aaaa").unwrap(), None);
     }
 
     #[test]
-    fn lexer_collect_result_success() {
-        #[derive(Debug, Clone, PartialEq)]
-        enum Kind {
-            Num,
-            Op,
-        }
+    fn bounded_min_max() {
+        let mut t: Table<&str> = Table::new("a".to_string());
+        t.add("a{2,4}", "some").unwrap();
+        assert_eq!(t.get("a").unwrap(), None);
+        assert_eq!(t.get("aa").unwrap(), Some(&"some"));
+        assert_eq!(t.get("aaa").unwrap(), Some(&"some"));
+        assert_eq!(t.get("aaaa").unwrap(), Some(&"some"));
+        assert_eq!(t.get("aaaaa").unwrap(), None);
+    }
 
-        let mut t = Table::new("0123456789+".to_string());
-        t.add("[0123456789]+", Kind::Num).unwrap();
-        t.add("+", Kind::Op).unwrap();
+    #[test]
+    fn bounded_open_ended() {
+        let mut t: Table<&str> = Table::new("a".to_string());
+        t.add("a{2,}", "many").unwrap();
+        assert_eq!(t.get("a").unwrap(), None);
+        assert_eq!(t.get("aa").unwrap(), Some(&"many"));
+        assert_eq!(t.get("aaaaaa").unwrap(), Some(&"many"));
+    }
 
-        let result: Result<Vec<_>, _> = t.lexer("1+2").unwrap().collect();
-        assert!(result.is_ok());
-        assert_eq!(result.unwrap().len(), 3);
+    #[test]
+    fn bounded_class_lexes_fixed_width() {
+        let mut t: Table<&str> = Table::new("0123456789-".to_string());
+        t.add("[0-9]{4}-[0-9]{2}", "date").unwrap();
+        t.add("[0-9]", "digit").unwrap();
+        let tokens: Vec<_> = t
+            .lexer("2026-105")
+            .unwrap()
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert_eq!(tokens, vec![(&"date", "2026-10"), (&"digit", "5")]);
     }
 
     #[test]
-    fn lexer_collect_result_failure() {
-        #[derive(Debug, Clone, PartialEq)]
-        enum Kind {
-            Num,
-            Op,
-        }
+    fn bounded_malformed_and_literal_brace() {
+        let mut t: Table<&str> = Table::new("a{}".to_string());
+        assert_eq!(t.add("a{2,1}", "x"), Err(TableError::InvalidRange));
+        // counts are capped, the atom is unrolled
+        assert_eq!(t.add("a{100000000}", "x"), Err(TableError::InvalidRange));
+        assert_eq!(t.node_count(), 1);
+        // without a closing brace, `{` is a literal
+        t.add("a{", "open").unwrap();
+        assert_eq!(t.get("a{").unwrap(), Some(&"open"));
+        // and so is one that doesn't hold numeric bounds
+        t.add("a{}", "empty").unwrap();
+        assert_eq!(t.get("a{}").unwrap(), Some(&"empty"));
+        let mut t: Table<&str> = Table::new("ab{}".to_string());
+        t.add("a{b}", "braces").unwrap();
+        assert_eq!(t.get("a{b}").unwrap(), Some(&"braces"));
+
+        let mut t: Table<&str> = Table::new_with_literals("a{}".to_string(), &['{']);
+        t.add("a{}", "braces").unwrap();
+        assert_eq!(t.get("a{}").unwrap(), Some(&"braces"));
+        assert!(t.metachar_conflicts().is_empty());
+    }
 
-        let mut t = Table::new("0123456789+".to_string());
-        t.add("[0123456789]+", Kind::Num).unwrap();
-        t.add("+", Kind::Op).unwrap();
+    #[test]
+    fn bounded_work_is_capped() {
+        let mut t: Table<&str> = Table::new("0123456789ab".to_string());
+        // fails before building the 10^8 paths
+        assert_eq!(
+            t.add("[0-9]{8}", "x"),
+            Err(TableError::PatternTooLarge {
+                limit: MAX_PATTERN_NODES
+            })
+        );
+        assert!(matches!(
+            t.add("[ab]{50}", "x"),
+            Err(TableError::PatternTooLarge { .. })
+        ));
+        // the partial paths are undone
+        assert_eq!(t.node_count(), 1);
 
-        let result: Result<Vec<_>, _> = t.lexer("1@2").unwrap().collect();
-        assert!(result.is_err());
+        t.add("[0-9]{4}", "year").unwrap();
+        t.add("a{0,1000}", "as").unwrap();
+        assert_eq!(t.get("2026").unwrap(), Some(&"year"));
+        assert_eq!(t.get(&"a".repeat(1000)).unwrap(), Some(&"as"));
     }
 
+    // ========================================================================
+    // GROUP REPETITION
+    // ========================================================================
+
     #[test]
-    fn lexer_only_repetition() {
-        #[derive(Debug, Clone, PartialEq)]
-        enum Kind {
-            Stars,
+    fn group_comma_list() {
+        let mut t: Table<&str> = Table::new("0123456789, ".to_string());
+        t.add("[0-9]+(,[0-9]+)*", "list").unwrap();
+        t.add(" ", "space").unwrap();
+        for list in ["1", "1,2", "1,2,3", "12,345,6,78"] {
+            assert_eq!(t.get(list).unwrap(), Some(&"list"), "{list}");
         }
+        assert_eq!(t.get("1,").unwrap(), None);
+        assert_eq!(t.get("1,2,").unwrap(), None);
+        assert_eq!(t.get(",1").unwrap(), None);
+        assert_eq!(t.get("1,,2").unwrap(), None);
 
-        let mut t = Table::new("*".to_string());
-        t.add("*+", Kind::Stars).unwrap();
-
-        let tokens: Vec<_> = t.lexer("****").unwrap().collect::<Result<_, _>>().unwrap();
+        let tokens: Vec<_> = t
+            .lexer("1,2,3 45")
+            .unwrap()
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert_eq!(
+            tokens,
+            vec![(&"list", "1,2,3"), (&"space", " "), (&"list", "45")]
+        );
+    }
 
-        assert_eq!(tokens.len(), 1);
-        assert_eq!(tokens[0], (&Kind::Stars, "****"));
+    #[test]
+    fn group_plus_optional_and_bounds() {
+        let mut t: Table<&str> = Table::new("abc".to_string());
+        t.add("(ab)+", "abs").unwrap();
+        t.add("c(ab)?c", "cc").unwrap();
+        t.add("a(bc){2}", "two").unwrap();
+        assert_eq!(t.get("ab").unwrap(), Some(&"abs"));
+        assert_eq!(t.get("ababab").unwrap(), Some(&"abs"));
+        assert_eq!(t.get("aba").unwrap(), None);
+        assert_eq!(t.get("cc").unwrap(), Some(&"cc"));
+        assert_eq!(t.get("cabc").unwrap(), Some(&"cc"));
+        assert_eq!(t.get("cababc").unwrap(), None);
+        assert_eq!(t.get("abcbc").unwrap(), Some(&"two"));
+        assert_eq!(t.get("abc").unwrap(), None);
     }
 
     #[test]
-    fn lexer_mixed_fixed_and_repetition() {
-        #[derive(Debug, Clone, PartialEq)]
-        enum Kind {
-            Prefix,
-            Suffix,
-        }
+    fn group_without_repetition_is_literal() {
+        let mut t: Table<&str> = Table::new("()ab".to_string());
+        t.add("(ab)", "parens").unwrap();
+        t.add("(a", "open").unwrap();
+        assert_eq!(t.get("(ab)").unwrap(), Some(&"parens"));
+        assert_eq!(t.get("ab").unwrap(), None);
+        assert_eq!(t.get("(a").unwrap(), Some(&"open"));
+    }
 
-        let mut t = Table::new("ab".to_string());
-        t.add("a+b", Kind::Prefix).unwrap(); // one or more 'a' followed by 'b'
+    // ========================================================================
+    // TOKEN AT
+    // ========================================================================
 
-        let tokens: Vec<_> = t.lexer("aaab").unwrap().collect::<Result<_, _>>().unwrap();
-        assert_eq!(tokens.len(), 1);
-        assert_eq!(tokens[0], (&Kind::Prefix, "aaab"));
+    #[test]
+    fn token_at_finds_containing_token() {
+        let mut t: Table<&str> = Table::new("0123456789+".to_string());
+        t.add("[0-9]+", "num").unwrap();
+        t.add("+", "plus").unwrap();
+        assert_eq!(t.token_at("12+34", 3), Ok(Some((&"num", 3..5))));
+        assert_eq!(t.token_at("12+34", 2), Ok(Some((&"plus", 2..3))));
+        assert_eq!(t.token_at("12+34", 0), Ok(Some((&"num", 0..2))));
+        assert_eq!(t.token_at("12+34", 5), Ok(None));
+    }
 
-        let tokens: Vec<_> = t.lexer("ab").unwrap().collect::<Result<_, _>>().unwrap();
-        assert_eq!(tokens.len(), 1);
-        assert_eq!(tokens[0], (&Kind::Prefix, "ab"));
+    #[test]
+    fn token_at_error_and_skipped_regions() {
+        let mut t: Table<&str> = Table::new("0123456789+ ".to_string());
+        t.add("[0-9]+", "num").unwrap();
+        t.add_skip(" +").unwrap();
+        assert_eq!(t.token_at("1  2", 1), Ok(None));
+        assert_eq!(t.token_at("1  2", 3), Ok(Some((&"num", 3..4))));
+        // the lexer stops at the stray `+`
+        assert_eq!(t.token_at("1+2", 0), Ok(Some((&"num", 0..1))));
+        assert_eq!(t.token_at("1+2", 2), Ok(None));
+        assert!(t.token_at("1é", 0).is_err());
     }
 
     // ========================================================================
-    // REAL-WORLD SCENARIOS
+    // ALTERNATION
     // ========================================================================
 
     #[test]
-    fn lexer_arithmetic_expression() {
-        #[derive(Debug, Clone, PartialEq)]
-        enum Kind {
-            Num,
-            Add,
-            Sub,
-            Mul,
-            Div,
-            LParen,
-            RParen,
-        }
-
-        let mut t = Table::new("0123456789+-*/()".to_string());
-        t.add("[0123456789]+", Kind::Num).unwrap();
-        t.add("+", Kind::Add).unwrap();
-        t.add("-", Kind::Sub).unwrap();
-        t.add("*", Kind::Mul).unwrap();
-        t.add("/", Kind::Div).unwrap();
-        t.add("(", Kind::LParen).unwrap();
-        t.add(")", Kind::RParen).unwrap();
+    fn alternation_http_methods() {
+        let mut t = alpha();
+        t.add("get|put|post|delete", "METHOD").unwrap();
 
-        let tokens: Vec<_> = t
-            .lexer("(1+2)*3")
-            .unwrap()
-            .collect::<Result<_, _>>()
-            .unwrap();
+        assert_eq!(t.get("get").unwrap(), Some(&"METHOD"));
+        assert_eq!(t.get("put").unwrap(), Some(&"METHOD"));
+        assert_eq!(t.get("post").unwrap(), Some(&"METHOD"));
+        assert_eq!(t.get("delete").unwrap(), Some(&"METHOD"));
+        assert_eq!(t.get("patch").unwrap(), None);
+        assert_eq!(t.get("getput").unwrap(), None);
+    }
 
-        assert_eq!(tokens.len(), 7);
-        assert_eq!(tokens[0], (&Kind::LParen, "("));
-        assert_eq!(tokens[1], (&Kind::Num, "1"));
-        assert_eq!(tokens[2], (&Kind::Add, "+"));
-        assert_eq!(tokens[3], (&Kind::Num, "2"));
-        assert_eq!(tokens[4], (&Kind::RParen, ")"));
-        assert_eq!(tokens[5], (&Kind::Mul, "*"));
-        assert_eq!(tokens[6], (&Kind::Num, "3"));
+    #[test]
+    fn alternation_converging_alternatives() {
+        let mut t = alpha();
+        t.add("ab|a[bc]|[ab]+", "x").unwrap();
+        assert_eq!(t.get("ab").unwrap(), Some(&"x"));
+        assert_eq!(t.get("ac").unwrap(), Some(&"x"));
+        assert_eq!(t.get("bbb").unwrap(), Some(&"x"));
+        // still a conflict with another value
+        assert!(matches!(
+            t.add("ab|xy", "y"),
+            Err(TableError::ValueAlreadyDefined { .. })
+        ));
     }
 
     #[test]
-    fn lexer_complex_arithmetic() {
-        #[derive(Debug, Clone, PartialEq)]
-        enum Kind {
-            Num,
-            Add,
-            Sub,
-            Mul,
-            Div,
-            LParen,
-            RParen,
-        }
+    fn alternation_pipe_literal() {
+        let mut t: Table<&str> = Table::new("ab|".to_string());
+        t.add("[a|]|b", "x").unwrap();
+        assert_eq!(t.get("|").unwrap(), Some(&"x"));
+        assert_eq!(t.get("b").unwrap(), Some(&"x"));
+        assert_eq!(t.get("a|").unwrap(), None);
+
+        let mut t: Table<&str> = Table::new_with_literals("ab|".to_string(), &['|']);
+        t.add("a|b", "pipe").unwrap();
+        assert_eq!(t.get("a|b").unwrap(), Some(&"pipe"));
+        assert_eq!(t.get("a").unwrap(), None);
+    }
 
-        let mut t = Table::new("0123456789+-*/()".to_string());
-        t.add("[0123456789]+", Kind::Num).unwrap();
-        t.add("+", Kind::Add).unwrap();
-        t.add("-", Kind::Sub).unwrap();
-        t.add("*", Kind::Mul).unwrap();
-        t.add("/", Kind::Div).unwrap();
-        t.add("(", Kind::LParen).unwrap();
-        t.add(")", Kind::RParen).unwrap();
+    // ========================================================================
+    // WILDCARD
+    // ========================================================================
 
-        let tokens: Vec<_> = t
-            .lexer("((10+20)*(30-5))/2")
-            .unwrap()
-            .collect::<Result<_, _>>()
-            .unwrap();
+    #[test]
+    fn wildcard_matches_any_alphabet_char() {
+        let mut t: Table<&str> = Table::new_with_wildcard("abc".to_string());
+        t.add("a.c", "x").unwrap();
+        assert_eq!(t.get("aac").unwrap(), Some(&"x"));
+        assert_eq!(t.get("abc").unwrap(), Some(&"x"));
+        assert_eq!(t.get("acc").unwrap(), Some(&"x"));
+        assert_eq!(t.get("ac").unwrap(), None);
+    }
 
-        assert_eq!(tokens.len(), 15);
-        assert_eq!(tokens[0], (&Kind::LParen, "("));
-        assert_eq!(tokens[1], (&Kind::LParen, "("));
-        assert_eq!(tokens[2], (&Kind::Num, "10"));
-        assert_eq!(tokens[3], (&Kind::Add, "+"));
-        assert_eq!(tokens[4], (&Kind::Num, "20"));
-        assert_eq!(tokens[5], (&Kind::RParen, ")"));
-        assert_eq!(tokens[6], (&Kind::Mul, "*"));
-        assert_eq!(tokens[7], (&Kind::LParen, "("));
-        assert_eq!(tokens[8], (&Kind::Num, "30"));
-        assert_eq!(tokens[9], (&Kind::Sub, "-"));
-        assert_eq!(tokens[10], (&Kind::Num, "5"));
-        assert_eq!(tokens[11], (&Kind::RParen, ")"));
-        assert_eq!(tokens[12], (&Kind::RParen, ")"));
-        assert_eq!(tokens[13], (&Kind::Div, "/"));
-        assert_eq!(tokens[14], (&Kind::Num, "2"));
+    #[test]
+    fn wildcard_plus() {
+        let mut t: Table<&str> = Table::new_with_wildcard("ab".to_string());
+        t.add("a.+", "rest").unwrap();
+        assert_eq!(t.get("a").unwrap(), None);
+        assert_eq!(t.get("ab").unwrap(), Some(&"rest"));
+        assert_eq!(t.get("abba").unwrap(), Some(&"rest"));
     }
 
     #[test]
-    fn lexer_simple_tokens_no_overlap() {
-        // Semplice scenario senza ambiguità
-        #[derive(Debug, Clone, PartialEq)]
-        enum Kind {
-            LBrace,
-            RBrace,
-            Comma,
-            Colon,
-            Num,
-        }
+    fn wildcard_literal_dot() {
+        let mut t: Table<&str> = Table::new_with_wildcard("ab.".to_string());
+        t.add("a[.]", "dot").unwrap();
+        t.add("b.", "any").unwrap();
+        assert_eq!(t.get("a.").unwrap(), Some(&"dot"));
+        assert_eq!(t.get("ab").unwrap(), None);
+        assert_eq!(t.get("b.").unwrap(), Some(&"any"));
+        assert_eq!(t.get("ba").unwrap(), Some(&"any"));
+        assert_eq!(t.metachar_conflicts(), vec!['.']);
 
-        let mut t = Table::new("{},:0123456789".to_string());
-        t.add("{", Kind::LBrace).unwrap();
-        t.add("}", Kind::RBrace).unwrap();
-        t.add(",", Kind::Comma).unwrap();
-        t.add(":", Kind::Colon).unwrap();
-        t.add("[0123456789]+", Kind::Num).unwrap();
+        // without the wildcard, `.` is a plain char
+        let mut t: Table<&str> = Table::new("ab.".to_string());
+        t.add("a.", "dot").unwrap();
+        assert_eq!(t.get("ab").unwrap(), None);
+        assert!(t.metachar_conflicts().is_empty());
+    }
 
-        let tokens: Vec<_> = t
-            .lexer("{1:2,3:4}")
-            .unwrap()
-            .collect::<Result<_, _>>()
-            .unwrap();
+    // ========================================================================
+    // LINT
+    // ========================================================================
 
-        assert_eq!(tokens.len(), 9);
-        assert_eq!(tokens[0], (&Kind::LBrace, "{"));
-        assert_eq!(tokens[1], (&Kind::Num, "1"));
-        assert_eq!(tokens[2], (&Kind::Colon, ":"));
-        assert_eq!(tokens[3], (&Kind::Num, "2"));
-        assert_eq!(tokens[4], (&Kind::Comma, ","));
-        assert_eq!(tokens[5], (&Kind::Num, "3"));
-        assert_eq!(tokens[6], (&Kind::Colon, ":"));
-        assert_eq!(tokens[7], (&Kind::Num, "4"));
-        assert_eq!(tokens[8], (&Kind::RBrace, "}"));
+    #[test]
+    fn lint_reports_every_kind() {
+        let mut t: Table<&str> = Table::new("abczx".to_string());
+        t.add("a", "a").unwrap();
+        t.add("ab", "ab").unwrap();
+        t.add("b", "b").unwrap();
+        t.add("c+", "cs").unwrap();
+        // the empty match of `z*` is never a token
+        t.add("z*", "zs").unwrap();
+        assert_eq!(
+            t.lint(),
+            vec![
+                Lint::Shadowed {
+                    key: Some(String::new())
+                },
+                Lint::Overlap {
+                    short: "a".to_string(),
+                    long: "ab".to_string()
+                },
+                Lint::UnusedChar('x'),
+                Lint::SelfLoop {
+                    key: "c".to_string(),
+                    chars: vec!['c']
+                },
+                Lint::SelfLoop {
+                    key: "z".to_string(),
+                    chars: vec!['z']
+                },
+            ]
+        );
     }
 
     #[test]
-    fn lexer_position_tracking() {
-        // Verifica che le posizioni negli errori siano corrette
-        #[derive(Debug, Clone, PartialEq)]
-        enum Kind {
-            A,
-        }
-
-        let mut t = Table::new("a".to_string());
-        t.add("a", Kind::A).unwrap();
+    fn lint_clean_table() {
+        let mut t: Table<&str> = Table::new("fi".to_string());
+        t.add("if", "if").unwrap();
+        // "iff" can't be split: `f` alone is not a token
+        t.add("iff", "iff").unwrap();
+        assert!(t.lint().is_empty());
+    }
 
-        // Errore a posizione 5
-        let mut iter = t.lexer("aaaaa@").unwrap();
+    // ========================================================================
+    // LEX SPANNED
+    // ========================================================================
 
-        for _ in 0..4 {
-            assert!(iter.next().unwrap().is_ok());
-        }
+    #[test]
+    fn lex_spanned_arithmetic() {
+        let mut t: Table<&str> = Table::new("0123456789+* ".to_string());
+        t.add("[0-9]+", "num").unwrap();
+        t.add("[+]", "plus").unwrap();
+        t.add("[*]", "times").unwrap();
+        t.add_skip(" +").unwrap();
+        assert_eq!(
+            t.lex_spanned("12 + 3*45"),
+            Ok(vec![
+                (&"num", "12", 0..2),
+                (&"plus", "+", 3..4),
+                (&"num", "3", 5..6),
+                (&"times", "*", 6..7),
+                (&"num", "45", 7..9),
+            ])
+        );
+        assert_eq!(t.lex_spanned(""), Ok(vec![]));
+    }
 
-        let err = iter.next().unwrap().unwrap_err();
+    #[test]
+    fn lex_spanned_first_error() {
+        let mut t: Table<&str> = Table::new("0123456789+".to_string());
+        t.add("[0-9]+", "num").unwrap();
+        t.add("+", "plus").unwrap();
         assert_eq!(
-            err,
-            LexerError::UnknownChar {
-                char: '@',
-                position: 5
-            }
+            t.lex_spanned("1+x+2"),
+            Err(LexerError::UnknownChar {
+                char: 'x',
+                position: 2
+            })
         );
+        assert!(matches!(
+            t.lex_spanned("1é"),
+            Err(LexerError::InvalidString(_))
+        ));
     }
 }
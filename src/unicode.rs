@@ -0,0 +1,269 @@
+use crate::error::{LexerError, TableError};
+use std::{
+    collections::{HashMap, HashSet},
+    fmt::Debug,
+};
+
+#[derive(Debug)]
+struct Node<T> {
+    children: HashMap<char, usize>,
+    value: Option<T>,
+}
+
+impl<T: Clone> Node<T> {
+    fn new() -> Self {
+        Self {
+            children: HashMap::new(),
+            value: None,
+        }
+    }
+
+    fn set_value(&mut self, value: T) -> Result<(), TableError<T>> {
+        if let Some(current) = &self.value {
+            return Err(TableError::ValueAlreadyDefined {
+                current: current.clone(),
+                requested: value,
+            });
+        }
+        self.value = Some(value);
+        Ok(())
+    }
+}
+
+#[derive(Debug)]
+pub struct UnicodeTable<T> {
+    alphabet: HashSet<char>,
+    nodes: Vec<Node<T>>,
+}
+
+impl<T: Debug + Clone> UnicodeTable<T> {
+    pub fn new(alphabet: &str) -> Self {
+        Self {
+            alphabet: alphabet.chars().collect(),
+            nodes: vec![Node::new()],
+        }
+    }
+
+    fn check_char(&self, ch: char) -> Result<char, TableError<T>> {
+        if self.alphabet.contains(&ch) {
+            Ok(ch)
+        } else {
+            Err(TableError::InvalidInput(ch))
+        }
+    }
+
+    fn append_node(&mut self, current: usize, ch: char) -> usize {
+        if let Some(next) = self.nodes[current].children.get(&ch) {
+            return *next;
+        }
+        self.nodes.push(Node::new());
+        let new_child = self.nodes.len() - 1;
+        self.nodes[current].children.insert(ch, new_child);
+        new_child
+    }
+
+    pub fn add(&mut self, s: &str, value: T) -> Result<(), TableError<T>> {
+        let mut currents = vec![0];
+        let mut iter = s.chars().peekable();
+        while let Some(ch) = iter.next() {
+            let mut range = vec![];
+            match ch {
+                '[' => {
+                    while let Some(next) = iter.next_if(|n| *n != ']') {
+                        let next = self.check_char(next)?;
+                        if !range.contains(&next) {
+                            range.push(next);
+                        }
+                    }
+                    if Some(']') != iter.next() {
+                        return Err(TableError::UnclosedClass);
+                    }
+                    if range.is_empty() {
+                        return Err(TableError::EmptyClass);
+                    }
+                }
+                _ => range.push(self.check_char(ch)?),
+            }
+            let mut new_currents = Vec::with_capacity(currents.len() * range.len());
+            for current in &currents {
+                for ch in &range {
+                    new_currents.push(self.append_node(*current, *ch));
+                }
+            }
+            currents = new_currents;
+            if iter.next_if_eq(&'+').is_some() {
+                for current in &currents {
+                    for ch in &range {
+                        let children = &mut self.nodes[*current].children;
+                        match children.get(ch) {
+                            Some(existing) if existing != current => {
+                                return Err(TableError::AmbiguousPattern(*ch));
+                            }
+                            _ => {
+                                children.insert(*ch, *current);
+                            }
+                        }
+                    }
+                }
+            }
+        }
+        currents.sort_unstable();
+        currents.dedup();
+        for current in currents {
+            self.nodes[current].set_value(value.clone())?;
+        }
+        Ok(())
+    }
+
+    pub fn get(&self, s: &str) -> Result<Option<&T>, TableError<T>> {
+        let mut current = 0;
+        for ch in s.chars() {
+            let ch = self.check_char(ch)?;
+            match self.nodes[current].children.get(&ch) {
+                Some(next) => current = *next,
+                None => return Ok(None),
+            }
+        }
+        Ok(self.nodes[current].value.as_ref())
+    }
+
+    pub fn lexer<'a>(&'a self, s: &'a str) -> UnicodeIterator<'a, T> {
+        UnicodeIterator {
+            table: self,
+            input: s,
+            index: 0,
+        }
+    }
+}
+
+pub struct UnicodeIterator<'a, T> {
+    table: &'a UnicodeTable<T>,
+    input: &'a str,
+    index: usize,
+}
+
+impl<'a, T: Debug + Clone> UnicodeIterator<'a, T> {
+    fn next_token(&self) -> Result<(&'a T, usize), LexerError> {
+        let mut node_id = 0;
+        let mut last_match = None;
+        for (offset, ch) in self.input[self.index..].char_indices() {
+            let position = self.index + offset;
+            if !self.table.alphabet.contains(&ch) {
+                return Err(LexerError::UnknownChar { char: ch, position });
+            }
+            match self.table.nodes[node_id].children.get(&ch) {
+                Some(&next) => {
+                    if let Some(value) = &self.table.nodes[next].value {
+                        last_match = Some((value, position + ch.len_utf8()));
+                    }
+                    node_id = next;
+                }
+                None => break,
+            }
+        }
+        last_match.ok_or(LexerError::UnexpectedEnd {
+            position: self.index,
+        })
+    }
+}
+
+impl<'a, T: Debug + Clone> Iterator for UnicodeIterator<'a, T> {
+    type Item = Result<(&'a T, &'a str), LexerError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.index >= self.input.len() {
+            return None;
+        }
+        match self.next_token() {
+            Ok((value, end)) => {
+                let content = &self.input[self.index..end];
+                self.index = end;
+                Some(Ok((value, content)))
+            }
+            Err(e) => {
+                self.index = self.input.len();
+                Some(Err(e))
+            }
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (0, Some(self.input.len() - self.index))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn unicode_literal_and_class() {
+        let mut t = UnicodeTable::new("àèéìòù😀abc");
+        t.add("[àèéìòù]+", "accents").unwrap();
+        t.add("😀", "smile").unwrap();
+
+        assert_eq!(t.get("èé").unwrap(), Some(&"accents"));
+        assert_eq!(t.get("😀").unwrap(), Some(&"smile"));
+        assert_eq!(t.get("😀😀").unwrap(), None);
+    }
+
+    #[test]
+    fn unicode_invalid_input() {
+        let mut t = UnicodeTable::new("àb");
+        assert_eq!(t.add("àc", 1), Err(TableError::InvalidInput('c')));
+        assert_eq!(t.get("ü"), Err(TableError::InvalidInput('ü')));
+    }
+
+    #[test]
+    fn unicode_lexer_spans() {
+        let mut t = UnicodeTable::new("abcàèé😀 ");
+        t.add("[abcàèé]+", "word").unwrap();
+        t.add("😀", "emoji").unwrap();
+        t.add(" +", "space").unwrap();
+
+        let tokens: Vec<_> = t.lexer("càbè 😀é").collect::<Result<_, _>>().unwrap();
+        assert_eq!(
+            tokens,
+            vec![
+                (&"word", "càbè"),
+                (&"space", " "),
+                (&"emoji", "😀"),
+                (&"word", "é"),
+            ]
+        );
+    }
+
+    #[test]
+    fn unicode_lexer_errors_use_byte_offsets() {
+        let mut t = UnicodeTable::new("àb");
+        t.add("[àb]+", "word").unwrap();
+
+        let mut iter = t.lexer("àbü");
+        assert_eq!(
+            iter.next().unwrap(),
+            Err(LexerError::UnknownChar {
+                char: 'ü',
+                position: 3
+            })
+        );
+        assert!(iter.next().is_none());
+
+        let mut t = UnicodeTable::new("àb");
+        t.add("àà", "pair").unwrap();
+        let mut iter = t.lexer("àb");
+        assert_eq!(
+            iter.next().unwrap(),
+            Err(LexerError::UnexpectedEnd { position: 0 })
+        );
+    }
+
+    #[test]
+    fn unicode_duplicate_value() {
+        let mut t = UnicodeTable::new("é");
+        t.add("é", 1).unwrap();
+        assert!(matches!(
+            t.add("é", 2),
+            Err(TableError::ValueAlreadyDefined { .. })
+        ));
+    }
+}